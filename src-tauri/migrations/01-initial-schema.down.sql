@@ -0,0 +1,12 @@
+-- Drop everything the initial schema created, dependents first
+DROP TRIGGER IF EXISTS pages_update_view_stats;
+DROP TRIGGER IF EXISTS pages_update_timestamp;
+DROP TRIGGER IF EXISTS pages_au;
+DROP TRIGGER IF EXISTS pages_ad;
+DROP TRIGGER IF EXISTS pages_ai;
+
+DROP TABLE IF EXISTS pages_fts;
+DROP TABLE IF EXISTS image_attachments;
+DROP TABLE IF EXISTS tag_associations;
+DROP TABLE IF EXISTS tags;
+DROP TABLE IF EXISTS pages;
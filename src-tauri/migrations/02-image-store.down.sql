@@ -0,0 +1,5 @@
+-- Images that were already moved to the image store have an empty `data` column, so an
+-- older version of the app can't show them. Restore the pre-migration copy to get them back.
+DROP INDEX IF EXISTS idx_image_attachments_content_hash;
+
+ALTER TABLE image_attachments DROP COLUMN content_hash;
@@ -0,0 +1,5 @@
+-- Image bytes live in the content-addressed store under the app data dir.
+-- `data` is emptied once an image has been moved to the store.
+ALTER TABLE image_attachments ADD COLUMN content_hash TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_image_attachments_content_hash ON image_attachments(content_hash);
@@ -0,0 +1,6 @@
+-- Which version of the app last opened the database, and the schema version it left it at.
+-- An older version that can't open the database uses this to say which version to update to.
+CREATE TABLE IF NOT EXISTS app_metadata (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,35 @@
+-- Go back to the contentless search index without tags
+DROP TRIGGER IF EXISTS tags_search_au;
+DROP TRIGGER IF EXISTS tag_associations_search_ad;
+DROP TRIGGER IF EXISTS tag_associations_search_ai;
+DROP TRIGGER IF EXISTS pages_au;
+DROP TRIGGER IF EXISTS pages_ad;
+DROP TRIGGER IF EXISTS pages_bd;
+DROP TRIGGER IF EXISTS pages_ai;
+DROP TABLE IF EXISTS pages_fts;
+DROP VIEW IF EXISTS pages_search;
+DROP TABLE IF EXISTS page_search_tags;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS pages_fts USING fts5(
+    title,
+    plain_text,
+    content='',
+    contentless_delete=1
+);
+
+INSERT INTO pages_fts(rowid, title, plain_text)
+SELECT id, title, plain_text FROM pages;
+
+CREATE TRIGGER IF NOT EXISTS pages_ai AFTER INSERT ON pages BEGIN
+    INSERT INTO pages_fts(rowid, title, plain_text)
+    VALUES (new.id, new.title, new.plain_text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_ad AFTER DELETE ON pages BEGIN
+    DELETE FROM pages_fts WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_au AFTER UPDATE ON pages BEGIN
+    INSERT OR REPLACE INTO pages_fts(rowid, title, plain_text)
+    VALUES (new.id, new.title, new.plain_text);
+END;
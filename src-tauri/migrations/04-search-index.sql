@@ -0,0 +1,111 @@
+-- Replace the contentless search index with one that reads its text from the
+-- `pages_search` view, so search can return snippets and highlights, and that also
+-- indexes each page's tags.
+
+DROP TRIGGER IF EXISTS pages_ai;
+DROP TRIGGER IF EXISTS pages_ad;
+DROP TRIGGER IF EXISTS pages_au;
+DROP TABLE IF EXISTS pages_fts;
+
+-- The tags of each page as last indexed, space separated. An external content index
+-- has to be given exactly the values it indexed to remove a row, so the tags are kept
+-- here rather than worked out from `tag_associations`, which has already changed by the
+-- time a trigger runs.
+CREATE TABLE IF NOT EXISTS page_search_tags (
+    page_id INTEGER PRIMARY KEY,
+    tags TEXT NOT NULL DEFAULT ''
+);
+
+INSERT INTO page_search_tags (page_id, tags)
+SELECT p.id, COALESCE((
+    SELECT group_concat(t.tag, ' ') FROM tag_associations ta
+    JOIN tags t ON t.id = ta.tag_id
+    WHERE ta.page_id = p.id
+), '')
+FROM pages p;
+
+CREATE VIEW IF NOT EXISTS pages_search AS
+SELECT p.id, p.title, p.plain_text, COALESCE(st.tags, '') AS tags
+FROM pages p
+LEFT JOIN page_search_tags st ON st.page_id = p.id;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS pages_fts USING fts5(
+    title,
+    plain_text,
+    tags,
+    content='pages_search',
+    content_rowid='id'
+);
+
+INSERT INTO pages_fts(pages_fts) VALUES('rebuild');
+
+-- Keep the index up to date with pages
+CREATE TRIGGER IF NOT EXISTS pages_ai AFTER INSERT ON pages BEGIN
+    INSERT INTO pages_fts(rowid, title, plain_text, tags)
+    VALUES (new.id, new.title, new.plain_text, '');
+END;
+
+-- Runs before the page's tag associations are deleted along with it
+CREATE TRIGGER IF NOT EXISTS pages_bd BEFORE DELETE ON pages BEGIN
+    INSERT INTO pages_fts(pages_fts, rowid, title, plain_text, tags)
+    SELECT 'delete', id, title, plain_text, tags FROM pages_search WHERE id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_ad AFTER DELETE ON pages BEGIN
+    DELETE FROM page_search_tags WHERE page_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_au AFTER UPDATE OF title, plain_text ON pages BEGIN
+    INSERT INTO pages_fts(pages_fts, rowid, title, plain_text, tags)
+    VALUES ('delete', old.id, old.title, old.plain_text,
+            COALESCE((SELECT tags FROM page_search_tags WHERE page_id = old.id), ''));
+    INSERT INTO pages_fts(rowid, title, plain_text, tags)
+    SELECT id, title, plain_text, tags FROM pages_search WHERE id = new.id;
+END;
+
+-- Keep the index up to date with tags. Each trigger takes the page out of the index,
+-- updates its tags, and puts it back. Pages that are being deleted are skipped, since
+-- `pages_search` no longer has them.
+CREATE TRIGGER IF NOT EXISTS tag_associations_search_ai AFTER INSERT ON tag_associations BEGIN
+    INSERT INTO pages_fts(pages_fts, rowid, title, plain_text, tags)
+    SELECT 'delete', id, title, plain_text, tags FROM pages_search WHERE id = new.page_id;
+    INSERT OR REPLACE INTO page_search_tags (page_id, tags)
+    SELECT p.id, COALESCE((
+        SELECT group_concat(t.tag, ' ') FROM tag_associations ta
+        JOIN tags t ON t.id = ta.tag_id
+        WHERE ta.page_id = p.id
+    ), '')
+    FROM pages p WHERE p.id = new.page_id;
+    INSERT INTO pages_fts(rowid, title, plain_text, tags)
+    SELECT id, title, plain_text, tags FROM pages_search WHERE id = new.page_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS tag_associations_search_ad AFTER DELETE ON tag_associations BEGIN
+    INSERT INTO pages_fts(pages_fts, rowid, title, plain_text, tags)
+    SELECT 'delete', id, title, plain_text, tags FROM pages_search WHERE id = old.page_id;
+    INSERT OR REPLACE INTO page_search_tags (page_id, tags)
+    SELECT p.id, COALESCE((
+        SELECT group_concat(t.tag, ' ') FROM tag_associations ta
+        JOIN tags t ON t.id = ta.tag_id
+        WHERE ta.page_id = p.id
+    ), '')
+    FROM pages p WHERE p.id = old.page_id;
+    INSERT INTO pages_fts(rowid, title, plain_text, tags)
+    SELECT id, title, plain_text, tags FROM pages_search WHERE id = old.page_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS tags_search_au AFTER UPDATE OF tag ON tags BEGIN
+    INSERT INTO pages_fts(pages_fts, rowid, title, plain_text, tags)
+    SELECT 'delete', id, title, plain_text, tags FROM pages_search
+    WHERE id IN (SELECT page_id FROM tag_associations WHERE tag_id = new.id);
+    INSERT OR REPLACE INTO page_search_tags (page_id, tags)
+    SELECT ta.page_id, (
+        SELECT group_concat(t.tag, ' ') FROM tag_associations ta2
+        JOIN tags t ON t.id = ta2.tag_id
+        WHERE ta2.page_id = ta.page_id
+    )
+    FROM tag_associations ta WHERE ta.tag_id = new.id;
+    INSERT INTO pages_fts(rowid, title, plain_text, tags)
+    SELECT id, title, plain_text, tags FROM pages_search
+    WHERE id IN (SELECT page_id FROM tag_associations WHERE tag_id = new.id);
+END;
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS pages_title_au;
+DROP TRIGGER IF EXISTS pages_title_ad;
+DROP TRIGGER IF EXISTS pages_title_ai;
+DROP TABLE IF EXISTS pages_title_fts;
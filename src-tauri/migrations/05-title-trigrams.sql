@@ -0,0 +1,24 @@
+-- Trigram index of page titles for typo-tolerant title search. Titles that share
+-- three-letter sequences with the query are the candidates the fuzzy matcher scores.
+-- Accents are ignored, like the matcher does, so "resume" finds "Résumé".
+CREATE VIRTUAL TABLE IF NOT EXISTS pages_title_fts USING fts5(
+    title,
+    content='pages',
+    content_rowid='id',
+    tokenize='trigram remove_diacritics 1'
+);
+
+INSERT INTO pages_title_fts(pages_title_fts) VALUES('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS pages_title_ai AFTER INSERT ON pages BEGIN
+    INSERT INTO pages_title_fts(rowid, title) VALUES (new.id, new.title);
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_title_ad AFTER DELETE ON pages BEGIN
+    INSERT INTO pages_title_fts(pages_title_fts, rowid, title) VALUES ('delete', old.id, old.title);
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_title_au AFTER UPDATE OF title ON pages BEGIN
+    INSERT INTO pages_title_fts(pages_title_fts, rowid, title) VALUES ('delete', old.id, old.title);
+    INSERT INTO pages_title_fts(rowid, title) VALUES (new.id, new.title);
+END;
@@ -0,0 +1,14 @@
+-- Back to the default unicode61 tokenizer, whichever tokenizer the vault had chosen
+DELETE FROM app_metadata WHERE key = 'search_tokenizer';
+
+DROP TABLE IF EXISTS pages_fts;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS pages_fts USING fts5(
+    title,
+    plain_text,
+    tags,
+    content='pages_search',
+    content_rowid='id'
+);
+
+INSERT INTO pages_fts(pages_fts) VALUES('rebuild');
@@ -0,0 +1,16 @@
+-- Rebuild the search index with porter stemming and without accents, so "meeting" finds
+-- "meetings" and "cafe" finds "café". The triggers from 04 keep working, since they only
+-- refer to the index by name. Vaults can switch to trigram tokenization for Chinese,
+-- Japanese and Korean text later (see search_index.rs).
+DROP TABLE IF EXISTS pages_fts;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS pages_fts USING fts5(
+    title,
+    plain_text,
+    tags,
+    content='pages_search',
+    content_rowid='id',
+    tokenize='porter unicode61 remove_diacritics 2'
+);
+
+INSERT INTO pages_fts(pages_fts) VALUES('rebuild');
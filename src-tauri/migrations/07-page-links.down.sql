@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS pages_links_au;
+DROP TRIGGER IF EXISTS pages_links_ai;
+DROP INDEX IF EXISTS idx_page_links_target_page_id;
+DROP TABLE IF EXISTS page_links;
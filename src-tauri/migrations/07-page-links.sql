@@ -0,0 +1,46 @@
+-- Links between pages, so a page's backlinks can be found without reading every page.
+-- Each internal link node (see InternalLinkNode.tsx) in a page's `lexical_json` is one row.
+-- Links to pages that don't exist are kept, since the page they point to may be restored.
+CREATE TABLE IF NOT EXISTS page_links (
+    source_page_id INTEGER NOT NULL,
+    target_page_id INTEGER NOT NULL,
+    -- Which of the source page's links this is, counting from 0 in document order
+    position INTEGER NOT NULL,
+    -- Where the link node is in the source page's `lexical_json`, as a JSON path
+    path TEXT NOT NULL,
+    PRIMARY KEY (source_page_id, position),
+    FOREIGN KEY (source_page_id) REFERENCES pages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_page_links_target_page_id ON page_links(target_page_id);
+
+-- Parse the links out of the pages that already exist
+INSERT INTO page_links (source_page_id, target_page_id, position, path)
+SELECT p.id, json_extract(node.value, '$.pageId'),
+       row_number() OVER (PARTITION BY p.id ORDER BY node.id) - 1, node.fullkey
+FROM pages p,
+     json_tree(CASE WHEN json_valid(p.lexical_json) THEN p.lexical_json ELSE '{}' END, '$.root') node
+WHERE node.type = 'object'
+  AND json_extract(node.value, '$.type') = 'internal-link'
+  AND json_extract(node.value, '$.pageId') IS NOT NULL;
+
+-- Keep them up to date as pages are written. Pages that aren't valid JSON have no links.
+CREATE TRIGGER IF NOT EXISTS pages_links_ai AFTER INSERT ON pages BEGIN
+    INSERT INTO page_links (source_page_id, target_page_id, position, path)
+    SELECT new.id, json_extract(value, '$.pageId'), row_number() OVER (ORDER BY id) - 1, fullkey
+    FROM json_tree(CASE WHEN json_valid(new.lexical_json) THEN new.lexical_json ELSE '{}' END, '$.root')
+    WHERE type = 'object'
+      AND json_extract(value, '$.type') = 'internal-link'
+      AND json_extract(value, '$.pageId') IS NOT NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_links_au AFTER UPDATE OF lexical_json ON pages
+WHEN old.lexical_json IS NOT new.lexical_json BEGIN
+    DELETE FROM page_links WHERE source_page_id = new.id;
+    INSERT INTO page_links (source_page_id, target_page_id, position, path)
+    SELECT new.id, json_extract(value, '$.pageId'), row_number() OVER (ORDER BY id) - 1, fullkey
+    FROM json_tree(CASE WHEN json_valid(new.lexical_json) THEN new.lexical_json ELSE '{}' END, '$.root')
+    WHERE type = 'object'
+      AND json_extract(value, '$.type') = 'internal-link'
+      AND json_extract(value, '$.pageId') IS NOT NULL;
+END;
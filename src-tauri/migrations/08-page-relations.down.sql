@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_page_relations_target_page_id;
+DROP TABLE IF EXISTS page_relations;
@@ -0,0 +1,18 @@
+-- Relationships between pages, such as parent/child and peer. A relation has a type and is
+-- either directed, from `source_page_id` to `target_page_id`, or undirected, in which case
+-- `source_page_id` is the lower of the two IDs. In a `parent` relation the source is the
+-- parent of the target.
+CREATE TABLE IF NOT EXISTS page_relations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_page_id INTEGER NOT NULL,
+    target_page_id INTEGER NOT NULL,
+    relation_type TEXT NOT NULL,
+    directed INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (source_page_id, target_page_id, relation_type),
+    CHECK (source_page_id != target_page_id),
+    FOREIGN KEY (source_page_id) REFERENCES pages(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_page_id) REFERENCES pages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_page_relations_target_page_id ON page_relations(target_page_id);
@@ -0,0 +1,4 @@
+-- Fleeting pages become permanent
+DROP INDEX IF EXISTS idx_pages_expires_at;
+
+ALTER TABLE pages DROP COLUMN expires_at;
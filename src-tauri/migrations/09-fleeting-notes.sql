@@ -0,0 +1,5 @@
+-- Fleeting notes: pages with an `expires_at` are moved to the trash by a background job
+-- once that time has passed (see fleeting_commands.rs). NULL means the page is permanent.
+ALTER TABLE pages ADD COLUMN expires_at TIMESTAMP NULL;
+
+CREATE INDEX IF NOT EXISTS idx_pages_expires_at ON pages(expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,6 @@
+-- Relevancy dates and the "Resurfaced" queue are lost
+DROP TABLE IF EXISTS resurfaced_pages;
+
+DROP INDEX IF EXISTS idx_pages_resurface_at;
+
+ALTER TABLE pages DROP COLUMN resurface_at;
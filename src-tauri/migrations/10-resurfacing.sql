@@ -0,0 +1,12 @@
+-- Relevancy dates: once a page's `resurface_at` passes, a background job moves it to the
+-- "Resurfaced" queue (see resurface_commands.rs), where it stays until it's dismissed or
+-- snoozed to a later date.
+ALTER TABLE pages ADD COLUMN resurface_at TIMESTAMP NULL;
+
+CREATE INDEX IF NOT EXISTS idx_pages_resurface_at ON pages(resurface_at) WHERE resurface_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS resurfaced_pages (
+    page_id INTEGER PRIMARY KEY,
+    resurfaced_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE
+);
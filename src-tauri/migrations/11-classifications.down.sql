@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_page_classifications_classification_id;
+DROP TABLE IF EXISTS page_classifications;
+DROP INDEX IF EXISTS idx_classifications_parent_id;
+DROP TABLE IF EXISTS classifications;
@@ -0,0 +1,25 @@
+-- A Johnny.Decimal-style index: areas ("10-19"), categories within them ("11") and IDs
+-- within categories ("11.01"), each with a title. Pages are assigned to a classification
+-- by its `id`, so renumbering a code doesn't touch its pages (see
+-- classification_commands.rs).
+CREATE TABLE IF NOT EXISTS classifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    parent_id INTEGER NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (parent_id) REFERENCES classifications(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_classifications_parent_id ON classifications(parent_id);
+
+-- A page belongs to at most one classification
+CREATE TABLE IF NOT EXISTS page_classifications (
+    page_id INTEGER PRIMARY KEY,
+    classification_id INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE,
+    FOREIGN KEY (classification_id) REFERENCES classifications(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_page_classifications_classification_id ON page_classifications(classification_id);
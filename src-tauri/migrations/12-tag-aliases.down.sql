@@ -0,0 +1,5 @@
+-- Aliases become tags of their own, with no pages
+DROP TRIGGER IF EXISTS tag_associations_alias_ai;
+DROP INDEX IF EXISTS idx_tags_alias_of;
+
+ALTER TABLE tags DROP COLUMN alias_of;
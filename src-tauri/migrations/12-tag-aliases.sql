@@ -0,0 +1,16 @@
+-- Tag aliases: a tag with `alias_of` set stands for another tag. Tagging a page with an
+-- alias tags it with the tag it stands for instead, whichever way the tag is added, so
+-- aliases never have pages of their own (see tag_commands.rs). A single INSERT that adds
+-- both an alias and its tag to a page fails on the primary key, so writers resolve
+-- aliases first where they can.
+ALTER TABLE tags ADD COLUMN alias_of INTEGER NULL REFERENCES tags(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_tags_alias_of ON tags(alias_of) WHERE alias_of IS NOT NULL;
+
+CREATE TRIGGER IF NOT EXISTS tag_associations_alias_ai AFTER INSERT ON tag_associations
+WHEN (SELECT alias_of FROM tags WHERE id = new.tag_id) IS NOT NULL
+BEGIN
+    INSERT OR IGNORE INTO tag_associations (page_id, tag_id, created_at)
+    SELECT new.page_id, alias_of, new.created_at FROM tags WHERE id = new.tag_id;
+    DELETE FROM tag_associations WHERE page_id = new.page_id AND tag_id = new.tag_id;
+END;
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS pages_graph_archive_au;
+DROP TRIGGER IF EXISTS tag_associations_graph_ad;
+DROP TRIGGER IF EXISTS tag_associations_graph_ai;
+DROP TABLE IF EXISTS tag_graph_cache;
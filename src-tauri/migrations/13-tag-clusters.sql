@@ -0,0 +1,26 @@
+-- The tag co-occurrence graph and its clusters are expensive to work out, so the last result
+-- is kept here (see tag_cluster_commands.rs). Triggers bump `version` whenever pages gain or
+-- lose tags or go in or out of the trash; the cache is stale when `cached_version` doesn't
+-- match.
+CREATE TABLE IF NOT EXISTS tag_graph_cache (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    version INTEGER NOT NULL DEFAULT 0,
+    cached_version INTEGER NULL,
+    graph TEXT NULL
+);
+
+INSERT OR IGNORE INTO tag_graph_cache (id) VALUES (1);
+
+CREATE TRIGGER IF NOT EXISTS tag_associations_graph_ai AFTER INSERT ON tag_associations BEGIN
+    UPDATE tag_graph_cache SET version = version + 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS tag_associations_graph_ad AFTER DELETE ON tag_associations BEGIN
+    UPDATE tag_graph_cache SET version = version + 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_graph_archive_au AFTER UPDATE OF archived_at ON pages
+WHEN old.archived_at IS NOT new.archived_at
+BEGIN
+    UPDATE tag_graph_cache SET version = version + 1;
+END;
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS pages_title_version_au;
+DROP TRIGGER IF EXISTS pages_title_version_ad;
+DROP TRIGGER IF EXISTS pages_title_version_ai;
+DROP TABLE IF EXISTS title_index_version;
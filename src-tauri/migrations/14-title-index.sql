@@ -0,0 +1,25 @@
+-- Fuzzy title search matches against a copy of every title kept in memory (see
+-- search_commands.rs). `version` is set to a new random value whenever a page is added,
+-- deleted, renamed, or goes in or out of the trash, so the copy can tell it's stale. It's
+-- random rather than counted so another vault's database, or a restored snapshot, never
+-- looks like the one the copy was made from.
+CREATE TABLE IF NOT EXISTS title_index_version (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    version INTEGER NOT NULL
+);
+
+INSERT OR IGNORE INTO title_index_version (id, version) VALUES (1, random());
+
+CREATE TRIGGER IF NOT EXISTS pages_title_version_ai AFTER INSERT ON pages BEGIN
+    UPDATE title_index_version SET version = random();
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_title_version_ad AFTER DELETE ON pages BEGIN
+    UPDATE title_index_version SET version = random();
+END;
+
+CREATE TRIGGER IF NOT EXISTS pages_title_version_au AFTER UPDATE OF title, archived_at ON pages
+WHEN old.title IS NOT new.title OR old.archived_at IS NOT new.archived_at
+BEGIN
+    UPDATE title_index_version SET version = random();
+END;
@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS page_activity_au;
+DROP TRIGGER IF EXISTS page_activity_ai;
+DROP INDEX IF EXISTS idx_page_activity_page_id;
+DROP TABLE IF EXISTS page_activity;
+
+ALTER TABLE pages DROP COLUMN word_count;
@@ -0,0 +1,40 @@
+-- Writing activity per page and local day, for the words-written and pages-updated series
+-- in stats_commands.rs. `pages.word_count` is set by whoever writes `plain_text` (pages
+-- saved before this migration are counted by `stats_commands::backfill_word_counts`), and
+-- the triggers add each change in it to the day it happened on. Only edits that change a
+-- page's text count as updates.
+ALTER TABLE pages ADD COLUMN word_count INTEGER NULL;
+
+CREATE TABLE IF NOT EXISTS page_activity (
+    day TEXT NOT NULL,
+    page_id INTEGER NOT NULL,
+    words_delta INTEGER NOT NULL DEFAULT 0,
+    edits INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, page_id),
+    FOREIGN KEY (page_id) REFERENCES pages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_page_activity_page_id ON page_activity(page_id);
+
+CREATE TRIGGER IF NOT EXISTS page_activity_ai AFTER INSERT ON pages
+WHEN new.word_count > 0
+BEGIN
+    INSERT INTO page_activity (day, page_id, words_delta)
+    VALUES (date('now', 'localtime'), new.id, new.word_count)
+    ON CONFLICT (day, page_id) DO UPDATE SET words_delta = words_delta + excluded.words_delta;
+END;
+
+CREATE TRIGGER IF NOT EXISTS page_activity_au AFTER UPDATE OF plain_text ON pages
+WHEN old.plain_text IS NOT new.plain_text
+BEGIN
+    INSERT INTO page_activity (day, page_id, words_delta, edits)
+    VALUES (
+        date('now', 'localtime'),
+        new.id,
+        COALESCE(new.word_count - old.word_count, 0),
+        1
+    )
+    ON CONFLICT (day, page_id) DO UPDATE SET
+        words_delta = words_delta + excluded.words_delta,
+        edits = edits + 1;
+END;
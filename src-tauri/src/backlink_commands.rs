@@ -0,0 +1,140 @@
+// Backlinks: the pages that link to a page. Links are kept in `page_links` by triggers on
+// `pages` (see migrations/07-page-links.sql); the text around each link is read from the
+// linking page when it's asked for.
+
+use crate::db::get_pool;
+use crate::lexical::internal_link_context;
+use crate::search_commands::{HighlightedText, MatchRange};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// How much text to show on each side of a link, in characters
+const CONTEXT_CHARS: usize = 80;
+const CONTEXT_ELLIPSIS: &str = "…";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backlink {
+    /// The linking page
+    pub page_id: i64,
+    pub title: String,
+    pub filename: String,
+    /// Which of the linking page's links this is, counting from 0
+    pub position: i64,
+    /// Text around the link, with the link itself as the match
+    pub context: HighlightedText,
+}
+
+#[derive(sqlx::FromRow)]
+struct LinkRow {
+    source_page_id: i64,
+    title: String,
+    filename: String,
+    lexical_json: String,
+    position: i64,
+    path: String,
+}
+
+/// Cuts `text` down to `CONTEXT_CHARS` on each side of `link`, on one line
+fn trim_context(text: &str, link: Range<usize>) -> HighlightedText {
+    let start = text[..link.start]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[link.end..]
+        .char_indices()
+        .nth(CONTEXT_CHARS)
+        .map_or(text.len(), |(i, _)| link.end + i);
+
+    let mut context = String::new();
+    if start > 0 {
+        context.push_str(CONTEXT_ELLIPSIS);
+    }
+    context.push_str(&text[start..link.start]);
+    let match_start = context.encode_utf16().count();
+    context.push_str(&text[link.clone()]);
+    let match_end = context.encode_utf16().count();
+    context.push_str(&text[link.end..end]);
+    if end < text.len() {
+        context.push_str(CONTEXT_ELLIPSIS);
+    }
+
+    HighlightedText {
+        // Line breaks and tabs are one UTF-16 code unit, like the spaces they become
+        text: context.replace(['\n', '\t'], " "),
+        matches: vec![MatchRange {
+            start: match_start,
+            end: match_end,
+        }],
+    }
+}
+
+/// Returns the links to `page_id` from other pages that aren't archived, most recently
+/// updated pages first
+pub async fn backlinks(pool: &SqlitePool, page_id: i64) -> Result<Vec<Backlink>, sqlx::Error> {
+    let rows: Vec<LinkRow> = sqlx::query_as(
+        "SELECT pl.source_page_id, p.title, p.filename, p.lexical_json, pl.position, pl.path
+         FROM page_links pl
+         JOIN pages p ON p.id = pl.source_page_id
+         WHERE pl.target_page_id = ? AND pl.source_page_id != ? AND p.archived_at IS NULL
+         ORDER BY p.updated_at DESC, p.id, pl.position",
+    )
+    .bind(page_id)
+    .bind(page_id)
+    .fetch_all(pool)
+    .await?;
+
+    // Titles of every page the linking pages link to, for writing links in the context
+    let titles: HashMap<i64, String> = sqlx::query_as::<_, (i64, String)>(
+        "SELECT DISTINCT p.id, p.title FROM page_links pl
+         JOIN pages p ON p.id = pl.target_page_id
+         WHERE pl.source_page_id IN (SELECT source_page_id FROM page_links WHERE target_page_id = ?)",
+    )
+    .bind(page_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut states: HashMap<i64, Option<JsonValue>> = HashMap::new();
+    let backlinks = rows
+        .into_iter()
+        .map(|row| {
+            let state = states
+                .entry(row.source_page_id)
+                .or_insert_with(|| serde_json::from_str(&row.lexical_json).ok());
+            let context = state
+                .as_ref()
+                .and_then(|state| internal_link_context(state, &row.path, &titles))
+                .map(|(text, link)| trim_context(&text, link))
+                .unwrap_or(HighlightedText {
+                    text: String::new(),
+                    matches: Vec::new(),
+                });
+            Backlink {
+                page_id: row.source_page_id,
+                title: row.title,
+                filename: row.filename,
+                position: row.position,
+                context,
+            }
+        })
+        .collect();
+    Ok(backlinks)
+}
+
+// Command to list the links to a page from other pages, with the text around each link
+#[tauri::command]
+pub async fn get_backlinks(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+) -> Result<Vec<Backlink>, String> {
+    let pool = get_pool(&app_handle).await?;
+    backlinks(&pool, page_id)
+        .await
+        .map_err(|e| format!("Failed to get backlinks: {}", e))
+}
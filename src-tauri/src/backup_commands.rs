@@ -0,0 +1,589 @@
+use crate::db::{
+    connect_options, current_key, export_database, get_pool, is_encrypted, latest_schema_version,
+    reload_windows, replace_database_file, with_database_closed,
+};
+use crate::image_store::ImageStore;
+use crate::settings::{get_string_setting, get_u64_setting};
+use crate::vault::{current_vault_dir, is_default_vault};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc};
+use serde::Serialize;
+use sqlx::{ConnectOptions, SqlitePool};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Emitter;
+
+/// Settings key for the directory snapshots are written to
+pub const BACKUP_PATH_KEY: &str = "backup_path";
+/// Settings key for the hours between scheduled backups (0 disables them)
+pub const BACKUP_INTERVAL_HOURS_KEY: &str = "backup_interval_hours";
+pub const DEFAULT_BACKUP_INTERVAL_HOURS: u64 = 24;
+/// Settings keys for how many daily and weekly snapshots to keep
+pub const BACKUP_KEEP_DAILY_KEY: &str = "backup_keep_daily";
+pub const DEFAULT_BACKUP_KEEP_DAILY: u64 = 7;
+pub const BACKUP_KEEP_WEEKLY_KEY: &str = "backup_keep_weekly";
+pub const DEFAULT_BACKUP_KEEP_WEEKLY: u64 = 4;
+
+const SNAPSHOT_PREFIX: &str = "notesponge-";
+const SNAPSHOT_EXTENSION: &str = ".db";
+const SNAPSHOT_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// Safety copies taken before a restore or a schema change use their own prefixes, so each
+/// kind is rotated separately and never pushes out regular snapshots
+const PRE_RESTORE_PREFIX: &str = "pre-restore-";
+const PRE_MIGRATION_PREFIX: &str = "pre-migration-";
+const ALL_PREFIXES: [&str; 3] = [SNAPSHOT_PREFIX, PRE_RESTORE_PREFIX, PRE_MIGRATION_PREFIX];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub file_name: String,
+    pub path: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+    /// Whether restoring the snapshot may need the passphrase it was taken with
+    pub encrypted: bool,
+    /// None if the snapshot couldn't be opened, e.g. because it was taken with an earlier
+    /// passphrase
+    pub page_count: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreResult {
+    pub restored_from: String,
+    /// Copy of the database as it was before the restore
+    pub safety_copy_path: String,
+    pub schema_version: i64,
+    /// Images the snapshot refers to that were neither in the image store nor saved with the
+    /// snapshot, so they're missing from the restored database
+    pub missing_images: Vec<String>,
+}
+
+/// Returns the directory the open vault is backed up to. That's `<vault>/backups`, except
+/// that the default vault uses the backup directory from settings if one is set. Other vaults
+/// ignore that setting so their snapshots never get mixed up with the default vault's.
+pub fn backup_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let vault_dir = current_vault_dir(app_handle)?;
+    let custom_dir = get_string_setting(app_handle, BACKUP_PATH_KEY)
+        .map(PathBuf::from)
+        .filter(|_| is_default_vault(app_handle, &vault_dir));
+    let dir = custom_dir.unwrap_or_else(|| vault_dir.join("backups"));
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Reads the time and sequence number out of a snapshot name like
+/// `notesponge-20250101T120000Z.db`, or `notesponge-20250101T120000Z-2.db` for the second
+/// snapshot taken that second
+fn parse_snapshot_name(file_name: &str, prefix: &str) -> Option<(DateTime<Utc>, u32)> {
+    let stem = file_name
+        .strip_prefix(prefix)?
+        .strip_suffix(SNAPSHOT_EXTENSION)?;
+    let (timestamp, sequence) = match stem.split_once('-') {
+        Some((timestamp, sequence)) => (timestamp, sequence.parse().ok()?),
+        None => (stem, 1),
+    };
+    let naive = NaiveDateTime::parse_from_str(timestamp, SNAPSHOT_TIMESTAMP_FORMAT).ok()?;
+    Some((naive.and_utc(), sequence))
+}
+
+/// Lists snapshot files in `dir` whose names start with `prefix`, newest first
+fn snapshot_files(dir: &Path, prefix: &str) -> Result<Vec<(PathBuf, DateTime<Utc>)>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read backup directory: {}", e))?;
+
+    let mut snapshots: Vec<(PathBuf, DateTime<Utc>, u32)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let (created_at, sequence) =
+                parse_snapshot_name(&entry.file_name().to_string_lossy(), prefix)?;
+            Some((entry.path(), created_at, sequence))
+        })
+        .collect();
+    snapshots.sort_by_key(|(_, created_at, sequence)| Reverse((*created_at, *sequence)));
+    Ok(snapshots
+        .into_iter()
+        .map(|(path, created_at, _)| (path, created_at))
+        .collect())
+}
+
+/// The directory next to a snapshot that holds the images it refers to
+fn snapshot_images_dir(snapshot_path: &Path) -> PathBuf {
+    snapshot_path.with_extension("images")
+}
+
+/// Content hashes of the images a database file refers to. Databases from before the image
+/// store keep their images inline, so they refer to none.
+async fn referenced_images(path: &Path, key: Option<&str>) -> Result<Vec<String>, String> {
+    let key = key.filter(|_| is_encrypted(path));
+    let mut conn = connect_options(path, key)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to open snapshot: {}", e))?;
+
+    let has_image_store: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('image_attachments') WHERE name = 'content_hash'",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to read snapshot: {}", e))?;
+    if !has_image_store {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_scalar(
+        "SELECT DISTINCT content_hash FROM image_attachments WHERE content_hash IS NOT NULL",
+    )
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|e| format!("Failed to read snapshot images: {}", e))
+}
+
+/// Saves the images a snapshot refers to next to it, so they survive the image store sweeping
+/// them. Files are hard-linked where the backup directory is on the same volume and copied
+/// otherwise.
+fn save_snapshot_images(
+    store: &ImageStore,
+    snapshot_path: &Path,
+    content_hashes: &[String],
+) -> Result<(), String> {
+    let dir = snapshot_images_dir(snapshot_path);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create snapshot images: {}", e))?;
+    for content_hash in content_hashes {
+        let source = store.path_for(content_hash);
+        let target = dir.join(content_hash);
+        if fs::hard_link(&source, &target).is_err() {
+            if let Err(e) = fs::copy(&source, &target) {
+                eprintln!("Failed to back up image {}: {}", content_hash, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a consistent copy of the live database into `dir` using `VACUUM INTO`, along with
+/// the images it refers to (see `save_snapshot_images`)
+pub async fn create_snapshot(
+    app_handle: &tauri::AppHandle,
+    pool: &SqlitePool,
+    dir: &Path,
+) -> Result<PathBuf, String> {
+    vacuum_into(app_handle, pool, dir, SNAPSHOT_PREFIX).await
+}
+
+/// Saves a copy of the database before its schema is upgraded or downgraded
+pub async fn create_pre_migration_copy(
+    app_handle: &tauri::AppHandle,
+    pool: &SqlitePool,
+) -> Result<PathBuf, String> {
+    let dir = backup_dir(app_handle)?;
+    let path = vacuum_into(app_handle, pool, &dir, PRE_MIGRATION_PREFIX).await?;
+    prune_snapshots(app_handle, &dir)?;
+    Ok(path)
+}
+
+async fn vacuum_into(
+    app_handle: &tauri::AppHandle,
+    pool: &SqlitePool,
+    dir: &Path,
+    prefix: &str,
+) -> Result<PathBuf, String> {
+    // Snapshots taken in the same second get a sequence number
+    let timestamp = Utc::now().format(SNAPSHOT_TIMESTAMP_FORMAT).to_string();
+    let mut path = dir.join(format!("{}{}{}", prefix, timestamp, SNAPSHOT_EXTENSION));
+    let mut sequence = 1;
+    while path.exists() || snapshot_images_dir(&path).exists() {
+        sequence += 1;
+        path = dir.join(format!(
+            "{}{}-{}{}",
+            prefix, timestamp, sequence, SNAPSHOT_EXTENSION
+        ));
+    }
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+
+    // Read from the snapshot rather than the live database, which may have changed since
+    let content_hashes = referenced_images(&path, current_key(app_handle).as_deref()).await?;
+    save_snapshot_images(&ImageStore::current(app_handle)?, &path, &content_hashes)?;
+    Ok(path)
+}
+
+/// Moves every snapshot and safety copy in the backup directory that's on `from_key` (None
+/// for plaintext) over to `to_key`, for when encryption is turned on or off or the passphrase
+/// changes. Plaintext snapshots that can't be encrypted are deleted rather than left readable;
+/// others that can't be converted are left on their passphrase, which `restore_snapshot`
+/// accepts. Returns how many were converted.
+pub async fn rekey_snapshots(
+    app_handle: &tauri::AppHandle,
+    from_key: Option<&str>,
+    to_key: Option<&str>,
+) -> Result<u64, String> {
+    let dir = backup_dir(app_handle)?;
+    let mut converted = 0;
+    for prefix in ALL_PREFIXES {
+        for (path, _) in snapshot_files(&dir, prefix)? {
+            let encrypted = is_encrypted(&path);
+            if encrypted != from_key.is_some() {
+                continue;
+            }
+            let tmp_path = path.with_extension("db.rekey");
+            let result = match export_database(&path, from_key, &tmp_path, to_key).await {
+                Ok(()) => fs::rename(&tmp_path, &path)
+                    .map_err(|e| format!("Failed to replace snapshot: {}", e)),
+                Err(e) => Err(e),
+            };
+            let Err(e) = result else {
+                converted += 1;
+                continue;
+            };
+            let _ = fs::remove_file(&tmp_path);
+            if encrypted {
+                eprintln!("Leaving snapshot {} as it was: {}", path.display(), e);
+                continue;
+            }
+            eprintln!("Deleting snapshot {}: {}", path.display(), e);
+            fs::remove_file(&path).map_err(|e| {
+                format!(
+                    "Failed to delete unencrypted snapshot {}: {}",
+                    path.display(),
+                    e
+                )
+            })?;
+        }
+    }
+    Ok(converted)
+}
+
+/// Picks the snapshots to delete: the newest snapshot of each of the last `keep_daily` days and
+/// of each of the last `keep_weekly` ISO weeks (in local time) are kept, everything else goes.
+/// The newest snapshot is always kept.
+fn snapshots_to_prune(
+    snapshots: &[(PathBuf, DateTime<Utc>)],
+    keep_daily: usize,
+    keep_weekly: usize,
+) -> Vec<PathBuf> {
+    let mut days_seen = HashSet::new();
+    let mut weeks_seen = HashSet::new();
+    let mut prune = Vec::new();
+
+    // Snapshots are sorted newest first, so the first one seen in each period is the newest
+    for (i, (path, created_at)) in snapshots.iter().enumerate() {
+        let local = created_at.with_timezone(&Local);
+        let day = local.date_naive();
+        let week = local.iso_week();
+
+        let mut keep = i == 0;
+        if days_seen.len() < keep_daily && days_seen.insert(day) {
+            keep = true;
+        }
+        if weeks_seen.len() < keep_weekly && weeks_seen.insert(week) {
+            keep = true;
+        }
+        if !keep {
+            prune.push(path.clone());
+        }
+    }
+    prune
+}
+
+/// Deletes snapshots that fall outside the retention settings, with their images. Each kind
+/// of safety copy is rotated the same way, separately from regular snapshots.
+fn prune_snapshots(app_handle: &tauri::AppHandle, dir: &Path) -> Result<(), String> {
+    let keep_daily = get_u64_setting(app_handle, BACKUP_KEEP_DAILY_KEY, DEFAULT_BACKUP_KEEP_DAILY);
+    let keep_weekly = get_u64_setting(
+        app_handle,
+        BACKUP_KEEP_WEEKLY_KEY,
+        DEFAULT_BACKUP_KEEP_WEEKLY,
+    );
+
+    let mut prune = Vec::new();
+    for prefix in ALL_PREFIXES {
+        let snapshots = snapshot_files(dir, prefix)?;
+        prune.extend(snapshots_to_prune(
+            &snapshots,
+            keep_daily as usize,
+            keep_weekly as usize,
+        ));
+    }
+    for path in prune {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Failed to delete snapshot {}: {}", path.display(), e);
+            continue;
+        }
+        let images_dir = snapshot_images_dir(&path);
+        if images_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&images_dir) {
+                eprintln!(
+                    "Failed to delete snapshot images {}: {}",
+                    images_dir.display(),
+                    e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn count_pages(path: &Path, key: Option<&str>) -> Option<i64> {
+    let key = key.filter(|_| is_encrypted(path));
+    let mut conn = connect_options(path, key)
+        .read_only(true)
+        .connect()
+        .await
+        .ok()?;
+    sqlx::query_scalar("SELECT COUNT(*) FROM pages")
+        .fetch_one(&mut conn)
+        .await
+        .ok()
+}
+
+async fn describe_snapshot(path: &Path, created_at: DateTime<Utc>, key: Option<&str>) -> Snapshot {
+    Snapshot {
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        created_at: created_at.to_rfc3339(),
+        size_bytes: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        encrypted: is_encrypted(path),
+        page_count: count_pages(path, key).await,
+    }
+}
+
+async fn back_up(app_handle: &tauri::AppHandle) -> Result<Snapshot, String> {
+    let pool = get_pool(app_handle).await?;
+    let dir = backup_dir(app_handle)?;
+    let path = create_snapshot(app_handle, &pool, &dir).await?;
+    prune_snapshots(app_handle, &dir)?;
+
+    let created_at = parse_snapshot_name(
+        &path.file_name().unwrap_or_default().to_string_lossy(),
+        SNAPSHOT_PREFIX,
+    )
+    .map_or_else(Utc::now, |(created_at, _)| created_at);
+    let key = current_key(app_handle);
+    let snapshot = describe_snapshot(&path, created_at, key.as_deref()).await;
+    println!("Created snapshot: {}", snapshot.path);
+    Ok(snapshot)
+}
+
+/// Background job: backs up if the newest snapshot is older than the backup interval
+pub async fn run_scheduled_backup(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let interval_hours = get_u64_setting(
+        &app_handle,
+        BACKUP_INTERVAL_HOURS_KEY,
+        DEFAULT_BACKUP_INTERVAL_HOURS,
+    );
+    if interval_hours == 0 {
+        return Ok(());
+    }
+
+    let dir = backup_dir(&app_handle)?;
+    let newest = snapshot_files(&dir, SNAPSHOT_PREFIX)?
+        .first()
+        .map(|(_, t)| *t);
+    let due = match newest {
+        Some(t) => Utc::now() - t >= chrono::Duration::hours(interval_hours as i64),
+        None => true,
+    };
+    if due {
+        back_up(&app_handle).await?;
+    }
+    Ok(())
+}
+
+/// Backs up from the "Back Up Now" menu item and tells the frontend how it went
+pub fn spawn_back_up_now(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        match back_up(&app_handle).await {
+            Ok(snapshot) => {
+                let _ = app_handle.emit("backup_created", snapshot);
+            }
+            Err(e) => {
+                eprintln!("Backup failed: {}", e);
+                let _ = app_handle.emit("backup_failed", e);
+            }
+        }
+    });
+}
+
+/// Checks that `path` is an intact NoteSponge database this version of the app can open.
+/// Returns its schema version.
+async fn validate_snapshot(path: &Path, key: Option<&str>) -> Result<i64, String> {
+    if !path.is_file() {
+        return Err(format!("Snapshot {} does not exist", path.display()));
+    }
+
+    let mut conn = connect_options(path, key)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to open snapshot: {}", e))?;
+
+    let problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut conn)
+        .await
+        .map_err(|e| match key {
+            Some(_) => format!("Snapshot can't be opened with this passphrase: {}", e),
+            None => format!("Snapshot is not a valid database: {}", e),
+        })?;
+    if problems.iter().any(|p| p != "ok") {
+        return Err(format!(
+            "Snapshot failed the integrity check: {}",
+            problems.join("; ")
+        ));
+    }
+
+    let schema_version: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|_| "Snapshot is not a NoteSponge database".to_string())?;
+    let Some(schema_version) = schema_version else {
+        return Err("Snapshot is not a NoteSponge database".to_string());
+    };
+    let latest = latest_schema_version();
+    if schema_version > latest {
+        return Err(format!(
+            "Snapshot has schema version {} but this version of NoteSponge only supports up to {}",
+            schema_version, latest
+        ));
+    }
+
+    Ok(schema_version)
+}
+
+/// Puts the images a snapshot refers to back into the image store from the copies saved with
+/// it. Returns the hashes found in neither, whose images will be missing after the restore.
+async fn restore_snapshot_images(
+    store: &ImageStore,
+    snapshot_path: &Path,
+    key: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let saved_dir = snapshot_images_dir(snapshot_path);
+    let mut missing = Vec::new();
+    for content_hash in referenced_images(snapshot_path, key).await? {
+        if store.path_for(&content_hash).exists() {
+            continue;
+        }
+        match fs::read(saved_dir.join(&content_hash)) {
+            Ok(bytes) if store.put(&bytes)? == content_hash => {}
+            _ => missing.push(content_hash),
+        }
+    }
+    Ok(missing)
+}
+
+async fn restore(
+    app_handle: &tauri::AppHandle,
+    snapshot_path: &Path,
+    passphrase: Option<String>,
+) -> Result<RestoreResult, String> {
+    // Snapshots of an encrypted database are encrypted with the passphrase in use at the time.
+    // That's the current one unless the snapshot couldn't be converted when it changed.
+    let key = current_key(app_handle);
+    let snapshot_key = if is_encrypted(snapshot_path) {
+        passphrase.or_else(|| key.clone())
+    } else {
+        None
+    };
+    validate_snapshot(snapshot_path, snapshot_key.as_deref()).await?;
+    let missing_images = restore_snapshot_images(
+        &ImageStore::current(app_handle)?,
+        snapshot_path,
+        snapshot_key.as_deref(),
+    )
+    .await?;
+    if !missing_images.is_empty() {
+        eprintln!(
+            "Snapshot {} refers to {} images that are missing",
+            snapshot_path.display(),
+            missing_images.len()
+        );
+    }
+
+    let pool = get_pool(app_handle).await?;
+    let dir = backup_dir(app_handle)?;
+    let safety_copy = vacuum_into(app_handle, &pool, &dir, PRE_RESTORE_PREFIX).await?;
+    drop(pool);
+    println!("Saved pre-restore copy: {}", safety_copy.display());
+    prune_snapshots(app_handle, &dir)?;
+
+    with_database_closed(app_handle, |db_path| async move {
+        // Go through a temporary file so the database is never left half-written.
+        // A snapshot that isn't on the current passphrase is converted to it on the way in.
+        let tmp_path = db_path.with_extension("db.restore");
+        if snapshot_key == key {
+            fs::copy(snapshot_path, &tmp_path)
+                .map_err(|e| format!("Failed to copy snapshot: {}", e))?;
+        } else {
+            export_database(
+                snapshot_path,
+                snapshot_key.as_deref(),
+                &tmp_path,
+                key.as_deref(),
+            )
+            .await?;
+        }
+        replace_database_file(&tmp_path, &db_path)
+    })
+    .await?;
+
+    let pool = get_pool(app_handle).await?;
+    ImageStore::current(app_handle)?
+        .upgrade_attachments(&pool)
+        .await;
+
+    let schema_version: i64 =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+    reload_windows(app_handle);
+
+    println!("Restored database from {}", snapshot_path.display());
+    Ok(RestoreResult {
+        restored_from: snapshot_path.to_string_lossy().to_string(),
+        safety_copy_path: safety_copy.to_string_lossy().to_string(),
+        schema_version,
+        missing_images,
+    })
+}
+
+// Command to back up the database immediately
+#[tauri::command]
+pub async fn back_up_now(app_handle: tauri::AppHandle) -> Result<Snapshot, String> {
+    back_up(&app_handle).await
+}
+
+// Command to list snapshots in the backup directory, newest first
+#[tauri::command]
+pub async fn list_snapshots(app_handle: tauri::AppHandle) -> Result<Vec<Snapshot>, String> {
+    let dir = backup_dir(&app_handle)?;
+    let key = current_key(&app_handle);
+    let mut snapshots = Vec::new();
+    for (path, created_at) in snapshot_files(&dir, SNAPSHOT_PREFIX)? {
+        snapshots.push(describe_snapshot(&path, created_at, key.as_deref()).await);
+    }
+    Ok(snapshots)
+}
+
+// Command to replace the database with a snapshot. A copy of the current database is saved
+// to the backup directory first, and all windows are reloaded afterwards. `passphrase` is
+// only needed for encrypted snapshots taken with a different passphrase than the current one.
+#[tauri::command]
+pub async fn restore_snapshot(
+    app_handle: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<RestoreResult, String> {
+    restore(&app_handle, Path::new(&path), passphrase).await
+}
@@ -0,0 +1,630 @@
+// A Johnny.Decimal-style index that pages fit into (see migrations/11-classifications.sql).
+// Codes come in three levels: areas ("10-19"), categories ("11", inside area 10-19) and IDs
+// ("11.01", inside category 11). Renumbering or moving a code renumbers everything below it
+// with it; pages stay assigned since they refer to the classification, not its code.
+
+use crate::db::get_pool;
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Settings key for whether `sync_to_directory` puts pages in folders by classification
+pub const SYNC_BY_CLASSIFICATION_KEY: &str = "sync_by_classification";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// The tens digit: `Area(1)` is 10-19
+    Area(u8),
+    Category(u8),
+    /// Category and number within it
+    Id(u8, u8),
+}
+
+impl Code {
+    pub fn parse(code: &str) -> Result<Code, String> {
+        let code = code.trim();
+        let invalid = || {
+            format!(
+                "\"{}\" isn't a classification code like 10-19, 11 or 11.01",
+                code
+            )
+        };
+        let two_digits = |digits: &str| -> Result<u8, String> {
+            if digits.len() == 2 && digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.parse().map_err(|_| invalid())
+            } else {
+                Err(invalid())
+            }
+        };
+
+        if let Some((start, end)) = code.split_once('-') {
+            let (start, end) = (two_digits(start)?, two_digits(end)?);
+            if start % 10 != 0 || end != start + 9 {
+                return Err(format!(
+                    "Area {} should span ten categories, like 10-19",
+                    code
+                ));
+            }
+            Ok(Code::Area(start / 10))
+        } else if let Some((category, number)) = code.split_once('.') {
+            Ok(Code::Id(two_digits(category)?, two_digits(number)?))
+        } else {
+            Ok(Code::Category(two_digits(code)?))
+        }
+    }
+
+    /// The code this one sits under, if any
+    pub fn parent(self) -> Option<Code> {
+        match self {
+            Code::Area(_) => None,
+            Code::Category(category) => Some(Code::Area(category / 10)),
+            Code::Id(category, _) => Some(Code::Category(category)),
+        }
+    }
+
+    fn level_name(self) -> &'static str {
+        match self {
+            Code::Area(_) => "an area",
+            Code::Category(_) => "a category",
+            Code::Id(..) => "an ID",
+        }
+    }
+
+    /// This code's place under `to` instead of `from`, where `from` is this code or one of
+    /// its ancestors and `to` is at the same level as `from`
+    fn rebase(self, from: Code, to: Code) -> Code {
+        match (self, from, to) {
+            (_, from, to) if self == from => to,
+            (Code::Category(category), Code::Area(_), Code::Area(area)) => {
+                Code::Category(area * 10 + category % 10)
+            }
+            (Code::Id(category, number), Code::Area(_), Code::Area(area)) => {
+                Code::Id(area * 10 + category % 10, number)
+            }
+            (Code::Id(_, number), Code::Category(_), Code::Category(category)) => {
+                Code::Id(category, number)
+            }
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Code::Area(area) => write!(f, "{}0-{}9", area, area),
+            Code::Category(category) => write!(f, "{:02}", category),
+            Code::Id(category, number) => write!(f, "{:02}.{:02}", category, number),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Classification {
+    pub id: i64,
+    pub code: String,
+    pub title: String,
+    pub parent_id: Option<i64>,
+    /// Pages assigned to this classification itself, not counting ones below it
+    pub page_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassifiedPage {
+    pub page_id: i64,
+    pub title: String,
+    pub filename: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutlineNode {
+    pub id: i64,
+    pub code: String,
+    pub title: String,
+    pub pages: Vec<ClassifiedPage>,
+    pub children: Vec<OutlineNode>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutlineFormat {
+    Markdown,
+    Opml,
+}
+
+async fn classification_id(
+    conn: &mut SqliteConnection,
+    code: Code,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM classifications WHERE code = ?")
+        .bind(code.to_string())
+        .fetch_optional(conn)
+        .await
+}
+
+async fn classification_code(conn: &mut SqliteConnection, id: i64) -> Result<Code, String> {
+    let code: Option<String> = sqlx::query_scalar("SELECT code FROM classifications WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| format!("Failed to look up classification {}: {}", id, e))?;
+    Code::parse(&code.ok_or_else(|| format!("Classification {} not found", id))?)
+}
+
+/// The ID of the classification `code` goes under, which has to exist
+async fn parent_id(conn: &mut SqliteConnection, code: Code) -> Result<Option<i64>, String> {
+    let Some(parent) = code.parent() else {
+        return Ok(None);
+    };
+    classification_id(conn, parent)
+        .await
+        .map_err(|e| format!("Failed to look up classification {}: {}", parent, e))?
+        .map(Some)
+        .ok_or_else(|| format!("{} needs {} {} to go in", code, parent.level_name(), parent))
+}
+
+pub async fn create(pool: &SqlitePool, code: &str, title: &str) -> Result<i64, String> {
+    let code = Code::parse(code)?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let parent_id = parent_id(&mut tx, code).await?;
+    let id = sqlx::query_scalar(
+        "INSERT INTO classifications (code, title, parent_id) VALUES (?, ?, ?)
+         ON CONFLICT (code) DO NOTHING
+         RETURNING id",
+    )
+    .bind(code.to_string())
+    .bind(title.trim())
+    .bind(parent_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to create classification: {}", e))?
+    .ok_or_else(|| format!("{} is already in use", code))?;
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to create classification: {}", e))?;
+    Ok(id)
+}
+
+/// Gives classification `id` a new code at the same level, which may put it under another
+/// area or category, and renumbers everything below it to match
+pub async fn recode(pool: &SqlitePool, id: i64, new_code: &str) -> Result<(), String> {
+    let to = Code::parse(new_code)?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    recode_in(&mut tx, id, to).await?;
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to renumber {}: {}", to, e))
+}
+
+/// Moves classification `id` under `parent_id` with the next free code there, which is
+/// picked in the same transaction so two moves can't both take it. Returns the new code.
+pub async fn move_to(pool: &SqlitePool, id: i64, parent_id: i64) -> Result<Code, String> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let code = classification_code(&mut tx, id).await?;
+    let to = next_free_code(&mut tx, code, parent_id).await?;
+    recode_in(&mut tx, id, to).await?;
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to move {}: {}", code, e))?;
+    Ok(to)
+}
+
+async fn recode_in(conn: &mut SqliteConnection, id: i64, to: Code) -> Result<(), String> {
+    let from = classification_code(conn, id).await?;
+    if from == to {
+        return Ok(());
+    }
+    if std::mem::discriminant(&from) != std::mem::discriminant(&to) {
+        return Err(format!(
+            "{} is {}, so it can't become {}",
+            from,
+            from.level_name(),
+            to
+        ));
+    }
+    if classification_id(conn, to)
+        .await
+        .map_err(|e| format!("Failed to look up classification {}: {}", to, e))?
+        .is_some()
+    {
+        return Err(format!("{} is already in use", to));
+    }
+    let parent_id = parent_id(conn, to).await?;
+
+    let descendants: Vec<(i64, String)> = sqlx::query_as(
+        "WITH RECURSIVE descendants(id) AS (
+             SELECT id FROM classifications WHERE parent_id = ?
+             UNION
+             SELECT c.id FROM classifications c JOIN descendants d ON c.parent_id = d.id
+         )
+         SELECT c.id, c.code FROM classifications c JOIN descendants d ON d.id = c.id",
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to look up classifications under {}: {}", from, e))?;
+    for (descendant_id, code) in descendants {
+        let code = Code::parse(&code)?.rebase(from, to);
+        sqlx::query("UPDATE classifications SET code = ? WHERE id = ?")
+            .bind(code.to_string())
+            .bind(descendant_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to renumber {}: {}", code, e))?;
+    }
+    sqlx::query("UPDATE classifications SET code = ?, parent_id = ? WHERE id = ?")
+        .bind(to.to_string())
+        .bind(parent_id)
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to renumber {}: {}", from, e))?;
+    Ok(())
+}
+
+/// The first free code under `parent_id` for a classification at `code`'s level: the
+/// lowest free category in an area, or the number after the highest ID in a category
+async fn next_free_code(
+    conn: &mut SqliteConnection,
+    code: Code,
+    parent_id: i64,
+) -> Result<Code, String> {
+    let parent = classification_code(conn, parent_id).await?;
+    let siblings: Vec<String> =
+        sqlx::query_scalar("SELECT code FROM classifications WHERE parent_id = ?")
+            .bind(parent_id)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to look up classifications under {}: {}", parent, e))?;
+    let taken: Vec<Code> = siblings
+        .iter()
+        .filter_map(|sibling| Code::parse(sibling).ok())
+        .collect();
+
+    let free = match (code, parent) {
+        (Code::Category(_), Code::Area(area)) => (0..10)
+            .map(|digit| Code::Category(area * 10 + digit))
+            .find(|category| !taken.contains(category)),
+        (Code::Id(..), Code::Category(category)) => {
+            let last = taken
+                .iter()
+                .filter_map(|taken| match taken {
+                    Code::Id(_, number) => Some(*number),
+                    _ => None,
+                })
+                .max();
+            match last {
+                Some(99) => None,
+                Some(number) => Some(Code::Id(category, number + 1)),
+                None => Some(Code::Id(category, 1)),
+            }
+        }
+        _ => {
+            return Err(format!(
+                "{} is {}, so it can't go in {}",
+                code,
+                code.level_name(),
+                parent
+            ))
+        }
+    };
+    free.ok_or_else(|| format!("{} is full", parent))
+}
+
+pub async fn list(pool: &SqlitePool) -> Result<Vec<Classification>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT c.id, c.code, c.title, c.parent_id,
+                (SELECT COUNT(*) FROM page_classifications pc
+                 JOIN pages p ON p.id = pc.page_id
+                 WHERE pc.classification_id = c.id AND p.archived_at IS NULL) AS page_count
+         FROM classifications c
+         ORDER BY c.code",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Every classification as a tree, with the pages in it that aren't in the trash. Codes at
+/// the same level sort the same as text, so siblings are in order.
+pub async fn outline(pool: &SqlitePool) -> Result<Vec<OutlineNode>, sqlx::Error> {
+    let classifications = list(pool).await?;
+    let page_rows: Vec<(i64, i64, String, String)> = sqlx::query_as(
+        "SELECT pc.classification_id, p.id, p.title, p.filename
+         FROM page_classifications pc
+         JOIN pages p ON p.id = pc.page_id
+         WHERE p.archived_at IS NULL
+         ORDER BY p.title, p.id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut pages: HashMap<i64, Vec<ClassifiedPage>> = HashMap::new();
+    for (classification_id, page_id, title, filename) in page_rows {
+        pages
+            .entry(classification_id)
+            .or_default()
+            .push(ClassifiedPage {
+                page_id,
+                title,
+                filename,
+            });
+    }
+    let mut children: HashMap<Option<i64>, Vec<Classification>> = HashMap::new();
+    for classification in classifications {
+        children
+            .entry(classification.parent_id)
+            .or_default()
+            .push(classification);
+    }
+
+    fn build(
+        parent_id: Option<i64>,
+        children: &mut HashMap<Option<i64>, Vec<Classification>>,
+        pages: &mut HashMap<i64, Vec<ClassifiedPage>>,
+    ) -> Vec<OutlineNode> {
+        children
+            .remove(&parent_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|classification| OutlineNode {
+                id: classification.id,
+                children: build(Some(classification.id), children, pages),
+                pages: pages.remove(&classification.id).unwrap_or_default(),
+                code: classification.code,
+                title: classification.title,
+            })
+            .collect()
+    }
+    Ok(build(None, &mut children, &mut pages))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn outline_to_markdown(outline: &[OutlineNode]) -> String {
+    fn write_nodes(out: &mut String, nodes: &[OutlineNode], depth: usize) {
+        for node in nodes {
+            let indent = "  ".repeat(depth);
+            let _ = writeln!(out, "{}- **{}** {}", indent, node.code, node.title);
+            for page in &node.pages {
+                let _ = writeln!(out, "{}  - {}", indent, page.title);
+            }
+            write_nodes(out, &node.children, depth + 1);
+        }
+    }
+
+    let mut out = String::from("# Classification\n\n");
+    write_nodes(&mut out, outline, 0);
+    out
+}
+
+pub fn outline_to_opml(outline: &[OutlineNode]) -> String {
+    fn write_nodes(out: &mut String, nodes: &[OutlineNode], depth: usize) {
+        for node in nodes {
+            let indent = "  ".repeat(depth);
+            let _ = writeln!(
+                out,
+                "{}<outline text=\"{}\">",
+                indent,
+                xml_escape(&format!("{} {}", node.code, node.title))
+            );
+            for page in &node.pages {
+                let _ = writeln!(
+                    out,
+                    "{}  <outline text=\"{}\" type=\"page\" filename=\"{}\"/>",
+                    indent,
+                    xml_escape(&page.title),
+                    xml_escape(&page.filename)
+                );
+            }
+            write_nodes(out, &node.children, depth + 1);
+            let _ = writeln!(out, "{}</outline>", indent);
+        }
+    }
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n  <head>\n    <title>Classification</title>\n  </head>\n  <body>\n",
+    );
+    write_nodes(&mut out, outline, 2);
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+/// A folder name for a classification, with characters that aren't allowed in file names
+/// replaced, the way page filenames are
+fn folder_name(code: &str, title: &str) -> String {
+    let name: String = format!("{} {}", code, title)
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    name.trim_end_matches(['.', ' ']).to_string()
+}
+
+/// The folder each classified page goes in when syncing by classification, such as
+/// `10-19 Admin/11 Finance/11.01 Taxes`, by page ID
+pub async fn page_folders(pool: &SqlitePool) -> Result<HashMap<i64, PathBuf>, sqlx::Error> {
+    fn collect(nodes: &[OutlineNode], parent: &Path, folders: &mut HashMap<i64, PathBuf>) {
+        for node in nodes {
+            let folder = parent.join(folder_name(&node.code, &node.title));
+            for page in &node.pages {
+                folders.insert(page.page_id, folder.clone());
+            }
+            collect(&node.children, &folder, folders);
+        }
+    }
+
+    let mut folders = HashMap::new();
+    collect(&outline(pool).await?, Path::new(""), &mut folders);
+    Ok(folders)
+}
+
+// Command to add a classification. Categories and IDs need their area or category to exist.
+#[tauri::command]
+pub async fn create_classification(
+    app_handle: tauri::AppHandle,
+    code: String,
+    title: String,
+) -> Result<i64, String> {
+    let pool = get_pool(&app_handle).await?;
+    create(&pool, &code, &title).await
+}
+
+// Command to change a classification's title
+#[tauri::command]
+pub async fn rename_classification(
+    app_handle: tauri::AppHandle,
+    classification_id: i64,
+    title: String,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let result = sqlx::query("UPDATE classifications SET title = ? WHERE id = ?")
+        .bind(title.trim())
+        .bind(classification_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to rename classification: {}", e))?;
+    if result.rows_affected() == 0 {
+        return Err(format!("Classification {} not found", classification_id));
+    }
+    Ok(())
+}
+
+// Command to give a classification a new code at the same level, renumbering the codes below
+// it. Its pages stay assigned to it.
+#[tauri::command]
+pub async fn renumber_classification(
+    app_handle: tauri::AppHandle,
+    classification_id: i64,
+    code: String,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    recode(&pool, classification_id, &code).await
+}
+
+// Command to move a category to another area, or an ID to another category, taking the next
+// free code there. Returns the new code.
+#[tauri::command]
+pub async fn move_classification(
+    app_handle: tauri::AppHandle,
+    classification_id: i64,
+    parent_id: i64,
+) -> Result<String, String> {
+    let pool = get_pool(&app_handle).await?;
+    let new_code = move_to(&pool, classification_id, parent_id).await?;
+    Ok(new_code.to_string())
+}
+
+// Command to delete a classification with nothing below it. Its pages become unclassified.
+#[tauri::command]
+pub async fn delete_classification(
+    app_handle: tauri::AppHandle,
+    classification_id: i64,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let has_children: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM classifications WHERE parent_id = ?)")
+            .bind(classification_id)
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| format!("Failed to delete classification: {}", e))?;
+    if has_children {
+        return Err("Only classifications with nothing below them can be deleted".to_string());
+    }
+    sqlx::query("DELETE FROM classifications WHERE id = ?")
+        .bind(classification_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to delete classification: {}", e))?;
+    Ok(())
+}
+
+// Command to list every classification, in code order
+#[tauri::command]
+pub async fn list_classifications(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<Classification>, String> {
+    let pool = get_pool(&app_handle).await?;
+    list(&pool)
+        .await
+        .map_err(|e| format!("Failed to list classifications: {}", e))
+}
+
+// Command to put a page in a classification, or take it out of its classification with `None`
+#[tauri::command]
+pub async fn set_page_classification(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+    classification_id: Option<i64>,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    match classification_id {
+        Some(classification_id) => sqlx::query(
+            "INSERT INTO page_classifications (page_id, classification_id) VALUES (?, ?)
+             ON CONFLICT (page_id) DO UPDATE SET
+                 classification_id = excluded.classification_id,
+                 created_at = CURRENT_TIMESTAMP",
+        )
+        .bind(page_id)
+        .bind(classification_id),
+        None => sqlx::query("DELETE FROM page_classifications WHERE page_id = ?").bind(page_id),
+    }
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to classify page {}: {}", page_id, e))?;
+    Ok(())
+}
+
+// Command to get the classifications as a tree, with their pages
+#[tauri::command]
+pub async fn get_classification_outline(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<OutlineNode>, String> {
+    let pool = get_pool(&app_handle).await?;
+    outline(&pool)
+        .await
+        .map_err(|e| format!("Failed to get classification outline: {}", e))
+}
+
+// Command to save the classification outline, with its pages, to `path` as Markdown or OPML
+#[tauri::command]
+pub async fn export_classification_outline(
+    app_handle: tauri::AppHandle,
+    path: String,
+    format: OutlineFormat,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let outline = outline(&pool)
+        .await
+        .map_err(|e| format!("Failed to get classification outline: {}", e))?;
+    let contents = match format {
+        OutlineFormat::Markdown => outline_to_markdown(&outline),
+        OutlineFormat::Opml => outline_to_opml(&outline),
+    };
+    fs::write(&path, contents).map_err(|e| format!("Failed to write outline: {}", e))?;
+    println!("Saved classification outline to {}", path);
+    Ok(())
+}
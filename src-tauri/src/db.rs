@@ -1,19 +1,281 @@
-use sqlx::SqlitePool;
+use crate::backup_commands::create_pre_migration_copy;
+use crate::stats_commands::backfill_word_counts;
+use crate::vault::{current_vault_dir, is_default_vault, DATABASE_FILE_NAME};
+use sqlx::error::BoxDynError;
+use sqlx::migrate::{Migration as SqlxMigration, MigrationSource, MigrationType, Migrator};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection, SqlitePool};
+use std::future::Future;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
 use tauri::Manager;
-use tauri_plugin_sql::{Migration, MigrationKind};
+use tauri_plugin_sql::{DbInstances, DbPool, Migration, MigrationKind};
 use tokio::sync::Mutex;
 
-/// Returns the database migrations for the application
+/// Key the SQL plugin knows the database by, as passed to `Database.load()` in the frontend
+pub const DB_URL: &str = "sqlite:notesponge.db";
+
+/// Returns the database migrations for the application. Migrations come in pairs:
+/// `NN-name.sql` brings the schema up to version NN and `NN-name.down.sql` takes it back
+/// to the previous version, so a vault can be handed back to an older version of the app.
 pub fn get_migrations() -> Vec<Migration> {
-    const INITIAL_SCHEMA: &str = include_str!("../migrations/01-initial-schema.sql");
+    [
+        migration_pair(
+            1,
+            "initial_schema",
+            include_str!("../migrations/01-initial-schema.sql"),
+            include_str!("../migrations/01-initial-schema.down.sql"),
+        ),
+        migration_pair(
+            2,
+            "image_store",
+            include_str!("../migrations/02-image-store.sql"),
+            include_str!("../migrations/02-image-store.down.sql"),
+        ),
+        migration_pair(
+            3,
+            "app_metadata",
+            include_str!("../migrations/03-app-metadata.sql"),
+            include_str!("../migrations/03-app-metadata.down.sql"),
+        ),
+        migration_pair(
+            4,
+            "search_index",
+            include_str!("../migrations/04-search-index.sql"),
+            include_str!("../migrations/04-search-index.down.sql"),
+        ),
+        migration_pair(
+            5,
+            "title_trigrams",
+            include_str!("../migrations/05-title-trigrams.sql"),
+            include_str!("../migrations/05-title-trigrams.down.sql"),
+        ),
+        migration_pair(
+            6,
+            "search_stemming",
+            include_str!("../migrations/06-search-stemming.sql"),
+            include_str!("../migrations/06-search-stemming.down.sql"),
+        ),
+        migration_pair(
+            7,
+            "page_links",
+            include_str!("../migrations/07-page-links.sql"),
+            include_str!("../migrations/07-page-links.down.sql"),
+        ),
+        migration_pair(
+            8,
+            "page_relations",
+            include_str!("../migrations/08-page-relations.sql"),
+            include_str!("../migrations/08-page-relations.down.sql"),
+        ),
+        migration_pair(
+            9,
+            "fleeting_notes",
+            include_str!("../migrations/09-fleeting-notes.sql"),
+            include_str!("../migrations/09-fleeting-notes.down.sql"),
+        ),
+        migration_pair(
+            10,
+            "resurfacing",
+            include_str!("../migrations/10-resurfacing.sql"),
+            include_str!("../migrations/10-resurfacing.down.sql"),
+        ),
+        migration_pair(
+            11,
+            "classifications",
+            include_str!("../migrations/11-classifications.sql"),
+            include_str!("../migrations/11-classifications.down.sql"),
+        ),
+        migration_pair(
+            12,
+            "tag_aliases",
+            include_str!("../migrations/12-tag-aliases.sql"),
+            include_str!("../migrations/12-tag-aliases.down.sql"),
+        ),
+        migration_pair(
+            13,
+            "tag_clusters",
+            include_str!("../migrations/13-tag-clusters.sql"),
+            include_str!("../migrations/13-tag-clusters.down.sql"),
+        ),
+        migration_pair(
+            14,
+            "title_index",
+            include_str!("../migrations/14-title-index.sql"),
+            include_str!("../migrations/14-title-index.down.sql"),
+        ),
+        migration_pair(
+            15,
+            "page_activity",
+            include_str!("../migrations/15-page-activity.sql"),
+            include_str!("../migrations/15-page-activity.down.sql"),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn migration_pair(
+    version: i64,
+    description: &'static str,
+    up: &'static str,
+    down: &'static str,
+) -> [Migration; 2] {
+    [
+        Migration {
+            version,
+            description,
+            sql: up,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version,
+            description,
+            sql: down,
+            kind: MigrationKind::Down,
+        },
+    ]
+}
 
-    vec![Migration {
-        version: 1,
-        description: "initial_schema",
-        sql: INITIAL_SCHEMA,
-        kind: MigrationKind::Up,
-    }]
+/// Returns the schema version the newest migration brings the database to
+pub fn latest_schema_version() -> i64 {
+    get_migrations()
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or(0)
+}
+
+/// `get_migrations` as a sqlx migration source, converted the same way the SQL plugin does it
+/// so the checksums recorded in `_sqlx_migrations` match
+#[derive(Debug)]
+struct AppMigrations;
+
+impl MigrationSource<'static> for AppMigrations {
+    fn resolve(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<SqlxMigration>, BoxDynError>> + Send>> {
+        Box::pin(async move {
+            Ok(get_migrations()
+                .into_iter()
+                .map(|m| {
+                    let migration_type = match m.kind {
+                        MigrationKind::Up => MigrationType::ReversibleUp,
+                        MigrationKind::Down => MigrationType::ReversibleDown,
+                    };
+                    SqlxMigration::new(
+                        m.version,
+                        m.description.into(),
+                        migration_type,
+                        m.sql.into(),
+                        false,
+                    )
+                })
+                .collect())
+        })
+    }
+}
+
+async fn migrator() -> Result<Migrator, String> {
+    Migrator::new(AppMigrations)
+        .await
+        .map_err(|e| format!("Failed to load migrations: {}", e))
+}
+
+/// Returns the highest migration version applied to the database, 0 for a new database
+pub async fn schema_version(pool: &SqlitePool) -> Result<i64, String> {
+    // `_sqlx_migrations` doesn't exist until the first migration has run
+    sqlx::query_scalar(
+        "SELECT CASE WHEN EXISTS(
+             SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'
+         ) THEN (SELECT COALESCE(MAX(version), 0) FROM _sqlx_migrations WHERE success = 1)
+         ELSE 0 END",
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+/// Returns the version of the app that last opened the database, if it recorded one
+async fn recorded_app_version(pool: &SqlitePool) -> Option<String> {
+    sqlx::query_scalar("SELECT value FROM app_metadata WHERE key = 'app_version'")
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Refuses a database whose schema is newer than this version of the app understands.
+/// Returns its schema version otherwise.
+pub async fn check_schema_version(pool: &SqlitePool) -> Result<i64, String> {
+    let version = schema_version(pool).await?;
+    let latest = latest_schema_version();
+    if version > latest {
+        let opened_by = match recorded_app_version(pool).await {
+            Some(app_version) => format!("NoteSponge {}", app_version),
+            None => "a newer version of NoteSponge".to_string(),
+        };
+        return Err(format!(
+            "This vault was last opened by {} and has schema version {}, but NoteSponge {} \
+             only supports up to version {}. Update NoteSponge to open it.",
+            opened_by,
+            version,
+            env!("CARGO_PKG_VERSION"),
+            latest
+        ));
+    }
+    Ok(version)
+}
+
+async fn record_versions(pool: &SqlitePool) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO app_metadata (key, value) VALUES ('app_version', ?), ('schema_version', ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+    )
+    .bind(env!("CARGO_PKG_VERSION"))
+    .bind(latest_schema_version().to_string())
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to record app version: {}", e))?;
+    Ok(())
+}
+
+/// Brings the schema of the open vault's database up to date. Refuses databases from a
+/// newer version of the app, and saves a copy of an existing database before upgrading it.
+pub async fn migrate(app_handle: &tauri::AppHandle, pool: &SqlitePool) -> Result<(), String> {
+    let version = check_schema_version(pool).await?;
+    if version > 0 && version < latest_schema_version() {
+        let copy = create_pre_migration_copy(app_handle, pool).await?;
+        println!(
+            "Saved pre-migration copy of schema version {}: {}",
+            version,
+            copy.display()
+        );
+    }
+
+    migrator()
+        .await?
+        .run(pool)
+        .await
+        .map_err(|e| format!("Failed to run migrations: {}", e))?;
+    match backfill_word_counts(pool).await {
+        Ok(0) => {}
+        Ok(n) => println!("Counted words in {} pages", n),
+        Err(e) => eprintln!("Word count backfill error: {}", e),
+    }
+    record_versions(pool).await
+}
+
+/// Runs down migrations until the database is at schema version `target`
+pub async fn revert_migrations(pool: &SqlitePool, target: i64) -> Result<(), String> {
+    migrator()
+        .await?
+        .undo(pool, target)
+        .await
+        .map_err(|e| format!("Failed to revert migrations: {}", e))
 }
 
 /// Sets up SQLite PRAGMAs for optimal performance
@@ -26,39 +288,253 @@ pub fn get_sqlite_pragmas() -> &'static str {
      PRAGMA temp_store = memory;"
 }
 
-/// Initializes the database and sets up the connection pool
-pub async fn initialize_database(app: &tauri::App) -> Result<(), String> {
-    let app_handle = app.handle();
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data directory");
-    std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
-
-    // I've verified that this path is correct
-    let db_path = app_data_dir.join("notesponge.db");
-    let db_url = format!("sqlite:{}", db_path.display());
-
-    match SqlitePool::connect(&db_url).await {
-        Ok(pool) => {
-            // Store the pool in app state
-            let pool_mutex = Arc::new(Mutex::new(pool));
-            app.manage(pool_mutex.clone());
-
-            // Set PRAGMAs immediately
-            let pool_guard = pool_mutex.lock().await;
-            let _ = sqlx::query(get_sqlite_pragmas())
-                .execute(&*pool_guard)
-                .await
-                .map_err(|e| e.to_string())?;
-            // Release the lock
-            drop(pool_guard);
-
-            Ok(())
+/// Passphrase the database was unlocked with, or `None` if it isn't encrypted
+#[derive(Default)]
+pub struct DatabaseKey(pub std::sync::Mutex<Option<String>>);
+
+/// Returns the current database passphrase
+pub fn current_key(app_handle: &tauri::AppHandle) -> Option<String> {
+    app_handle
+        .try_state::<DatabaseKey>()
+        .and_then(|key| key.0.lock().unwrap().clone())
+}
+
+pub fn set_current_key(app_handle: &tauri::AppHandle, key: Option<String>) {
+    if let Some(state) = app_handle.try_state::<DatabaseKey>() {
+        *state.0.lock().unwrap() = key;
+    }
+}
+
+/// Why the open vault's database couldn't be brought up to date, e.g. because it's from a
+/// newer version of the app. The frontend shows it instead of the notes.
+#[derive(Default)]
+pub struct DatabaseOpenError(pub std::sync::Mutex<Option<String>>);
+
+pub fn open_error(app_handle: &tauri::AppHandle) -> Option<String> {
+    app_handle
+        .try_state::<DatabaseOpenError>()
+        .and_then(|error| error.0.lock().unwrap().clone())
+}
+
+fn set_open_error(app_handle: &tauri::AppHandle, error: Option<String>) {
+    if let Some(state) = app_handle.try_state::<DatabaseOpenError>() {
+        *state.0.lock().unwrap() = error;
+    }
+}
+
+/// Returns a clone of the connection pool managed by `initialize_database`
+pub async fn get_pool(app_handle: &tauri::AppHandle) -> Result<SqlitePool, String> {
+    let pool_mutex = app_handle
+        .try_state::<Arc<Mutex<SqlitePool>>>()
+        .ok_or_else(|| "Database is not initialized".to_string())?;
+    let pool = pool_mutex.lock().await.clone();
+    Ok(pool)
+}
+
+/// Returns the path of the open vault's database file
+pub fn database_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(current_vault_dir(app_handle)?.join(DATABASE_FILE_NAME))
+}
+
+/// Earlier versions let the SQL plugin open the database in the app config directory,
+/// which isn't the app data directory on every platform. Moves it over if it's only there.
+fn adopt_plugin_database(app_handle: &tauri::AppHandle, db_path: &Path) -> Result<(), String> {
+    let Ok(config_dir) = app_handle.path().app_config_dir() else {
+        return Ok(());
+    };
+    let old_path = config_dir.join(DATABASE_FILE_NAME);
+    let in_default_vault = db_path
+        .parent()
+        .is_some_and(|dir| is_default_vault(app_handle, dir));
+    if !in_default_vault || db_path.exists() || !old_path.exists() {
+        return Ok(());
+    }
+
+    for suffix in ["", "-wal", "-shm"] {
+        let from = sidecar_path(&old_path, suffix);
+        if from.exists() {
+            std::fs::rename(&from, sidecar_path(db_path, suffix))
+                .map_err(|e| format!("Failed to move database from {}: {}", from.display(), e))?;
         }
-        Err(e) => {
-            eprintln!("Failed to connect to database: {}", e);
-            Err(format!("Failed to connect to database: {}", e))
+    }
+    println!("Moved database from {}", old_path.display());
+    Ok(())
+}
+
+/// Returns `db_path` with `suffix` appended, e.g. the `-wal` file next to the database
+pub fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Whether the file at `db_path` is encrypted. Plaintext SQLite files start with a fixed
+/// header, SQLCipher files are indistinguishable from random bytes.
+pub fn is_encrypted(db_path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    let read = std::fs::File::open(db_path).and_then(|mut f| f.read_exact(&mut header));
+    read.is_ok() && &header != b"SQLite format 3\0"
+}
+
+/// Quotes a passphrase for use in `PRAGMA key`, `PRAGMA rekey` or `ATTACH ... KEY`
+pub fn quote_key(key: &str) -> String {
+    format!("'{}'", key.replace('\'', "''"))
+}
+
+/// Connection options for the database at `db_path`, unlocked with `key` if it's encrypted
+pub fn connect_options(db_path: &Path, key: Option<&str>) -> SqliteConnectOptions {
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .create_if_missing(true);
+    match key {
+        Some(key) => options.pragma("key", quote_key(key)),
+        None => options,
+    }
+}
+
+/// Connects to the database at `db_path` and sets PRAGMAs
+pub async fn open_pool(db_path: &Path, key: Option<&str>) -> Result<SqlitePool, String> {
+    let pool = SqlitePool::connect_with(connect_options(db_path, key))
+        .await
+        .map_err(|e| format!("Failed to connect to database: {}", e))?;
+
+    // Set PRAGMAs immediately. This is also the first read, so it fails on a wrong passphrase.
+    if let Err(e) = sqlx::query(get_sqlite_pragmas()).execute(&pool).await {
+        pool.close().await;
+        return Err(match key {
+            Some(_) if is_encrypted(db_path) => "Incorrect passphrase".to_string(),
+            _ => e.to_string(),
+        });
+    }
+    Ok(pool)
+}
+
+/// Opens the database with `key`, brings its schema up to date, and makes it available
+/// both to Rust commands and to the frontend through the SQL plugin
+pub async fn initialize_database(
+    app_handle: &tauri::AppHandle,
+    key: Option<String>,
+) -> Result<(), String> {
+    let db_path = database_path(app_handle)?;
+    adopt_plugin_database(app_handle, &db_path)?;
+
+    let pool = open_pool(&db_path, key.as_deref()).await?;
+    let migrated = migrate(app_handle, &pool).await;
+    set_open_error(app_handle, migrated.as_ref().err().cloned());
+    if let Err(e) = migrated {
+        pool.close().await;
+        return Err(e);
+    }
+    set_current_key(app_handle, key);
+
+    let db_instances = app_handle.state::<DbInstances>();
+    db_instances
+        .0
+        .write()
+        .await
+        .insert(DB_URL.to_string(), DbPool::Sqlite(pool.clone()));
+
+    // Store the pool in app state
+    match app_handle.try_state::<Arc<Mutex<SqlitePool>>>() {
+        Some(pool_mutex) => *pool_mutex.lock().await = pool,
+        None => {
+            app_handle.manage(Arc::new(Mutex::new(pool)));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a copy of the database at `from` (opened with `from_key`) to a new file at `to`,
+/// encrypted with `to_key`, or in plaintext if it's `None`
+pub async fn export_database(
+    from: &Path,
+    from_key: Option<&str>,
+    to: &Path,
+    to_key: Option<&str>,
+) -> Result<(), String> {
+    let mut conn = connect_options(from, from_key)
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    let export = async {
+        sqlx::query("ATTACH DATABASE ? AS export KEY ?")
+            .bind(to.to_string_lossy().to_string())
+            .bind(to_key.unwrap_or_default())
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("SELECT sqlcipher_export('export')")
+            .execute(&mut conn)
+            .await?;
+        sqlx::query("DETACH DATABASE export")
+            .execute(&mut conn)
+            .await?;
+        Ok::<_, sqlx::Error>(())
+    };
+    let result = export
+        .await
+        .map_err(|e| format!("Failed to export database: {}", e));
+    let _ = conn.close().await;
+    result
+}
+
+/// Moves the file at `new_file` over the database at `db_path`, discarding the old
+/// database's WAL and shared-memory files so they aren't replayed on top of it.
+/// Only call this while the database is closed.
+pub fn replace_database_file(new_file: &Path, db_path: &Path) -> Result<(), String> {
+    for suffix in ["-wal", "-shm"] {
+        let sidecar = sidecar_path(db_path, suffix);
+        if sidecar.exists() {
+            std::fs::remove_file(&sidecar)
+                .map_err(|e| format!("Failed to remove {}: {}", sidecar.display(), e))?;
+        }
+    }
+    std::fs::rename(new_file, db_path).map_err(|e| format!("Failed to replace database: {}", e))
+}
+
+/// Closes every connection to the database, runs `f` on the file, then reopens the open
+/// vault's database (`f` may have switched vaults) with the current key (which `f` may
+/// have changed) and runs migrations. Commands that need the
+/// database wait until it has been reopened. The database is reopened even if `f` fails.
+pub async fn with_database_closed<T, F, Fut>(
+    app_handle: &tauri::AppHandle,
+    f: F,
+) -> Result<T, String>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let db_path = database_path(app_handle)?;
+    let pool_mutex = app_handle
+        .try_state::<Arc<Mutex<SqlitePool>>>()
+        .ok_or_else(|| "Database is not initialized".to_string())?;
+    let db_instances = app_handle.state::<DbInstances>();
+
+    let mut pool = pool_mutex.lock().await;
+    let mut instances = db_instances.0.write().await;
+    pool.close().await;
+    if let Some(DbPool::Sqlite(plugin_pool)) = instances.remove(DB_URL) {
+        plugin_pool.close().await;
+    }
+
+    let result = f(db_path).await;
+
+    let db_path = database_path(app_handle)?;
+    let new_pool = open_pool(&db_path, current_key(app_handle).as_deref()).await?;
+    *pool = new_pool.clone();
+    instances.insert(DB_URL.to_string(), DbPool::Sqlite(new_pool.clone()));
+    drop(instances);
+    drop(pool);
+
+    migrate(app_handle, &new_pool).await?;
+    result
+}
+
+/// Reloads every window so the frontend picks up a database that changed under it
+pub fn reload_windows(app_handle: &tauri::AppHandle) {
+    for window in app_handle.webview_windows().values() {
+        if let Err(e) = window.eval("window.location.reload()") {
+            eprintln!("Failed to reload window {}: {}", window.label(), e);
         }
     }
 }
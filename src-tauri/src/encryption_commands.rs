@@ -0,0 +1,188 @@
+// Encryption at rest using SQLCipher. An encrypted database can't be opened until the user
+// enters its passphrase, so startup skips `db::initialize_database` and waits for
+// `unlock_database` instead. The passphrase is only ever kept in memory.
+//
+// Snapshots taken while the database is encrypted are encrypted with the passphrase in
+// use at the time. Turning encryption on or off or changing the passphrase converts the
+// snapshots taken before, so they can still be restored without the old passphrase.
+// Images (in the image store and saved with snapshots) are not encrypted, so turning
+// encryption on in a vault that has any must be confirmed.
+
+use crate::backup_commands::rekey_snapshots;
+use crate::db::{
+    connect_options, current_key, database_path, export_database, get_pool, initialize_database,
+    is_encrypted, open_error, quote_key, replace_database_file, set_current_key,
+    with_database_closed,
+};
+use crate::image_store::ImageStore;
+use serde::Serialize;
+use sqlx::{ConnectOptions, Connection};
+use tauri::Emitter;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStatus {
+    pub encrypted: bool,
+    pub unlocked: bool,
+    /// Why the database couldn't be opened, if it couldn't
+    pub error: Option<String>,
+    /// Images in the vault, which are stored unencrypted whether or not the database is
+    pub unencrypted_image_count: u64,
+}
+
+fn check_passphrase(app_handle: &tauri::AppHandle, passphrase: &str) -> Result<(), String> {
+    match current_key(app_handle) {
+        Some(key) if key == passphrase => Ok(()),
+        Some(_) => Err("Incorrect passphrase".to_string()),
+        None => Err("The database is not encrypted".to_string()),
+    }
+}
+
+fn check_new_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase can't be empty".to_string());
+    }
+    Ok(())
+}
+
+// Command to report whether the database is encrypted and whether it has been unlocked
+#[tauri::command]
+pub async fn get_database_status(app_handle: tauri::AppHandle) -> Result<DatabaseStatus, String> {
+    Ok(DatabaseStatus {
+        encrypted: is_encrypted(&database_path(&app_handle)?),
+        unlocked: get_pool(&app_handle).await.is_ok(),
+        error: open_error(&app_handle),
+        unencrypted_image_count: ImageStore::current(&app_handle)?.image_count()?,
+    })
+}
+
+// Command to open an encrypted database with its passphrase. Emits `database_unlocked`
+// so every window can continue loading.
+#[tauri::command]
+pub async fn unlock_database(
+    app_handle: tauri::AppHandle,
+    passphrase: String,
+) -> Result<(), String> {
+    if get_pool(&app_handle).await.is_ok() {
+        return Ok(());
+    }
+
+    initialize_database(&app_handle, Some(passphrase)).await?;
+    let pool = get_pool(&app_handle).await?;
+    ImageStore::current(&app_handle)?
+        .upgrade_attachments(&pool)
+        .await;
+
+    println!("Database unlocked");
+    let _ = app_handle.emit("database_unlocked", ());
+    Ok(())
+}
+
+// Command to encrypt a plaintext database, and the snapshots taken of it, with a new
+// passphrase. Images aren't encrypted, so a vault with images is only encrypted when
+// `allow_unencrypted_images` is set.
+#[tauri::command]
+pub async fn enable_encryption(
+    app_handle: tauri::AppHandle,
+    passphrase: String,
+    allow_unencrypted_images: Option<bool>,
+) -> Result<(), String> {
+    check_new_passphrase(&passphrase)?;
+    if current_key(&app_handle).is_some() {
+        return Err("The database is already encrypted".to_string());
+    }
+    let store = ImageStore::current(&app_handle)?;
+    let image_count = store.image_count()?;
+    if image_count > 0 && !allow_unencrypted_images.unwrap_or(false) {
+        return Err(format!(
+            "Images can't be encrypted: the {} images in {} would stay readable on disk",
+            image_count,
+            store.dir.display()
+        ));
+    }
+
+    let key = passphrase.clone();
+
+    let handle = app_handle.clone();
+    with_database_closed(&app_handle, |db_path| async move {
+        let tmp_path = db_path.with_extension("db.encrypt");
+        export_database(&db_path, None, &tmp_path, Some(&passphrase)).await?;
+        replace_database_file(&tmp_path, &db_path)?;
+        set_current_key(&handle, Some(passphrase));
+        Ok(())
+    })
+    .await?;
+    println!("Database encrypted");
+
+    let snapshots = rekey_snapshots(&app_handle, None, Some(&key)).await?;
+    if snapshots > 0 {
+        println!("Encrypted {} snapshots", snapshots);
+    }
+    Ok(())
+}
+
+// Command to decrypt an encrypted database, and the snapshots taken of it, back to plaintext
+#[tauri::command]
+pub async fn disable_encryption(
+    app_handle: tauri::AppHandle,
+    passphrase: String,
+) -> Result<(), String> {
+    check_passphrase(&app_handle, &passphrase)?;
+
+    let key = passphrase.clone();
+
+    let handle = app_handle.clone();
+    with_database_closed(&app_handle, |db_path| async move {
+        let tmp_path = db_path.with_extension("db.decrypt");
+        export_database(&db_path, Some(&passphrase), &tmp_path, None).await?;
+        replace_database_file(&tmp_path, &db_path)?;
+        set_current_key(&handle, None);
+        Ok(())
+    })
+    .await?;
+    println!("Database decrypted");
+
+    let snapshots = rekey_snapshots(&app_handle, Some(&key), None).await?;
+    if snapshots > 0 {
+        println!("Decrypted {} snapshots", snapshots);
+    }
+    Ok(())
+}
+
+// Command to re-encrypt the database, and the snapshots taken of it, with a new passphrase
+#[tauri::command]
+pub async fn change_passphrase(
+    app_handle: tauri::AppHandle,
+    current_passphrase: String,
+    new_passphrase: String,
+) -> Result<(), String> {
+    check_passphrase(&app_handle, &current_passphrase)?;
+    check_new_passphrase(&new_passphrase)?;
+
+    let (old_key, new_key) = (current_passphrase.clone(), new_passphrase.clone());
+
+    let handle = app_handle.clone();
+    with_database_closed(&app_handle, |db_path| async move {
+        let mut conn = connect_options(&db_path, Some(&current_passphrase))
+            .connect()
+            .await
+            .map_err(|e| format!("Failed to open database: {}", e))?;
+        let rekeyed = sqlx::query(&format!("PRAGMA rekey = {}", quote_key(&new_passphrase)))
+            .execute(&mut conn)
+            .await
+            .map_err(|e| format!("Failed to change passphrase: {}", e));
+        let _ = conn.close().await;
+        rekeyed?;
+
+        set_current_key(&handle, Some(new_passphrase));
+        Ok(())
+    })
+    .await?;
+    println!("Database passphrase changed");
+
+    let snapshots = rekey_snapshots(&app_handle, Some(&old_key), Some(&new_key)).await?;
+    if snapshots > 0 {
+        println!("Re-encrypted {} snapshots", snapshots);
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+// Export of a vault's pages, tags and page relations as a single JSON file, for reading by
+// other tools. Pages in the trash are left out, along with relations to them.
+
+use crate::db::get_pool;
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::fs;
+
+/// Bumped whenever the layout of the export changes
+const EXPORT_FORMAT_VERSION: i64 = 1;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedPage {
+    pub id: i64,
+    pub title: String,
+    pub filename: String,
+    pub markdown: String,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    #[sqlx(skip)]
+    pub tags: Vec<String>,
+}
+
+/// In `parent` relations the source page is the parent. Undirected relations have the lower
+/// page ID as their source.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedRelation {
+    pub source_page_id: i64,
+    pub target_page_id: i64,
+    pub relation_type: String,
+    pub directed: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultExport {
+    pub format_version: i64,
+    pub app_version: String,
+    pub exported_at: String,
+    pub pages: Vec<ExportedPage>,
+    pub relations: Vec<ExportedRelation>,
+}
+
+pub async fn export_vault(pool: &SqlitePool) -> Result<VaultExport, sqlx::Error> {
+    let mut pages: Vec<ExportedPage> = sqlx::query_as(
+        "SELECT id, title, filename, markdown_text AS markdown, created_at, updated_at
+         FROM pages WHERE archived_at IS NULL ORDER BY id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let tag_rows: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT ta.page_id, t.tag FROM tag_associations ta
+         JOIN tags t ON t.id = ta.tag_id
+         ORDER BY t.tag",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    for (page_id, tag) in tag_rows {
+        tags.entry(page_id).or_default().push(tag);
+    }
+    for page in &mut pages {
+        page.tags = tags.remove(&page.id).unwrap_or_default();
+    }
+
+    let relations: Vec<ExportedRelation> = sqlx::query_as(
+        "SELECT r.source_page_id, r.target_page_id, r.relation_type, r.directed
+         FROM page_relations r
+         JOIN pages s ON s.id = r.source_page_id
+         JOIN pages t ON t.id = r.target_page_id
+         WHERE s.archived_at IS NULL AND t.archived_at IS NULL
+         ORDER BY r.id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(VaultExport {
+        format_version: EXPORT_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: Utc::now().to_rfc3339(),
+        pages,
+        relations,
+    })
+}
+
+// Command to export the open vault's pages, tags and page relations to `path` as JSON
+#[tauri::command]
+pub async fn export_vault_json(app_handle: tauri::AppHandle, path: String) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let export = export_vault(&pool)
+        .await
+        .map_err(|e| format!("Failed to export vault: {}", e))?;
+    let json = serde_json::to_string_pretty(&export)
+        .map_err(|e| format!("Failed to serialize export: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write export: {}", e))?;
+    println!("Exported {} pages to {}", export.pages.len(), path);
+    Ok(())
+}
@@ -0,0 +1,171 @@
+// Fleeting notes: pages with an expiry date. A background job moves them to the trash once
+// they expire and emits `fleeting_pages_archived`, so the UI can offer to bring them back
+// with `restore_page`. An expired page restored from the trash comes back permanent; pages
+// that went to the trash below it keep their own expiry dates.
+
+use crate::db::get_pool;
+use crate::settings::get_u64_setting;
+use crate::trash_commands::archive_page_in;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::Emitter;
+
+/// Settings key for how many days a page marked fleeting lives, unless told otherwise
+pub const FLEETING_LIFETIME_DAYS_KEY: &str = "fleeting_lifetime_days";
+pub const DEFAULT_FLEETING_LIFETIME_DAYS: u64 = 7;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetingPage {
+    pub id: i64,
+    pub title: String,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedFleetingPage {
+    pub id: i64,
+    pub title: String,
+}
+
+/// Payload of the `fleeting_pages_archived` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetingPagesArchived {
+    /// Every page that went to the trash, including pages below an expired page in the
+    /// parent/child hierarchy
+    pub pages: Vec<ArchivedFleetingPage>,
+}
+
+/// Gives a page that isn't in the trash an expiry date `lifetime_days` from now. Returns
+/// the new expiry date, or `None` if there's no such page.
+pub async fn mark_fleeting(
+    pool: &SqlitePool,
+    page_id: i64,
+    lifetime_days: u64,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "UPDATE pages SET expires_at = datetime('now', ?)
+         WHERE id = ? AND archived_at IS NULL
+         RETURNING expires_at",
+    )
+    .bind(format!("+{} days", lifetime_days))
+    .bind(page_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Moves every expired page to the trash in one transaction, clearing the expiry dates of
+/// the expired pages so that restoring them keeps them. Returns the pages that were moved.
+pub async fn archive_expired_pages(
+    pool: &SqlitePool,
+) -> Result<Vec<ArchivedFleetingPage>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let expired_ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM pages
+         WHERE archived_at IS NULL AND expires_at IS NOT NULL
+         AND expires_at <= CURRENT_TIMESTAMP
+         ORDER BY expires_at, id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut archived_ids = Vec::new();
+    for &page_id in &expired_ids {
+        // May already have gone to the trash below an earlier expired page
+        archived_ids.extend(archive_page_in(&mut tx, page_id).await?);
+    }
+    if archived_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query(
+        "UPDATE pages SET expires_at = NULL
+         WHERE id IN (SELECT value FROM json_each(?))",
+    )
+    .bind(serde_json::to_string(&expired_ids).unwrap_or_default())
+    .execute(&mut *tx)
+    .await?;
+    let pages = sqlx::query_as(
+        "SELECT id, title FROM pages
+         WHERE id IN (SELECT value FROM json_each(?))
+         ORDER BY id",
+    )
+    .bind(serde_json::to_string(&archived_ids).unwrap_or_default())
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(pages)
+}
+
+/// Background job: moves expired fleeting pages to the trash
+pub async fn run_fleeting_sweep(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let pages = archive_expired_pages(&pool)
+        .await
+        .map_err(|e| format!("Failed to archive expired pages: {}", e))?;
+
+    if !pages.is_empty() {
+        println!("Moved {} expired pages to the trash", pages.len());
+        let _ = app_handle.emit("fleeting_pages_archived", FleetingPagesArchived { pages });
+    }
+    Ok(())
+}
+
+// Command to make a page fleeting. It goes to the trash after `lifetime_days`, or the
+// `fleeting_lifetime_days` setting if not given. Returns the expiry date.
+#[tauri::command]
+pub async fn mark_page_fleeting(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+    lifetime_days: Option<u64>,
+) -> Result<String, String> {
+    let lifetime_days = lifetime_days.unwrap_or_else(|| {
+        get_u64_setting(
+            &app_handle,
+            FLEETING_LIFETIME_DAYS_KEY,
+            DEFAULT_FLEETING_LIFETIME_DAYS,
+        )
+    });
+    if lifetime_days == 0 {
+        return Err("A fleeting page has to live at least one day".to_string());
+    }
+
+    let pool = get_pool(&app_handle).await?;
+    mark_fleeting(&pool, page_id, lifetime_days)
+        .await
+        .map_err(|e| format!("Failed to mark page {} fleeting: {}", page_id, e))?
+        .ok_or_else(|| format!("Page {} not found", page_id))
+}
+
+// Command to make a fleeting page permanent again
+#[tauri::command]
+pub async fn make_page_permanent(app_handle: tauri::AppHandle, page_id: i64) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let result = sqlx::query("UPDATE pages SET expires_at = NULL WHERE id = ?")
+        .bind(page_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to make page {} permanent: {}", page_id, e))?;
+    if result.rows_affected() == 0 {
+        return Err(format!("Page {} not found", page_id));
+    }
+    Ok(())
+}
+
+// Command to list fleeting pages that aren't in the trash, soonest to expire first
+#[tauri::command]
+pub async fn list_fleeting_pages(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<FleetingPage>, String> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query_as::<_, FleetingPage>(
+        "SELECT id, title, expires_at FROM pages
+         WHERE archived_at IS NULL AND expires_at IS NOT NULL
+         ORDER BY expires_at, id",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to list fleeting pages: {}", e))
+}
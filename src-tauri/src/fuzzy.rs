@@ -0,0 +1,314 @@
+// Fuzzy matching of a query against a title, for quick page switching. Titles are first
+// aligned with the query as a subsequence, favouring runs of consecutive characters and
+// characters at the start of words, like most fuzzy finders. Titles that don't contain the
+// query as a subsequence can still match with a typo or two, through edit distance.
+
+use crate::search_commands::MatchRange;
+
+const MATCH_SCORE: f64 = 16.0;
+const CONSECUTIVE_BONUS: f64 = 6.0;
+const WORD_START_BONUS: f64 = 8.0;
+const TITLE_START_BONUS: f64 = 4.0;
+const GAP_PENALTY: f64 = 1.0;
+/// Per title character that isn't matched, so shorter titles win ties
+const LENGTH_PENALTY: f64 = 0.2;
+
+/// A typo match earns this per matched character and loses `TYPO_PENALTY` per typo, which
+/// keeps it below a subsequence match of the same query
+const TYPO_MATCH_SCORE: f64 = 10.0;
+const TYPO_PENALTY: f64 = 15.0;
+
+#[derive(Debug, Clone)]
+pub struct FuzzyMatch {
+    pub score: f64,
+    /// Matched parts of the title
+    pub matches: Vec<MatchRange>,
+}
+
+/// Lowercases one character to one character and drops common Latin accents, so positions
+/// stay the same
+fn fold(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'ď' => 'd',
+        'è'..='ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+        'ğ' => 'g',
+        'ì'..='ï' | 'ī' | 'į' | 'ı' => 'i',
+        'ł' => 'l',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò'..='ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ř' => 'r',
+        'ś' | 'š' | 'ş' => 's',
+        'ť' | 'ţ' => 't',
+        'ù'..='ü' | 'ū' | 'ů' | 'ű' | 'ų' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        folded => folded,
+    }
+}
+
+fn is_word_start(title: &[char], j: usize) -> bool {
+    if j == 0 {
+        return true;
+    }
+    let (previous, current) = (title[j - 1], title[j]);
+    !previous.is_alphanumeric() && current.is_alphanumeric()
+        || previous.is_lowercase() && current.is_uppercase()
+        || !previous.is_numeric() && current.is_numeric()
+}
+
+fn match_bonus(title: &[char], j: usize) -> f64 {
+    let mut bonus = MATCH_SCORE;
+    if is_word_start(title, j) {
+        bonus += WORD_START_BONUS;
+    }
+    if j == 0 {
+        bonus += TITLE_START_BONUS;
+    }
+    bonus
+}
+
+/// Turns matched character indices into ranges in UTF-16 code units
+fn to_ranges(
+    title: &[char],
+    positions: impl IntoIterator<Item = (usize, usize)>,
+) -> Vec<MatchRange> {
+    let mut offsets = Vec::with_capacity(title.len() + 1);
+    let mut offset = 0;
+    for c in title {
+        offsets.push(offset);
+        offset += c.len_utf16();
+    }
+    offsets.push(offset);
+
+    let mut ranges: Vec<MatchRange> = Vec::new();
+    for (start, end) in positions {
+        let (start, end) = (offsets[start], offsets[end]);
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => ranges.push(MatchRange { start, end }),
+        }
+    }
+    ranges
+}
+
+/// Best alignment of `query` as a subsequence of `title`, by dynamic programming over
+/// (query character, title position)
+fn subsequence_match(query: &[char], title: &[char], folded: &[char]) -> Option<FuzzyMatch> {
+    let (n, m) = (query.len(), title.len());
+    if n == 0 || n > m {
+        return None;
+    }
+    // Most titles don't match at all, which is much quicker to rule out than to score
+    let mut rest = folded.iter();
+    if !query.iter().all(|q| rest.any(|c| c == q)) {
+        return None;
+    }
+
+    // score[i][j]: best score with query[..=i] matched and query[i] at title[j].
+    // from[i][j]: where query[i - 1] was matched on that best path.
+    let mut score = vec![vec![f64::NEG_INFINITY; m]; n];
+    let mut from = vec![vec![usize::MAX; m]; n];
+
+    for j in 0..m {
+        if folded[j] == query[0] {
+            score[0][j] = match_bonus(title, j);
+        }
+    }
+    for i in 1..n {
+        // Best of score[i - 1][k] + GAP_PENALTY * k over k < j - 1, for matches with a gap
+        let mut best_gapped = f64::NEG_INFINITY;
+        let mut best_gapped_at = usize::MAX;
+        for j in i..m {
+            if j >= 2 {
+                let candidate = score[i - 1][j - 2] + GAP_PENALTY * (j - 2) as f64;
+                if candidate > best_gapped {
+                    best_gapped = candidate;
+                    best_gapped_at = j - 2;
+                }
+            }
+            if folded[j] != query[i] {
+                continue;
+            }
+
+            let consecutive = score[i - 1][j - 1] + CONSECUTIVE_BONUS;
+            let gapped = best_gapped - GAP_PENALTY * (j - 1) as f64;
+            let (previous, previous_at) = if consecutive >= gapped {
+                (consecutive, j - 1)
+            } else {
+                (gapped, best_gapped_at)
+            };
+            if previous.is_finite() {
+                score[i][j] = previous + match_bonus(title, j);
+                from[i][j] = previous_at;
+            }
+        }
+    }
+
+    let (end, best) = score[n - 1]
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if !best.is_finite() {
+        return None;
+    }
+
+    let mut positions = vec![end];
+    for i in (1..n).rev() {
+        positions.push(from[i][*positions.last()?]);
+    }
+    positions.reverse();
+
+    Some(FuzzyMatch {
+        score: best - LENGTH_PENALTY * (m - n) as f64,
+        matches: to_ranges(title, positions.into_iter().map(|j| (j, j + 1))),
+    })
+}
+
+/// How many typos a query of `length` characters may have
+fn allowed_typos(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Closest substring of `title` to `query` by edit distance (Sellers' algorithm), if it's
+/// within the allowed number of typos. Swapping two neighbouring letters counts as one typo.
+fn typo_match(query: &[char], title: &[char], folded: &[char]) -> Option<FuzzyMatch> {
+    let (n, m) = (query.len(), title.len());
+    let max_typos = allowed_typos(n);
+    if max_typos == 0 || m == 0 {
+        return None;
+    }
+
+    // distance[j] / start[j]: edit distance of query[..i] to the best substring of the title
+    // ending at j, and where that substring starts. A match can start anywhere for free.
+    // The row before that is kept for transpositions.
+    let mut distance: Vec<usize> = vec![0; m + 1];
+    let mut start: Vec<usize> = (0..=m).collect();
+    let mut previous_distance: Vec<usize> = Vec::new();
+    let mut previous_start: Vec<usize> = Vec::new();
+    for (i, &q) in query.iter().enumerate() {
+        let mut next_distance = vec![i + 1; m + 1];
+        let mut next_start = vec![0; m + 1];
+        for j in 1..=m {
+            let substitute = distance[j - 1] + usize::from(folded[j - 1] != q);
+            let delete = distance[j] + 1;
+            let insert = next_distance[j - 1] + 1;
+            (next_distance[j], next_start[j]) = if substitute <= delete && substitute <= insert {
+                (substitute, start[j - 1])
+            } else if delete <= insert {
+                (delete, start[j])
+            } else {
+                (insert, next_start[j - 1])
+            };
+
+            let transposed = i >= 1
+                && j >= 2
+                && folded[j - 2] == q
+                && folded[j - 1] == query[i - 1]
+                && folded[j - 1] != q;
+            if transposed && previous_distance[j - 2] + 1 < next_distance[j] {
+                next_distance[j] = previous_distance[j - 2] + 1;
+                next_start[j] = previous_start[j - 2];
+            }
+        }
+        previous_distance = std::mem::replace(&mut distance, next_distance);
+        previous_start = std::mem::replace(&mut start, next_start);
+    }
+
+    let (end, typos) = (1..=m).map(|j| (j, distance[j])).min_by_key(|&(_, d)| d)?;
+    if typos > max_typos || start[end] >= end {
+        return None;
+    }
+    let begin = start[end];
+
+    let mut score = TYPO_MATCH_SCORE * (n - typos) as f64 - TYPO_PENALTY * typos as f64;
+    if is_word_start(title, begin) {
+        score += WORD_START_BONUS;
+    }
+    Some(FuzzyMatch {
+        score: score - LENGTH_PENALTY * (m - (end - begin)) as f64,
+        matches: to_ranges(title, [(begin, end)]),
+    })
+}
+
+/// Matches `query` against `title`, ignoring case. Returns `None` if it doesn't match.
+pub fn fuzzy_match(query: &str, title: &str) -> Option<FuzzyMatch> {
+    match_title(query, title, true)
+}
+
+/// Like `fuzzy_match`, but only as a subsequence, without the slower check for typos
+pub fn subsequence_fuzzy_match(query: &str, title: &str) -> Option<FuzzyMatch> {
+    match_title(query, title, false)
+}
+
+fn match_title(query: &str, title: &str, allow_typos: bool) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    let title: Vec<char> = title.chars().collect();
+    let folded: Vec<char> = title.iter().copied().map(fold).collect();
+    if query.is_empty() {
+        return None;
+    }
+
+    subsequence_match(&query, &title, &folded)
+        .or_else(|| allow_typos.then(|| typo_match(&query, &title, &folded))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(query: &str, title: &str) -> f64 {
+        fuzzy_match(query, title).map_or(f64::NEG_INFINITY, |found| found.score)
+    }
+
+    fn ranges(query: &str, title: &str) -> Vec<(usize, usize)> {
+        fuzzy_match(query, title)
+            .unwrap()
+            .matches
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn matches_abbreviations() {
+        assert_eq!(ranges("mtg", "Meeting"), vec![(0, 1), (3, 4), (6, 7)]);
+        assert!(fuzzy_match("xyz", "Meeting").is_none());
+    }
+
+    #[test]
+    fn prefers_word_starts_and_runs() {
+        assert!(score("pn", "Project Notes") > score("pn", "Happen"));
+        assert!(score("note", "Notes") > score("note", "No time"));
+        assert!(score("note", "Notes") > score("note", "Old notes"));
+    }
+
+    #[test]
+    fn tolerates_typos() {
+        assert!(fuzzy_match("meetnig", "Meeting").is_some());
+        assert!(subsequence_fuzzy_match("meetnig", "Meeting").is_none());
+        assert!(score("meeting", "Meeting") > score("meetnig", "Meeting"));
+        // Short queries have to match exactly
+        assert!(fuzzy_match("mtx", "Meeting").is_none());
+    }
+
+    #[test]
+    fn ignores_case_and_accents() {
+        assert_eq!(ranges("resume", "Résumé"), vec![(0, 6)]);
+    }
+
+    #[test]
+    fn ranges_are_in_utf16_code_units() {
+        assert_eq!(ranges("b", "😀 b"), vec![(3, 4)]);
+    }
+}
@@ -0,0 +1,362 @@
+// The vault health report: problems with the notes themselves, as opposed to problems with
+// the database (see repair_commands.rs). Every finding names the page it's on, so the UI
+// can open it. Pages in the trash are left out.
+
+use crate::db::get_pool;
+use crate::image_store::ImageStore;
+use crate::storage_commands::file_size;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::fmt::Write;
+use std::fs;
+
+/// Images bigger than this are reported, unless the caller picks another threshold
+const DEFAULT_LARGE_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PageFinding {
+    pub page_id: i64,
+    pub title: String,
+}
+
+/// An internal link to a page that doesn't exist or is in the trash
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenLink {
+    /// The page the link is on
+    pub page_id: i64,
+    pub title: String,
+    pub target_page_id: i64,
+    /// Title of the linked page if it's in the trash, `None` if it doesn't exist
+    pub target_title: Option<String>,
+    /// Which of the page's links it is, counting from 0
+    pub position: i64,
+}
+
+/// Pages that share a title or filename
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Duplicate {
+    pub value: String,
+    pub pages: Vec<PageFinding>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeImage {
+    pub page_id: i64,
+    pub title: String,
+    pub attachment_id: i64,
+    pub original_filename: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultHealthReport {
+    pub broken_links: Vec<BrokenLink>,
+    /// Pages with no tags and no links to or from other pages
+    pub orphan_pages: Vec<PageFinding>,
+    pub untitled_pages: Vec<PageFinding>,
+    /// Pages with no text
+    pub empty_pages: Vec<PageFinding>,
+    /// Titles are compared ignoring case and surrounding spaces
+    pub duplicate_titles: Vec<Duplicate>,
+    /// Filenames are compared ignoring case, since most file systems do
+    pub duplicate_filenames: Vec<Duplicate>,
+    pub large_images: Vec<LargeImage>,
+    /// Size above which images count as large
+    pub large_image_bytes: u64,
+}
+
+fn display_title(title: &str) -> &str {
+    if title.trim().is_empty() {
+        "Untitled"
+    } else {
+        title
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MB {
+        format!("{:.1} MB", bytes as f64 / MB)
+    } else {
+        format!("{:.0} KB", bytes as f64 / 1024.0)
+    }
+}
+
+impl VaultHealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.broken_links.is_empty()
+            && self.orphan_pages.is_empty()
+            && self.untitled_pages.is_empty()
+            && self.empty_pages.is_empty()
+            && self.duplicate_titles.is_empty()
+            && self.duplicate_filenames.is_empty()
+            && self.large_images.is_empty()
+    }
+
+    /// The report as a Markdown document, one section per kind of finding
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Vault Health\n\n");
+        if self.is_healthy() {
+            out.push_str("No problems found.\n");
+            return out;
+        }
+
+        let page = |finding: &PageFinding| {
+            format!(
+                "{} (page {})",
+                display_title(&finding.title),
+                finding.page_id
+            )
+        };
+        let mut section = |heading: &str, lines: Vec<String>| {
+            if lines.is_empty() {
+                return;
+            }
+            let _ = writeln!(out, "## {} ({})\n", heading, lines.len());
+            for line in lines {
+                let _ = writeln!(out, "- {}", line);
+            }
+            out.push('\n');
+        };
+
+        section(
+            "Broken links",
+            self.broken_links
+                .iter()
+                .map(|link| {
+                    let target = match &link.target_title {
+                        Some(title) => format!(
+                            "{} (page {}), which is in the trash",
+                            display_title(title),
+                            link.target_page_id
+                        ),
+                        None => format!("page {}, which doesn't exist", link.target_page_id),
+                    };
+                    format!(
+                        "{} (page {}) links to {}",
+                        display_title(&link.title),
+                        link.page_id,
+                        target
+                    )
+                })
+                .collect(),
+        );
+        section("Orphan pages", self.orphan_pages.iter().map(page).collect());
+        section(
+            "Untitled pages",
+            self.untitled_pages.iter().map(page).collect(),
+        );
+        section("Empty pages", self.empty_pages.iter().map(page).collect());
+        for (heading, duplicates) in [
+            ("Duplicate titles", &self.duplicate_titles),
+            ("Duplicate filenames", &self.duplicate_filenames),
+        ] {
+            section(
+                heading,
+                duplicates
+                    .iter()
+                    .map(|duplicate| {
+                        let pages: Vec<String> = duplicate.pages.iter().map(page).collect();
+                        format!("\"{}\": {}", duplicate.value, pages.join(", "))
+                    })
+                    .collect(),
+            );
+        }
+        section(
+            &format!("Images over {}", format_bytes(self.large_image_bytes)),
+            self.large_images
+                .iter()
+                .map(|image| {
+                    format!(
+                        "{} ({}) on {} (page {})",
+                        image.original_filename,
+                        format_bytes(image.bytes),
+                        display_title(&image.title),
+                        image.page_id
+                    )
+                })
+                .collect(),
+        );
+        out
+    }
+}
+
+async fn broken_links(pool: &SqlitePool) -> Result<Vec<BrokenLink>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT pl.source_page_id AS page_id, s.title, pl.target_page_id,
+                t.title AS target_title, pl.position
+         FROM page_links pl
+         JOIN pages s ON s.id = pl.source_page_id
+         LEFT JOIN pages t ON t.id = pl.target_page_id
+         WHERE s.archived_at IS NULL AND (t.id IS NULL OR t.archived_at IS NOT NULL)
+         ORDER BY pl.source_page_id, pl.position",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Pages with no tags, and no links to or from other pages that aren't in the trash
+async fn orphan_pages(pool: &SqlitePool) -> Result<Vec<PageFinding>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT p.id AS page_id, p.title FROM pages p
+         WHERE p.archived_at IS NULL
+           AND NOT EXISTS (SELECT 1 FROM tag_associations ta WHERE ta.page_id = p.id)
+           AND NOT EXISTS (
+               SELECT 1 FROM page_links pl JOIN pages t ON t.id = pl.target_page_id
+               WHERE pl.source_page_id = p.id AND t.id != p.id AND t.archived_at IS NULL
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM page_links pl JOIN pages s ON s.id = pl.source_page_id
+               WHERE pl.target_page_id = p.id AND s.id != p.id AND s.archived_at IS NULL
+           )
+         ORDER BY p.id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn pages_where(pool: &SqlitePool, condition: &str) -> Result<Vec<PageFinding>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "SELECT id AS page_id, title FROM pages
+         WHERE archived_at IS NULL AND {}
+         ORDER BY id",
+        condition
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+/// Groups pages whose `key` (an expression over `pages`) is the same, skipping blank keys
+async fn duplicates(
+    pool: &SqlitePool,
+    key: &str,
+    value: &str,
+) -> Result<Vec<Duplicate>, sqlx::Error> {
+    let rows: Vec<(String, String, i64, String)> = sqlx::query_as(&format!(
+        "SELECT {key} AS duplicate_key, {value}, id, title FROM pages
+         WHERE archived_at IS NULL AND {key} != '' AND {key} IN (
+             SELECT {key} FROM pages WHERE archived_at IS NULL
+             GROUP BY {key} HAVING COUNT(*) > 1
+         )
+         ORDER BY duplicate_key, id",
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut groups: Vec<(String, Duplicate)> = Vec::new();
+    for (key, value, page_id, title) in rows {
+        let finding = PageFinding { page_id, title };
+        match groups.last_mut() {
+            Some((last_key, group)) if *last_key == key => group.pages.push(finding),
+            _ => groups.push((
+                key,
+                Duplicate {
+                    value,
+                    pages: vec![finding],
+                },
+            )),
+        }
+    }
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+async fn large_images(
+    pool: &SqlitePool,
+    image_store: &ImageStore,
+    threshold: u64,
+) -> Result<Vec<LargeImage>, sqlx::Error> {
+    // Images not yet moved to the image store are still in `data`
+    let attachments: Vec<(i64, i64, String, String, Option<String>, i64)> = sqlx::query_as(
+        "SELECT ia.id, ia.page_id, p.title, ia.original_filename, ia.content_hash,
+                length(ia.data)
+         FROM image_attachments ia
+         JOIN pages p ON p.id = ia.page_id
+         WHERE p.archived_at IS NULL
+         ORDER BY ia.page_id, ia.id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut images: Vec<LargeImage> = attachments
+        .into_iter()
+        .map(
+            |(attachment_id, page_id, title, original_filename, content_hash, data_bytes)| {
+                let bytes = match content_hash {
+                    Some(hash) => file_size(&image_store.path_for(&hash)),
+                    None => data_bytes.max(0) as u64,
+                };
+                LargeImage {
+                    page_id,
+                    title,
+                    attachment_id,
+                    original_filename,
+                    bytes,
+                }
+            },
+        )
+        .filter(|image| image.bytes > threshold)
+        .collect();
+    images.sort_by_key(|image| std::cmp::Reverse(image.bytes));
+    Ok(images)
+}
+
+pub async fn vault_health_report(
+    pool: &SqlitePool,
+    image_store: &ImageStore,
+    large_image_bytes: u64,
+) -> Result<VaultHealthReport, sqlx::Error> {
+    Ok(VaultHealthReport {
+        broken_links: broken_links(pool).await?,
+        orphan_pages: orphan_pages(pool).await?,
+        untitled_pages: pages_where(pool, "trim(title) = ''").await?,
+        empty_pages: pages_where(pool, "trim(plain_text, ' ' || char(9, 10, 13)) = ''").await?,
+        duplicate_titles: duplicates(pool, "lower(trim(title))", "trim(title)").await?,
+        duplicate_filenames: duplicates(pool, "lower(filename)", "filename").await?,
+        large_images: large_images(pool, image_store, large_image_bytes).await?,
+        large_image_bytes,
+    })
+}
+
+async fn build_report(
+    app_handle: &tauri::AppHandle,
+    large_image_bytes: Option<u64>,
+) -> Result<VaultHealthReport, String> {
+    let pool = get_pool(app_handle).await?;
+    let image_store = ImageStore::current(app_handle)?;
+    vault_health_report(
+        &pool,
+        &image_store,
+        large_image_bytes.unwrap_or(DEFAULT_LARGE_IMAGE_BYTES),
+    )
+    .await
+    .map_err(|e| format!("Failed to check vault health: {}", e))
+}
+
+// Command to report broken links, orphan, untitled and empty pages, duplicate titles and
+// filenames, and images over `large_image_bytes`
+#[tauri::command]
+pub async fn get_vault_health_report(
+    app_handle: tauri::AppHandle,
+    large_image_bytes: Option<u64>,
+) -> Result<VaultHealthReport, String> {
+    build_report(&app_handle, large_image_bytes).await
+}
+
+// Command to save the vault health report to `path` as Markdown
+#[tauri::command]
+pub async fn export_vault_health_report(
+    app_handle: tauri::AppHandle,
+    path: String,
+    large_image_bytes: Option<u64>,
+) -> Result<(), String> {
+    let report = build_report(&app_handle, large_image_bytes).await?;
+    fs::write(&path, report.to_markdown())
+        .map_err(|e| format!("Failed to write health report: {}", e))?;
+    println!("Saved vault health report to {}", path);
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use crate::db::get_pool;
+use crate::image_pipeline::{
+    process_image, CompressionSettings, ThumbnailSize, DEFAULT_IMAGE_MAX_DIMENSION,
+    DEFAULT_IMAGE_QUALITY, IMAGE_MAX_DIMENSION_KEY, IMAGE_QUALITY_KEY, KEEP_IMAGE_METADATA_KEY,
+};
+use crate::image_store::ImageStore;
+use crate::settings::{get_bool_setting, get_u64_setting};
+use base64::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedImageAttachment {
+    pub id: i64,
+    pub file_extension: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageAttachment {
+    pub data_url: String,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub original_filename: String,
+    pub file_extension: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecompressionReport {
+    pub images_processed: u64,
+    pub images_changed: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// Content hashes of images that were left as they were because they couldn't be read,
+    /// decoded or saved
+    pub skipped_images: Vec<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ImageAttachmentRow {
+    mime_type: String,
+    content_hash: Option<String>,
+    width: Option<i64>,
+    height: Option<i64>,
+    original_filename: String,
+    file_extension: String,
+}
+
+fn compression_settings(app_handle: &tauri::AppHandle) -> CompressionSettings {
+    let max_dimension = get_u64_setting(
+        app_handle,
+        IMAGE_MAX_DIMENSION_KEY,
+        DEFAULT_IMAGE_MAX_DIMENSION,
+    );
+    let quality = get_u64_setting(app_handle, IMAGE_QUALITY_KEY, DEFAULT_IMAGE_QUALITY);
+
+    CompressionSettings {
+        max_dimension: max_dimension.min(u32::MAX as u64) as u32,
+        quality: quality.clamp(1, 100) as u8,
+        recompress_lossy: false,
+    }
+}
+
+// Command to store an image in the image store and attach it to a page.
+// Dimensions are read from the image itself, large images are downscaled, and
+// EXIF/GPS metadata is stripped unless the user has opted to keep it.
+#[tauri::command]
+pub async fn create_image_attachment(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+    mime_type: String,
+    data: String,
+    original_filename: String,
+    file_extension: String,
+) -> Result<CreatedImageAttachment, String> {
+    let bytes = BASE64_STANDARD
+        .decode(&data)
+        .map_err(|e| format!("Invalid image data: {}", e))?;
+
+    let keep_metadata = get_bool_setting(&app_handle, KEEP_IMAGE_METADATA_KEY, false);
+    let compression = compression_settings(&app_handle);
+    let processed = tauri::async_runtime::spawn_blocking(move || {
+        process_image(bytes, keep_metadata, compression)
+    })
+    .await
+    .map_err(|e| format!("Image processing failed: {}", e))??;
+
+    let image_store = ImageStore::current(&app_handle)?;
+    let content_hash = image_store.put(&processed.bytes)?;
+
+    let pool = get_pool(&app_handle).await?;
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO image_attachments (
+            page_id, mime_type, data, content_hash, width, height, original_filename, file_extension
+        ) VALUES (?, ?, X'', ?, ?, ?, ?, ?) RETURNING id",
+    )
+    .bind(page_id)
+    .bind(&mime_type)
+    .bind(&content_hash)
+    .bind(processed.width)
+    .bind(processed.height)
+    .bind(&original_filename)
+    .bind(&file_extension)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| format!("Failed to save image attachment: {}", e))?;
+
+    // Warm the thumbnail cache in the background
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = image_store.generate_thumbnails(&content_hash) {
+            eprintln!("Failed to generate thumbnails for image {}: {}", id, e);
+        }
+    });
+
+    Ok(CreatedImageAttachment { id, file_extension })
+}
+
+// Command to load an image attachment as a data URL
+#[tauri::command]
+pub async fn get_image_attachment(
+    app_handle: tauri::AppHandle,
+    id: i64,
+) -> Result<Option<ImageAttachment>, String> {
+    let pool = get_pool(&app_handle).await?;
+    let row = sqlx::query_as::<_, ImageAttachmentRow>(
+        "SELECT mime_type, content_hash, width, height, original_filename, file_extension
+         FROM image_attachments WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| format!("Failed to fetch image attachment {}: {}", id, e))?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let Some(content_hash) = row.content_hash else {
+        return Err(format!("Image attachment {} has not been migrated", id));
+    };
+
+    let bytes = ImageStore::current(&app_handle)?.read(&content_hash)?;
+    let data_url = format!(
+        "data:{};base64,{}",
+        row.mime_type,
+        BASE64_STANDARD.encode(bytes)
+    );
+
+    Ok(Some(ImageAttachment {
+        data_url,
+        width: row.width,
+        height: row.height,
+        original_filename: row.original_filename,
+        file_extension: row.file_extension,
+    }))
+}
+
+// Command to load a thumbnail of an image attachment as a data URL
+#[tauri::command]
+pub async fn get_image_thumbnail(
+    app_handle: tauri::AppHandle,
+    id: i64,
+    size: ThumbnailSize,
+) -> Result<Option<String>, String> {
+    let pool = get_pool(&app_handle).await?;
+    let content_hash: Option<Option<String>> =
+        sqlx::query_scalar("SELECT content_hash FROM image_attachments WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(|e| format!("Failed to fetch image attachment {}: {}", id, e))?;
+
+    let Some(content_hash) = content_hash else {
+        return Ok(None);
+    };
+    let Some(content_hash) = content_hash else {
+        return Err(format!("Image attachment {} has not been migrated", id));
+    };
+
+    let image_store = ImageStore::current(&app_handle)?;
+    let bytes =
+        tauri::async_runtime::spawn_blocking(move || image_store.thumbnail(&content_hash, size))
+            .await
+            .map_err(|e| format!("Thumbnail generation failed: {}", e))??;
+
+    let mime_type = image::guess_format(&bytes)
+        .map(|f| f.to_mime_type())
+        .unwrap_or("application/octet-stream");
+    Ok(Some(format!(
+        "data:{};base64,{}",
+        mime_type,
+        BASE64_STANDARD.encode(bytes)
+    )))
+}
+
+// Command to downscale and recompress every stored image using the current settings,
+// including JPEGs and lossy WebPs that imports leave alone. Images are only replaced when the
+// result is smaller.
+#[tauri::command]
+pub async fn recompress_all_images(
+    app_handle: tauri::AppHandle,
+) -> Result<RecompressionReport, String> {
+    let pool = get_pool(&app_handle).await?;
+    let image_store = ImageStore::current(&app_handle)?;
+    let keep_metadata = get_bool_setting(&app_handle, KEEP_IMAGE_METADATA_KEY, false);
+    let compression = CompressionSettings {
+        recompress_lossy: true,
+        ..compression_settings(&app_handle)
+    };
+
+    let content_hashes: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT content_hash FROM image_attachments WHERE content_hash IS NOT NULL",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to fetch images: {}", e))?;
+
+    let mut report = RecompressionReport::default();
+    for old_hash in content_hashes {
+        let bytes = match image_store.read(&old_hash) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("Skipping image {}: {}", old_hash, e);
+                report.skipped_images.push(old_hash);
+                continue;
+            }
+        };
+        let size_before = bytes.len() as u64;
+        report.images_processed += 1;
+        report.bytes_before += size_before;
+
+        let processed = tauri::async_runtime::spawn_blocking(move || {
+            process_image(bytes, keep_metadata, compression)
+        })
+        .await
+        .map_err(|e| format!("Image processing failed: {}", e))?;
+
+        let processed = match processed {
+            Ok(p) if (p.bytes.len() as u64) < size_before => p,
+            Ok(_) => {
+                report.bytes_after += size_before;
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Skipping image {}: {}", old_hash, e);
+                report.bytes_after += size_before;
+                report.skipped_images.push(old_hash);
+                continue;
+            }
+        };
+
+        let new_hash = match image_store.put(&processed.bytes) {
+            Ok(new_hash) => new_hash,
+            Err(e) => {
+                eprintln!("Skipping image {}: {}", old_hash, e);
+                report.bytes_after += size_before;
+                report.skipped_images.push(old_hash);
+                continue;
+            }
+        };
+        sqlx::query(
+            "UPDATE image_attachments SET content_hash = ?, width = ?, height = ?
+             WHERE content_hash = ?",
+        )
+        .bind(&new_hash)
+        .bind(processed.width)
+        .bind(processed.height)
+        .bind(&old_hash)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to update image {}: {}", old_hash, e))?;
+
+        report.images_changed += 1;
+        report.bytes_after += processed.bytes.len() as u64;
+    }
+
+    println!(
+        "Recompressed {} of {} images: {} -> {} bytes, {} skipped",
+        report.images_changed,
+        report.images_processed,
+        report.bytes_before,
+        report.bytes_after,
+        report.skipped_images.len()
+    );
+    Ok(report)
+}
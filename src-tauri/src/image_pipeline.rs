@@ -0,0 +1,817 @@
+// Decoding, metadata stripping and thumbnailing for image attachments
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::imageops::FilterType as ResizeFilter;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader};
+use serde::Deserialize;
+use std::io::Cursor;
+
+/// Settings key for keeping EXIF/GPS metadata in imported images
+pub const KEEP_IMAGE_METADATA_KEY: &str = "keep_image_metadata";
+
+/// Settings key for the longest edge, in pixels, of a stored image (0 disables downscaling)
+pub const IMAGE_MAX_DIMENSION_KEY: &str = "image_max_dimension";
+pub const DEFAULT_IMAGE_MAX_DIMENSION: u64 = 2560;
+
+/// Settings key for the quality (1-100) used when re-encoding JPEGs and WebPs
+pub const IMAGE_QUALITY_KEY: &str = "image_quality";
+pub const DEFAULT_IMAGE_QUALITY: u64 = 85;
+
+const THUMBNAIL_JPEG_QUALITY: u8 = 80;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// How imported images are downscaled and recompressed
+#[derive(Clone, Copy, Debug)]
+pub struct CompressionSettings {
+    /// Longest edge in pixels; 0 keeps the original size
+    pub max_dimension: u32,
+    /// Quality for JPEGs and lossy WebPs. PNGs and lossless WebPs are always re-encoded
+    /// losslessly.
+    pub quality: u8,
+    /// Whether to re-encode JPEGs and lossy WebPs at `quality` even when they aren't
+    /// downscaled. Each re-encode loses some detail, so this is only set when the user asks
+    /// for their images to be recompressed.
+    pub recompress_lossy: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThumbnailSize {
+    Small,
+    Medium,
+}
+
+impl ThumbnailSize {
+    pub const ALL: [ThumbnailSize; 2] = [ThumbnailSize::Small, ThumbnailSize::Medium];
+
+    /// The longest edge of the thumbnail, in pixels
+    pub fn max_dimension(self) -> u32 {
+        match self {
+            ThumbnailSize::Small => 128,
+            ThumbnailSize::Medium => 512,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThumbnailSize::Small => "small",
+            ThumbnailSize::Medium => "medium",
+        }
+    }
+}
+
+pub struct ProcessedImage {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct ImageInfo {
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    icc_profile: Option<Vec<u8>>,
+}
+
+fn read_info(bytes: &[u8]) -> Result<ImageInfo, String> {
+    let reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let format = reader.format().ok_or("Unrecognized image format")?;
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let (width, height) = decoder.dimensions();
+
+    Ok(ImageInfo {
+        format,
+        width,
+        height,
+        orientation,
+        icc_profile,
+    })
+}
+
+fn swaps_axes(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    )
+}
+
+/// Returns the displayed (orientation-corrected) dimensions of an image
+pub fn read_dimensions(bytes: &[u8]) -> Result<(u32, u32), String> {
+    let info = read_info(bytes)?;
+    if swaps_axes(info.orientation) {
+        Ok((info.height, info.width))
+    } else {
+        Ok((info.width, info.height))
+    }
+}
+
+/// Decodes an image with its orientation applied
+pub fn decode_oriented(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?
+        .into_decoder()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Encodes `image` as `format`, using `quality` for JPEGs and (lossy) WebPs and maximum
+/// compression for PNGs
+pub fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let result = match format {
+        ImageFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut bytes, quality);
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)
+        }
+        ImageFormat::Png => {
+            let encoder = PngEncoder::new_with_quality(
+                &mut bytes,
+                CompressionType::Best,
+                FilterType::Adaptive,
+            );
+            image.write_with_encoder(encoder)
+        }
+        // The image crate only writes lossless WebPs, so lossy ones go through libwebp
+        ImageFormat::WebP => {
+            let rgba = image.to_rgba8();
+            let encoded =
+                webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height()).encode(quality as f32);
+            return Ok(encoded.to_vec());
+        }
+        _ => image.write_to(&mut Cursor::new(&mut bytes), format),
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(bytes)
+}
+
+/// Color chunks carried over when a PNG is re-encoded, so it looks the same afterwards
+const PNG_COLOR_CHUNKS: [&[u8; 4]; 5] = [b"iCCP", b"gAMA", b"cHRM", b"sRGB", b"cICP"];
+
+/// Encodes `image` like `source`, which it was decoded from: lossless WebPs stay lossless,
+/// and JPEGs keep their ICC profile and PNGs their color chunks
+fn encode_like(
+    image: &DynamicImage,
+    source: &[u8],
+    info: &ImageInfo,
+    quality: u8,
+) -> Result<Vec<u8>, String> {
+    match info.format {
+        ImageFormat::Jpeg => {
+            let mut bytes = Vec::new();
+            let mut encoder = JpegEncoder::new_with_quality(&mut bytes, quality);
+            if let Some(icc_profile) = &info.icc_profile {
+                encoder
+                    .set_icc_profile(icc_profile.clone())
+                    .map_err(|e| format!("Failed to encode image: {}", e))?;
+            }
+            DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(encoder)
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+            Ok(bytes)
+        }
+        ImageFormat::Png => {
+            let encoded = encode(image, info.format, quality)?;
+            Ok(copy_png_chunks(source, encoded, &PNG_COLOR_CHUNKS))
+        }
+        ImageFormat::WebP if is_lossless_webp(source) => {
+            let rgba = image.to_rgba8();
+            let encoded =
+                webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height()).encode_lossless();
+            Ok(encoded.to_vec())
+        }
+        _ => encode(image, info.format, quality),
+    }
+}
+
+fn is_lossless_webp(bytes: &[u8]) -> bool {
+    has_chunk(bytes, 12, b"VP8L", false)
+}
+
+/// Whether the image can be decoded and re-encoded without losing anything but metadata.
+/// Animated images are left alone because only their first frame would survive, and so are
+/// WebPs with an ICC profile because libwebp's simple encoder can't write one.
+fn can_reencode(bytes: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Jpeg => true,
+        ImageFormat::Png => !has_chunk(bytes, 8, b"acTL", true),
+        ImageFormat::WebP => {
+            !has_chunk(bytes, 12, b"ANIM", false) && !has_chunk(bytes, 12, b"ICCP", false)
+        }
+        _ => false,
+    }
+}
+
+/// Whether re-encoding the image at the same size can lose detail
+fn is_lossy(bytes: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Png => false,
+        ImageFormat::WebP => !is_lossless_webp(bytes),
+        _ => true,
+    }
+}
+
+/// Inserts the chunks of `source` whose types are in `chunk_types` into `png` right after its
+/// IHDR chunk. Returns `png` unchanged if either isn't a well-formed PNG.
+fn copy_png_chunks(source: &[u8], png: Vec<u8>, chunk_types: &[&[u8; 4]]) -> Vec<u8> {
+    // The signature and the IHDR chunk, which always comes first
+    const HEADER_LENGTH: usize = 8 + 12 + 13;
+    let Some(chunks) = png_chunks(source) else {
+        return png;
+    };
+    if png_chunks(&png).is_none() || png.len() < HEADER_LENGTH {
+        return png;
+    }
+
+    let mut out = png[..HEADER_LENGTH].to_vec();
+    for (chunk_type, chunk) in chunks {
+        if chunk_types.contains(&chunk_type) {
+            out.extend_from_slice(chunk);
+        }
+    }
+    out.extend_from_slice(&png[HEADER_LENGTH..]);
+    out
+}
+
+/// Splits a PNG into its chunks, with their types. None if it isn't a well-formed PNG.
+fn png_chunks(bytes: &[u8]) -> Option<Vec<(&[u8; 4], &[u8])>> {
+    if bytes.get(0..8)? != PNG_SIGNATURE {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < bytes.len() {
+        let length = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let chunk_type = bytes.get(pos + 4..pos + 8)?.try_into().ok()?;
+        // Length, type, data and CRC
+        let end = pos + 12 + length;
+        chunks.push((chunk_type, bytes.get(pos..end)?));
+        pos = end;
+    }
+    Some(chunks)
+}
+
+/// Scans PNG (big-endian, CRC-terminated) or RIFF (little-endian, padded) chunks for `wanted`
+fn has_chunk(bytes: &[u8], start: usize, wanted: &[u8; 4], png: bool) -> bool {
+    let mut pos = start;
+    while let Some(header) = bytes.get(pos..pos + 8) {
+        let (length, chunk_type) = if png {
+            (
+                u32::from_be_bytes([header[0], header[1], header[2], header[3]]),
+                &header[4..8],
+            )
+        } else {
+            (
+                u32::from_le_bytes([header[4], header[5], header[6], header[7]]),
+                &header[0..4],
+            )
+        };
+        if chunk_type == wanted {
+            return true;
+        }
+        let length = length as usize;
+        pos += if png {
+            12 + length
+        } else {
+            8 + length + (length & 1)
+        };
+    }
+    false
+}
+
+/// Prepares an image for storage:
+///
+/// - Images larger than `compression.max_dimension` are downscaled and re-encoded, unless
+///   re-encoding makes them bigger than the original.
+/// - Otherwise, EXIF/GPS metadata is stripped (unless `keep_metadata` is set) and lossless
+///   images are recompressed, keeping whichever version is smaller. JPEGs and lossy WebPs are
+///   only recompressed when `compression.recompress_lossy` is set.
+/// - Images whose EXIF orientation isn't upright are re-encoded with the rotation applied when
+///   their metadata is stripped, since the orientation would otherwise be lost.
+///
+/// The format never changes, so the attachment's MIME type and extension stay valid.
+pub fn process_image(
+    bytes: Vec<u8>,
+    keep_metadata: bool,
+    compression: CompressionSettings,
+) -> Result<ProcessedImage, String> {
+    let info = read_info(&bytes)?;
+    let (width, height) = if swaps_axes(info.orientation) {
+        (info.height, info.width)
+    } else {
+        (info.width, info.height)
+    };
+    let reencodable = can_reencode(&bytes, info.format);
+
+    let max = compression.max_dimension;
+    if reencodable && max > 0 && (width > max || height > max) {
+        let resized = decode_oriented(&bytes)?.resize(max, max, ResizeFilter::Lanczos3);
+        let encoded = encode_like(&resized, &bytes, &info, compression.quality)?;
+        if encoded.len() < bytes.len() {
+            return Ok(ProcessedImage {
+                bytes: encoded,
+                width: resized.width(),
+                height: resized.height(),
+            });
+        }
+    }
+
+    if keep_metadata {
+        return Ok(ProcessedImage {
+            bytes,
+            width,
+            height,
+        });
+    }
+
+    let stripped = if info.orientation != Orientation::NoTransforms {
+        None
+    } else {
+        match info.format {
+            ImageFormat::Jpeg => strip_jpeg_metadata(&bytes),
+            ImageFormat::Png => strip_png_metadata(&bytes),
+            ImageFormat::WebP => strip_webp_metadata(&bytes),
+            _ => Some(bytes.clone()),
+        }
+    };
+    let stripped = match stripped {
+        Some(b) => b,
+        None => encode_like(
+            &decode_oriented(&bytes)?,
+            &bytes,
+            &info,
+            compression.quality,
+        )?,
+    };
+
+    let recompress = !is_lossy(&bytes, info.format) || compression.recompress_lossy;
+    let bytes = if reencodable && recompress {
+        let recompressed = encode_like(
+            &decode_oriented(&stripped)?,
+            &stripped,
+            &info,
+            compression.quality,
+        )?;
+        if recompressed.len() < stripped.len() {
+            recompressed
+        } else {
+            stripped
+        }
+    } else {
+        stripped
+    };
+
+    Ok(ProcessedImage {
+        bytes,
+        width,
+        height,
+    })
+}
+
+/// Produces a thumbnail no larger than `size` on either edge.
+/// Opaque images become JPEGs and images with transparency become PNGs.
+pub fn make_thumbnail(bytes: &[u8], size: ThumbnailSize) -> Result<Vec<u8>, String> {
+    let image = decode_oriented(bytes)?;
+    let max = size.max_dimension();
+    let thumbnail = if image.width() <= max && image.height() <= max {
+        image
+    } else {
+        image.thumbnail(max, max)
+    };
+
+    let format = if thumbnail.color().has_alpha() {
+        ImageFormat::Png
+    } else {
+        ImageFormat::Jpeg
+    };
+    encode(&thumbnail, format, THUMBNAIL_JPEG_QUALITY)
+}
+
+/// Removes APP1 (EXIF/XMP) and APP13 (IPTC) segments from a JPEG without re-encoding it
+fn strip_jpeg_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut out = vec![0xFF, 0xD8];
+    let mut pos = 2;
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        // Start of scan: everything after this is image data
+        if marker == 0xDA {
+            out.extend_from_slice(&bytes[pos..]);
+            return Some(out);
+        }
+        let length = u16::from_be_bytes([*bytes.get(pos + 2)?, *bytes.get(pos + 3)?]) as usize;
+        let end = pos + 2 + length;
+        let segment = bytes.get(pos..end)?;
+        if marker != 0xE1 && marker != 0xED {
+            out.extend_from_slice(segment);
+        }
+        pos = end;
+    }
+}
+
+/// Removes eXIf and text chunks from a PNG
+fn strip_png_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = PNG_SIGNATURE.to_vec();
+    for (chunk_type, chunk) in png_chunks(bytes)? {
+        if !matches!(chunk_type, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            out.extend_from_slice(chunk);
+        }
+    }
+    Some(out)
+}
+
+/// Removes EXIF and XMP chunks from a WebP and clears their flags in the VP8X header
+fn strip_webp_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    let mut out = b"RIFF\0\0\0\0WEBP".to_vec();
+    let mut pos = 12;
+    while pos < bytes.len() {
+        let chunk_type = bytes.get(pos..pos + 4)?;
+        let length = u32::from_le_bytes(bytes.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        // Chunks are padded to an even length, though some writers leave out the last padding
+        // byte
+        let data_end = pos + 8 + length;
+        if data_end > bytes.len() {
+            return None;
+        }
+        let end = (data_end + (length & 1)).min(bytes.len());
+        let chunk = &bytes[pos..end];
+        match chunk_type {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let flags_offset = out.len() + 8;
+                out.extend_from_slice(chunk);
+                *out.get_mut(flags_offset)? &= !0x0C;
+            }
+            _ => out.extend_from_slice(chunk),
+        }
+        pos = end;
+    }
+
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: CompressionSettings = CompressionSettings {
+        max_dimension: 0,
+        quality: 85,
+        recompress_lossy: false,
+    };
+
+    fn jpeg() -> Vec<u8> {
+        encode(&DynamicImage::new_rgb8(8, 4), ImageFormat::Jpeg, 90).unwrap()
+    }
+
+    fn png() -> Vec<u8> {
+        encode(&DynamicImage::new_rgba8(8, 4), ImageFormat::Png, 0).unwrap()
+    }
+
+    /// Inserts APPn segments right after the SOI marker
+    fn with_jpeg_segments(jpeg: &[u8], segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut out = jpeg[..2].to_vec();
+        for (marker, data) in segments {
+            out.extend_from_slice(&[0xFF, *marker]);
+            out.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+            out.extend_from_slice(data);
+        }
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in bytes {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+        chunk
+    }
+
+    /// Inserts chunks right after IHDR
+    fn with_png_chunks(png: &[u8], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = png[..33].to_vec();
+        out.extend(chunks.concat());
+        out.extend_from_slice(&png[33..]);
+        out
+    }
+
+    fn riff_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = chunk_type.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    /// An extended (VP8X) WebP with the given flags and extra chunks after the image data
+    fn webp(flags: u8, extra_chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut lossless = Vec::new();
+        DynamicImage::new_rgba8(8, 4)
+            .write_to(&mut Cursor::new(&mut lossless), ImageFormat::WebP)
+            .unwrap();
+        // Canvas width and height minus one, as 24-bit little-endian numbers
+        let vp8x = [flags, 0, 0, 0, 7, 0, 0, 3, 0, 0];
+
+        let mut body = b"WEBP".to_vec();
+        body.extend(riff_chunk(b"VP8X", &vp8x));
+        body.extend_from_slice(&lossless[12..]);
+        body.extend(extra_chunks.concat());
+        let mut out = b"RIFF".to_vec();
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend(body);
+        out
+    }
+
+    #[test]
+    fn strips_exif_and_iptc_from_jpegs_but_keeps_icc_profiles() {
+        let icc: &[u8] = b"ICC_PROFILE\0\x01\x01profile";
+        let original = with_jpeg_segments(
+            &jpeg(),
+            &[
+                (0xE1, b"Exif\0\0MM\0\x2a"),
+                (0xE2, icc),
+                (0xE1, b"http://ns.adobe.com/xap/1.0/\0<x/>"),
+                (0xED, b"Photoshop 3.0\0"),
+            ],
+        );
+
+        let stripped = strip_jpeg_metadata(&original).unwrap();
+        assert_eq!(stripped, with_jpeg_segments(&jpeg(), &[(0xE2, icc)]));
+        assert_eq!(read_dimensions(&stripped).unwrap(), (8, 4));
+    }
+
+    #[test]
+    fn strips_text_and_exif_from_pngs_but_keeps_color_chunks() {
+        let color = [
+            png_chunk(b"gAMA", &45455u32.to_be_bytes()),
+            png_chunk(b"iCCP", b"profile\0\0data"),
+        ];
+        let original = with_png_chunks(
+            &png(),
+            &[
+                png_chunk(b"tEXt", b"Comment\0hello"),
+                color[0].clone(),
+                png_chunk(b"eXIf", b"MM\0\x2a"),
+                color[1].clone(),
+                png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x/>"),
+                png_chunk(b"tIME", &[7, 233, 1, 1, 0, 0, 0]),
+            ],
+        );
+
+        let stripped = strip_png_metadata(&original).unwrap();
+        assert_eq!(stripped, with_png_chunks(&png(), &color));
+    }
+
+    #[test]
+    fn strips_exif_and_xmp_from_webps_and_clears_their_flags() {
+        const ALPHA: u8 = 0x10;
+        const EXIF: u8 = 0x08;
+        const XMP: u8 = 0x04;
+        let original = webp(
+            ALPHA | EXIF | XMP,
+            &[
+                riff_chunk(b"EXIF", b"MM\0\x2a\0"),
+                riff_chunk(b"XMP ", b"<x/>"),
+            ],
+        );
+
+        let stripped = strip_webp_metadata(&original).unwrap();
+        assert_eq!(stripped, webp(ALPHA, &[]));
+        assert_eq!(read_dimensions(&stripped).unwrap(), (8, 4));
+    }
+
+    #[test]
+    fn tolerates_a_missing_final_padding_byte_in_webps() {
+        let original = webp(0, &[riff_chunk(b"XMP ", b"<x />")]);
+        let unpadded = &original[..original.len() - 1];
+        assert_eq!(strip_webp_metadata(unpadded).unwrap(), webp(0, &[]));
+    }
+
+    #[test]
+    fn rejects_truncated_and_garbage_input() {
+        let jpeg = with_jpeg_segments(&jpeg(), &[(0xE1, b"Exif\0\0MM\0\x2a")]);
+        let png = with_png_chunks(&png(), &[png_chunk(b"eXIf", b"MM\0\x2a")]);
+        let webp = webp(0x08, &[riff_chunk(b"EXIF", b"MM\0\x2a\0\0")]);
+
+        // Cut inside the metadata
+        assert_eq!(strip_jpeg_metadata(&jpeg[..10]), None);
+        assert_eq!(strip_png_metadata(&png[..40]), None);
+        assert_eq!(strip_webp_metadata(&webp[..webp.len() - 3]), None);
+
+        // No cut may panic
+        for len in 0..jpeg.len() {
+            strip_jpeg_metadata(&jpeg[..len]);
+        }
+        for len in 0..png.len() {
+            strip_png_metadata(&png[..len]);
+            has_chunk(&png[..len], 8, b"acTL", true);
+        }
+        for len in 0..webp.len() {
+            strip_webp_metadata(&webp[..len]);
+            has_chunk(&webp[..len], 12, b"ANIM", false);
+        }
+
+        // Lengths that run past the end
+        let mut huge = png.clone();
+        huge[33..37].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(strip_png_metadata(&huge), None);
+        assert!(!has_chunk(&huge, 8, b"acTL", true));
+        let mut huge = webp.clone();
+        huge[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(strip_webp_metadata(&huge), None);
+
+        let garbage: Vec<u8> = (0..200u32).map(|i| (i * 37 % 251) as u8).collect();
+        for bytes in [&[][..], &[0xFF, 0xD8][..], &garbage] {
+            assert_eq!(strip_jpeg_metadata(bytes), None);
+            assert_eq!(strip_png_metadata(bytes), None);
+            assert_eq!(strip_webp_metadata(bytes), None);
+            assert!(process_image(bytes.to_vec(), false, SETTINGS).is_err());
+        }
+    }
+
+    #[test]
+    fn finds_animation_chunks() {
+        let apng = with_png_chunks(&png(), &[png_chunk(b"acTL", &[0, 0, 0, 2, 0, 0, 0, 0])]);
+        assert!(can_reencode(&png(), ImageFormat::Png));
+        assert!(!can_reencode(&apng, ImageFormat::Png));
+
+        let animated = webp(0x02, &[riff_chunk(b"ANIM", &[0; 6])]);
+        assert!(can_reencode(&webp(0, &[]), ImageFormat::WebP));
+        assert!(!can_reencode(&animated, ImageFormat::WebP));
+    }
+
+    #[test]
+    fn processing_strips_metadata_unless_asked_to_keep_it() {
+        let original = with_jpeg_segments(&jpeg(), &[(0xE1, b"Exif\0\0MM\0\x2a")]);
+
+        let kept = process_image(original.clone(), true, SETTINGS).unwrap();
+        assert_eq!(kept.bytes, original);
+
+        let processed = process_image(original, false, SETTINGS).unwrap();
+        assert_eq!(
+            strip_jpeg_metadata(&processed.bytes).unwrap(),
+            processed.bytes
+        );
+        assert_eq!((processed.width, processed.height), (8, 4));
+    }
+
+    /// An image that doesn't compress well, so downscaling always makes it smaller
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        let mut state = 1u32;
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |_, _| {
+            let mut pixel = [0; 3];
+            for channel in &mut pixel {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                *channel = (state >> 16) as u8;
+            }
+            image::Rgb(pixel)
+        }))
+    }
+
+    /// `data` as an uncompressed zlib stream
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let length = data.len() as u16;
+        let mut out = vec![0x78, 0x01, 0x01];
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(data);
+        let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+            let a = (a + byte as u32) % 65521;
+            (a, (b + a) % 65521)
+        });
+        out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        out
+    }
+
+    fn downscale_to(max_dimension: u32) -> CompressionSettings {
+        CompressionSettings {
+            max_dimension,
+            ..SETTINGS
+        }
+    }
+
+    #[test]
+    fn processing_downscales_large_images() {
+        let large = encode(&noise(64, 32), ImageFormat::Png, 0).unwrap();
+        let processed = process_image(large, false, downscale_to(16)).unwrap();
+        assert_eq!((processed.width, processed.height), (16, 8));
+        assert_eq!(read_dimensions(&processed.bytes).unwrap(), (16, 8));
+    }
+
+    #[test]
+    fn reencoded_pngs_keep_their_color_chunks() {
+        let mut iccp = b"profile\0\0".to_vec();
+        iccp.extend(zlib_stored(b"not really a profile"));
+        let color = [
+            png_chunk(b"iCCP", &iccp),
+            png_chunk(b"gAMA", &45455u32.to_be_bytes()),
+        ];
+        let large = encode(&noise(64, 32), ImageFormat::Png, 0).unwrap();
+        let original = with_png_chunks(&large, &color);
+
+        let processed = process_image(original, false, downscale_to(16)).unwrap();
+        let small = encode(
+            &noise(64, 32).resize(16, 16, ResizeFilter::Lanczos3),
+            ImageFormat::Png,
+            0,
+        )
+        .unwrap();
+        assert_eq!(processed.bytes, with_png_chunks(&small, &color));
+    }
+
+    #[test]
+    fn reencoded_jpegs_keep_their_icc_profile() {
+        let original = encode(&noise(64, 32), ImageFormat::Jpeg, 95).unwrap();
+        let original = with_jpeg_segments(&original, &[(0xE2, b"ICC_PROFILE\0\x01\x01profile")]);
+
+        let processed = process_image(original, false, downscale_to(16)).unwrap();
+        let mut decoder = ImageReader::new(Cursor::new(&processed.bytes))
+            .with_guessed_format()
+            .unwrap()
+            .into_decoder()
+            .unwrap();
+        assert_eq!(decoder.dimensions(), (16, 8));
+        assert_eq!(decoder.icc_profile().unwrap().unwrap(), b"profile");
+    }
+
+    #[test]
+    fn lossy_images_are_only_recompressed_when_resized_or_asked_to() {
+        let original = encode(&noise(32, 16), ImageFormat::Jpeg, 100).unwrap();
+
+        let processed = process_image(original.clone(), false, SETTINGS).unwrap();
+        assert_eq!(processed.bytes, original);
+
+        let recompress = CompressionSettings {
+            quality: 50,
+            recompress_lossy: true,
+            ..SETTINGS
+        };
+        let processed = process_image(original.clone(), false, recompress).unwrap();
+        assert!(processed.bytes.len() < original.len());
+    }
+
+    #[test]
+    fn lossless_webps_stay_lossless() {
+        let mut original = Vec::new();
+        noise(64, 32)
+            .write_to(&mut Cursor::new(&mut original), ImageFormat::WebP)
+            .unwrap();
+        assert!(is_lossless_webp(&original));
+
+        let recompress = CompressionSettings {
+            recompress_lossy: true,
+            ..downscale_to(16)
+        };
+        let processed = process_image(original, false, recompress).unwrap();
+        assert_eq!((processed.width, processed.height), (16, 8));
+        assert!(is_lossless_webp(&processed.bytes));
+    }
+}
@@ -0,0 +1,246 @@
+// Content-addressed storage for image attachments.
+// Each file is named by the SHA-256 of its bytes, so identical images are stored once.
+
+use crate::image_pipeline::{make_thumbnail, read_dimensions, ThumbnailSize};
+use crate::vault::current_vault_dir;
+use base64::prelude::*;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Files younger than this are never swept, because the row that refers to
+/// them may not have been committed yet.
+const SWEEP_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone)]
+pub struct ImageStore {
+    pub dir: PathBuf,
+}
+
+impl ImageStore {
+    /// Opens the store in the open vault's `images` directory, creating it if needed
+    pub fn current(app_handle: &tauri::AppHandle) -> Result<Self, String> {
+        Self::open_at(current_vault_dir(app_handle)?.join("images"))
+    }
+
+    pub fn open_at(dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(dir.join("thumbnails"))
+            .map_err(|e| format!("Failed to create image store: {}", e))?;
+        Ok(Self { dir })
+    }
+
+    pub fn path_for(&self, content_hash: &str) -> PathBuf {
+        self.dir.join(content_hash)
+    }
+
+    fn thumbnail_path_for(&self, content_hash: &str, size: ThumbnailSize) -> PathBuf {
+        self.dir
+            .join("thumbnails")
+            .join(format!("{}-{}", content_hash, size.name()))
+    }
+
+    /// Writes `bytes` to the store if they aren't already there and returns their hash
+    pub fn put(&self, bytes: &[u8]) -> Result<String, String> {
+        let content_hash = format!("{:x}", Sha256::digest(bytes));
+        let path = self.path_for(&content_hash);
+        // An existing file may be old and unreferenced, so it's touched to keep `sweep` from
+        // deleting it before the row that's about to refer to it is committed. If that fails
+        // it's rewritten instead, which also resets its age.
+        if path.exists() && touch(&path).is_ok() {
+            return Ok(content_hash);
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated blob
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bytes)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Failed to write image {}: {}", content_hash, e))?;
+        Ok(content_hash)
+    }
+
+    pub fn read(&self, content_hash: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.path_for(content_hash))
+            .map_err(|e| format!("Failed to read image {}: {}", content_hash, e))
+    }
+
+    /// Returns a cached thumbnail, generating it first if needed
+    pub fn thumbnail(&self, content_hash: &str, size: ThumbnailSize) -> Result<Vec<u8>, String> {
+        let path = self.thumbnail_path_for(content_hash, size);
+        if let Ok(bytes) = fs::read(&path) {
+            return Ok(bytes);
+        }
+
+        let bytes = make_thumbnail(&self.read(content_hash)?, size)?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, &bytes)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Failed to write thumbnail {}: {}", content_hash, e))?;
+        Ok(bytes)
+    }
+
+    /// Generates every thumbnail size for an image ahead of time
+    pub fn generate_thumbnails(&self, content_hash: &str) -> Result<(), String> {
+        for size in ThumbnailSize::ALL {
+            self.thumbnail(content_hash, size)?;
+        }
+        Ok(())
+    }
+
+    /// Fills in `width`/`height` for image attachments that were saved without them
+    pub async fn backfill_dimensions(&self, pool: &SqlitePool) -> Result<u64, String> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, content_hash FROM image_attachments
+             WHERE content_hash IS NOT NULL AND (width IS NULL OR height IS NULL)",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Failed to fetch images without dimensions: {}", e))?;
+
+        let mut updated = 0;
+        for (image_id, content_hash) in rows {
+            let (width, height) = match self.read(&content_hash).and_then(|b| read_dimensions(&b)) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("Skipping image {}: {}", image_id, e);
+                    continue;
+                }
+            };
+            sqlx::query("UPDATE image_attachments SET width = ?, height = ? WHERE id = ?")
+                .bind(width)
+                .bind(height)
+                .bind(image_id)
+                .execute(pool)
+                .await
+                .map_err(|e| format!("Failed to update image {}: {}", image_id, e))?;
+            updated += 1;
+        }
+        Ok(updated)
+    }
+
+    /// Moves images still stored inline as base64 in `image_attachments.data` into the store
+    pub async fn migrate_inline_images(&self, pool: &SqlitePool) -> Result<u64, String> {
+        let rows: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, data FROM image_attachments WHERE content_hash IS NULL")
+                .fetch_all(pool)
+                .await
+                .map_err(|e| format!("Failed to fetch inline images: {}", e))?;
+
+        let mut migrated = 0;
+        for (image_id, data_base64) in rows {
+            let bytes = match BASE64_STANDARD.decode(&data_base64) {
+                Ok(b) => b,
+                Err(e) => {
+                    eprintln!("Skipping image {}: invalid base64: {}", image_id, e);
+                    continue;
+                }
+            };
+            let content_hash = self.put(&bytes)?;
+
+            sqlx::query("UPDATE image_attachments SET content_hash = ?, data = X'' WHERE id = ?")
+                .bind(&content_hash)
+                .bind(image_id)
+                .execute(pool)
+                .await
+                .map_err(|e| format!("Failed to update image {}: {}", image_id, e))?;
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
+
+    /// Moves inline images into the store and records missing dimensions.
+    /// Run whenever a database is opened, since it may have been written by an older version.
+    pub async fn upgrade_attachments(&self, pool: &SqlitePool) {
+        match self.migrate_inline_images(pool).await {
+            Ok(0) => {}
+            Ok(n) => println!("Moved {} images into the image store", n),
+            Err(e) => eprintln!("Image store migration error: {}", e),
+        }
+        match self.backfill_dimensions(pool).await {
+            Ok(0) => {}
+            Ok(n) => println!("Recorded dimensions for {} images", n),
+            Err(e) => eprintln!("Image dimension backfill error: {}", e),
+        }
+    }
+
+    /// Deletes files that no image attachment refers to.
+    /// Returns the number of files deleted and their total size.
+    pub async fn sweep(&self, pool: &SqlitePool) -> Result<(u64, u64), String> {
+        let referenced: HashSet<String> = sqlx::query_scalar(
+            "SELECT DISTINCT content_hash FROM image_attachments WHERE content_hash IS NOT NULL",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Failed to fetch image hashes: {}", e))?
+        .into_iter()
+        .collect();
+
+        let entries = fs::read_dir(&self.dir)
+            .and_then(|originals| {
+                let thumbnails = fs::read_dir(self.dir.join("thumbnails"))?;
+                Ok(originals.chain(thumbnails))
+            })
+            .map_err(|e| format!("Failed to read image store: {}", e))?;
+
+        let mut files_deleted = 0;
+        let mut bytes_reclaimed = 0;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Thumbnails are named `<hash>-<size>`
+            let content_hash = name.split('-').next().unwrap_or_default();
+            if referenced.contains(content_hash) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() || is_recent(&metadata) {
+                continue;
+            }
+            if let Err(e) = fs::remove_file(entry.path()) {
+                eprintln!("Failed to delete unused image {}: {}", name, e);
+                continue;
+            }
+            files_deleted += 1;
+            bytes_reclaimed += metadata.len();
+        }
+        Ok((files_deleted, bytes_reclaimed))
+    }
+
+    /// Number of images in the store, not counting thumbnails
+    pub fn image_count(&self) -> Result<u64, String> {
+        let entries =
+            fs::read_dir(&self.dir).map_err(|e| format!("Failed to read image store: {}", e))?;
+        Ok(entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_none())
+            .filter(|entry| entry.metadata().is_ok_and(|m| m.is_file()))
+            .count() as u64)
+    }
+
+    /// Copies a stored image to `destination`
+    pub fn copy_to(&self, content_hash: &str, destination: &Path) -> Result<(), String> {
+        fs::copy(self.path_for(content_hash), destination)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to copy image {}: {}", content_hash, e))
+    }
+}
+
+/// Sets a file's modification time to now
+fn touch(path: &Path) -> std::io::Result<()> {
+    fs::File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+fn is_recent(metadata: &fs::Metadata) -> bool {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age < SWEEP_GRACE_PERIOD)
+        .unwrap_or(true)
+}
@@ -0,0 +1,29 @@
+use crate::db::get_pool;
+use std::future::Future;
+use std::time::Duration;
+
+/// Runs `job` once right away and then every `interval` for the lifetime of the app.
+/// Errors are logged and don't stop the schedule. Every job works on the database, so runs are
+/// skipped while it's locked or couldn't be opened, rather than failing each time.
+pub fn spawn_periodic<F, Fut>(
+    app_handle: tauri::AppHandle,
+    name: &'static str,
+    interval: Duration,
+    job: F,
+) where
+    F: Fn(tauri::AppHandle) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if get_pool(&app_handle).await.is_err() {
+                continue;
+            }
+            if let Err(e) = job(app_handle.clone()).await {
+                eprintln!("Background job '{}' failed: {}", name, e);
+            }
+        }
+    });
+}
@@ -0,0 +1,136 @@
+// Helpers for reading the serialized Lexical editor state stored in `pages.lexical_json`
+
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Calls `visit` for every node in a serialized Lexical tree, depth first
+pub fn walk_nodes(node: &JsonValue, visit: &mut impl FnMut(&JsonValue)) {
+    visit(node);
+    if let Some(children) = node.get("children").and_then(|c| c.as_array()) {
+        for child in children {
+            walk_nodes(child, visit);
+        }
+    }
+}
+
+/// Returns the root node of a page's `lexical_json`, or `None` if it can't be parsed
+pub fn parse_root(lexical_json: &str) -> Option<JsonValue> {
+    let state: JsonValue = serde_json::from_str(lexical_json).ok()?;
+    state.get("root").cloned()
+}
+
+/// Collects the IDs of all image nodes (see ImageNode.tsx) in a page's `lexical_json`
+pub fn collect_image_ids(lexical_json: &str, ids: &mut HashSet<i64>) {
+    let Some(root) = parse_root(lexical_json) else {
+        return;
+    };
+    walk_nodes(&root, &mut |node| {
+        if node.get("type").and_then(|t| t.as_str()) == Some("image") {
+            if let Some(id) = node.get("id").and_then(|id| id.as_i64()) {
+                ids.insert(id);
+            }
+        }
+    });
+}
+
+/// Element node types that Lexical renders inline, so no paragraph break follows them
+const INLINE_ELEMENT_TYPES: [&str; 2] = ["link", "autolink"];
+
+/// Mirrors Lexical's `getTextContent()`: block elements are separated by a blank line.
+/// Decorators such as images and internal links are passed to `decorator_text`, which can
+/// write text for them; Lexical leaves them out.
+fn text_content(
+    node: &JsonValue,
+    out: &mut String,
+    decorator_text: &mut impl FnMut(&JsonValue, &mut String),
+) {
+    match node.get("type").and_then(|t| t.as_str()) {
+        Some("text") => {
+            if let Some(text) = node.get("text").and_then(|t| t.as_str()) {
+                out.push_str(text);
+            }
+            return;
+        }
+        Some("linebreak") => {
+            out.push('\n');
+            return;
+        }
+        Some("tab") => {
+            out.push('\t');
+            return;
+        }
+        _ => {}
+    }
+
+    let Some(children) = node.get("children").and_then(|c| c.as_array()) else {
+        decorator_text(node, out);
+        return;
+    };
+    for (i, child) in children.iter().enumerate() {
+        text_content(child, out, decorator_text);
+        let is_block = child.get("children").is_some()
+            && !child
+                .get("type")
+                .and_then(|t| t.as_str())
+                .is_some_and(|t| INLINE_ELEMENT_TYPES.contains(&t));
+        if is_block && i + 1 < children.len() {
+            out.push_str("\n\n");
+        }
+    }
+}
+
+/// Returns the plain text of a page's `lexical_json`, as the editor would save it in `plain_text`
+pub fn plain_text(lexical_json: &str) -> Option<String> {
+    let root = parse_root(lexical_json)?;
+    let mut text = String::new();
+    text_content(&root, &mut text, &mut |_, _| {});
+    Some(text)
+}
+
+/// Looks up a node by a JSON path such as `$.root.children[0].children[1]`, as SQLite's
+/// `json_tree` reports them
+pub fn node_at_path<'a>(state: &'a JsonValue, path: &str) -> Option<&'a JsonValue> {
+    let mut node = state;
+    for segment in path.strip_prefix("$.")?.replace('[', ".[").split('.') {
+        node = match segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(index) => node.get(index.parse::<usize>().ok()?)?,
+            None => node.get(segment)?,
+        };
+    }
+    Some(node)
+}
+
+/// Returns the text of the block holding the internal link at `path` in a page's Lexical
+/// state, and where the link is in it (in bytes). Internal links are written as the titles
+/// of the pages they link to, from `titles`.
+pub fn internal_link_context(
+    state: &JsonValue,
+    path: &str,
+    titles: &HashMap<i64, String>,
+) -> Option<(String, Range<usize>)> {
+    let link = node_at_path(state, path)?;
+    let (parent_path, _) = path.rsplit_once('.')?;
+    let block = node_at_path(state, parent_path)?;
+
+    let mut text = String::new();
+    let mut link_range = None;
+    text_content(block, &mut text, &mut |node, out| {
+        if node.get("type").and_then(|t| t.as_str()) != Some("internal-link") {
+            return;
+        }
+        let start = out.len();
+        match node.get("pageId").and_then(|id| id.as_i64()) {
+            // Like the Markdown export (see InternalLinkNode.tsx)
+            Some(id) => match titles.get(&id) {
+                Some(title) => out.push_str(title),
+                None => out.push_str(&format!("Page {}", id)),
+            },
+            None => return,
+        }
+        if std::ptr::eq(node, link) {
+            link_range = Some(start..out.len());
+        }
+    });
+    Some((text, link_range?))
+}
@@ -1,10 +1,40 @@
+mod backlink_commands;
+mod backup_commands;
+mod classification_commands;
 mod commands;
 mod db;
 mod db_wrapper;
+mod encryption_commands;
+mod export_commands;
+mod fleeting_commands;
+mod fuzzy;
+mod health_commands;
+mod image_commands;
+mod image_pipeline;
+mod image_store;
+mod jobs;
+mod lexical;
+mod maintenance_commands;
+mod md_sync_commands;
 mod menu;
 mod menu_commands;
-mod md_sync_commands;
+mod relation_commands;
+mod repair_commands;
+mod resurface_commands;
+mod schema_commands;
+mod search_commands;
+mod search_index;
+mod search_query;
+mod settings;
+mod stats_commands;
+mod storage_commands;
+mod tag_cluster_commands;
+mod tag_commands;
+mod trash_commands;
+mod vault;
+mod vault_commands;
 
+use std::time::Duration;
 use tauri::Manager;
 
 // Command handlers have been moved to commands.rs
@@ -19,17 +49,81 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
-        .plugin(
-            tauri_plugin_sql::Builder::default()
-                .add_migrations("sqlite:notesponge.db", db::get_migrations())
-                .build(),
-        )
+        // The database is opened and migrated in Rust (see db.rs), which hands the
+        // connection pool to the SQL plugin for the frontend to use
+        .plugin(tauri_plugin_sql::Builder::default().build())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             menu_commands::update_formatting_menu_state,
             menu_commands::disable_editor_menus,
             md_sync_commands::sync_to_directory,
+            trash_commands::move_page_to_trash,
+            trash_commands::list_trashed_pages,
+            trash_commands::restore_page,
+            trash_commands::delete_page_permanently,
+            maintenance_commands::run_maintenance,
+            image_commands::create_image_attachment,
+            image_commands::get_image_attachment,
+            image_commands::get_image_thumbnail,
+            image_commands::recompress_all_images,
+            backup_commands::back_up_now,
+            backup_commands::list_snapshots,
+            backup_commands::restore_snapshot,
+            repair_commands::check_database_health,
+            repair_commands::repair_database,
+            storage_commands::get_storage_report,
+            storage_commands::compact_database,
+            encryption_commands::get_database_status,
+            encryption_commands::unlock_database,
+            encryption_commands::enable_encryption,
+            encryption_commands::disable_encryption,
+            encryption_commands::change_passphrase,
+            search_commands::search_pages,
+            search_commands::fuzzy_find_pages,
+            search_commands::get_search_index_settings,
+            search_commands::set_search_tokenizer,
+            search_commands::reindex_search,
+            backlink_commands::get_backlinks,
+            health_commands::get_vault_health_report,
+            health_commands::export_vault_health_report,
+            relation_commands::add_page_relation,
+            relation_commands::remove_page_relation,
+            relation_commands::list_page_relations,
+            export_commands::export_vault_json,
+            fleeting_commands::mark_page_fleeting,
+            fleeting_commands::make_page_permanent,
+            fleeting_commands::list_fleeting_pages,
+            resurface_commands::set_resurface_date,
+            resurface_commands::list_upcoming_resurfacing,
+            resurface_commands::list_resurfaced_pages,
+            resurface_commands::snooze_resurfaced_page,
+            resurface_commands::dismiss_resurfaced_page,
+            stats_commands::get_vault_stats,
+            classification_commands::create_classification,
+            classification_commands::rename_classification,
+            classification_commands::renumber_classification,
+            classification_commands::move_classification,
+            classification_commands::delete_classification,
+            classification_commands::list_classifications,
+            classification_commands::set_page_classification,
+            classification_commands::get_classification_outline,
+            classification_commands::export_classification_outline,
+            tag_commands::rename_tag,
+            tag_commands::merge_tags,
+            tag_commands::add_tag_alias,
+            tag_commands::remove_tag_alias,
+            tag_commands::list_tag_aliases,
+            tag_commands::get_tag_tree,
+            tag_cluster_commands::get_tag_clusters,
+            schema_commands::get_schema_info,
+            schema_commands::downgrade_database,
+            vault_commands::get_current_vault,
+            vault_commands::list_vaults,
+            vault_commands::create_vault,
+            vault_commands::open_vault,
+            vault_commands::switch_vault,
+            vault_commands::close_vault,
         ])
         .on_window_event(|window, event| {
             // Prevent fully closing the main window because it messes up
@@ -52,15 +146,75 @@ pub fn run() {
             app.manage(menu_items);
             app.set_menu(menu)?;
 
-            // Initialize the database
-            tauri::async_runtime::block_on(async {
-                if let Err(e) = db::initialize_database(app).await {
-                    eprintln!("Database initialization error: {}", e);
-                }
-            });
+            app.manage(vault::CurrentVault::load(app.handle())?);
+            app.manage(db::DatabaseKey::default());
+            app.manage(db::DatabaseOpenError::default());
+            app.manage(search_commands::TitleIndex::default());
+
+            // Initialize the database, unless it's encrypted and has to wait for the
+            // passphrase (see encryption_commands.rs)
+            let db_path = db::database_path(app.handle())?;
+            if db::is_encrypted(&db_path) {
+                println!("Database is encrypted, waiting for it to be unlocked");
+            } else {
+                tauri::async_runtime::block_on(async {
+                    if let Err(e) = db::initialize_database(app.handle(), None).await {
+                        eprintln!("Database initialization error: {}", e);
+                        return;
+                    }
+
+                    // Move any images still stored inline in the database into the image store
+                    // and record dimensions for images saved without them
+                    match (
+                        db::get_pool(app.handle()).await,
+                        image_store::ImageStore::current(app.handle()),
+                    ) {
+                        (Ok(pool), Ok(image_store)) => image_store.upgrade_attachments(&pool).await,
+                        (Err(e), _) | (_, Err(e)) => eprintln!("Image store migration error: {}", e),
+                    }
+                });
+            }
 
             app.on_menu_event(menu::handle_menu_event);
 
+            // Start background maintenance jobs
+            jobs::spawn_periodic(
+                app.handle().clone(),
+                "trash_purge",
+                Duration::from_secs(60 * 60),
+                trash_commands::run_trash_purge,
+            );
+            jobs::spawn_periodic(
+                app.handle().clone(),
+                "garbage_collection",
+                Duration::from_secs(6 * 60 * 60),
+                maintenance_commands::run_garbage_collection,
+            );
+            jobs::spawn_periodic(
+                app.handle().clone(),
+                "scheduled_backup",
+                Duration::from_secs(60 * 60),
+                backup_commands::run_scheduled_backup,
+            );
+            jobs::spawn_periodic(
+                app.handle().clone(),
+                "fleeting_sweep",
+                Duration::from_secs(15 * 60),
+                fleeting_commands::run_fleeting_sweep,
+            );
+            jobs::spawn_periodic(
+                app.handle().clone(),
+                "resurface_check",
+                Duration::from_secs(5 * 60),
+                resurface_commands::run_resurface_check,
+            );
+            jobs::spawn_periodic(
+                app.handle().clone(),
+                "tag_cluster_refresh",
+                Duration::from_secs(10 * 60),
+                tag_cluster_commands::run_tag_cluster_refresh,
+            );
+
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,106 @@
+use crate::db::get_pool;
+use crate::image_store::ImageStore;
+use crate::lexical::collect_image_ids;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+
+/// Images younger than this are never collected, because the page that
+/// references them may not have been saved yet.
+const IMAGE_GRACE_PERIOD: &str = "-1 hour";
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub tags_deleted: u64,
+    pub images_deleted: u64,
+    pub image_bytes_reclaimed: u64,
+}
+
+/// Deletes tags with no page associations. Aliases, and tags with aliases, are kept.
+async fn delete_orphaned_tags(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM tags
+         WHERE alias_of IS NULL
+           AND id NOT IN (SELECT DISTINCT tag_id FROM tag_associations)
+           AND id NOT IN (SELECT alias_of FROM tags WHERE alias_of IS NOT NULL)",
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Deletes image attachments that no page's `lexical_json` refers to
+async fn delete_unreferenced_images(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let lexical_states: Vec<String> = sqlx::query_scalar("SELECT lexical_json FROM pages")
+        .fetch_all(pool)
+        .await?;
+
+    let mut referenced_ids = HashSet::new();
+    for lexical_json in &lexical_states {
+        collect_image_ids(lexical_json, &mut referenced_ids);
+    }
+
+    let candidates: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM image_attachments
+         WHERE created_at < datetime('now', ?)",
+    )
+    .bind(IMAGE_GRACE_PERIOD)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let mut images_deleted = 0;
+    for image_id in candidates {
+        if referenced_ids.contains(&image_id) {
+            continue;
+        }
+        sqlx::query("DELETE FROM image_attachments WHERE id = ?")
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+        images_deleted += 1;
+    }
+    tx.commit().await?;
+
+    Ok(images_deleted)
+}
+
+/// Deletes orphaned tags, unreferenced images, and image store files left behind by them
+pub async fn collect_garbage(
+    pool: &SqlitePool,
+    image_store: &ImageStore,
+) -> Result<MaintenanceReport, String> {
+    let tags_deleted = delete_orphaned_tags(pool)
+        .await
+        .map_err(|e| format!("Failed to delete orphaned tags: {}", e))?;
+    let images_deleted = delete_unreferenced_images(pool)
+        .await
+        .map_err(|e| format!("Failed to delete unreferenced images: {}", e))?;
+    let (_, image_bytes_reclaimed) = image_store.sweep(pool).await?;
+
+    Ok(MaintenanceReport {
+        tags_deleted,
+        images_deleted,
+        image_bytes_reclaimed,
+    })
+}
+
+/// Background job: runs garbage collection and logs what it reclaimed
+pub async fn run_garbage_collection(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let report = collect_garbage(&pool, &ImageStore::current(&app_handle)?).await?;
+
+    println!(
+        "Garbage collection deleted {} tags and {} images ({} bytes)",
+        report.tags_deleted, report.images_deleted, report.image_bytes_reclaimed
+    );
+    Ok(())
+}
+
+// Command to run garbage collection on demand
+#[tauri::command]
+pub async fn run_maintenance(app_handle: tauri::AppHandle) -> Result<MaintenanceReport, String> {
+    let pool = get_pool(&app_handle).await?;
+    collect_garbage(&pool, &ImageStore::current(&app_handle)?).await
+}
@@ -1,13 +1,122 @@
-use base64::prelude::*;
+use crate::classification_commands::{page_folders, SYNC_BY_CLASSIFICATION_KEY};
+use crate::image_store::ImageStore;
+use crate::relation_commands::{all_relations, PageRelation, PARENT, PEER};
+use crate::settings::get_bool_setting;
+use crate::vault::{current_vault_dir, vault_name};
+use serde::{Deserialize, Serialize};
 use sqlx::Row;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::Write;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use tauri::Manager;
 use tauri_plugin_sql::{DbInstances, DbPool};
 use tauri_plugin_store::StoreExt;
 use crate::db_wrapper::DbPoolExt;
 
+/// File in the sync directory recording the vault it was last synced from and the files
+/// that sync wrote
+const SYNC_MANIFEST: &str = ".notesponge-sync.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncManifest {
+    /// Directory of the vault
+    vault: Option<String>,
+    /// Paths relative to the sync directory, separated by `/`
+    files: Vec<String>,
+}
+
+fn read_manifest(sync_dir: &Path) -> SyncManifest {
+    fs::read_to_string(sync_dir.join(SYNC_MANIFEST))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Where a page's file goes, relative to the sync directory and separated by `/` so the
+/// same path can be used in front matter
+fn sync_file(folders: &HashMap<i64, PathBuf>, page_id: i64, filename: &str) -> String {
+    match folders.get(&page_id) {
+        Some(folder) => folder
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .chain([filename.into()])
+            .collect::<Vec<_>>()
+            .join("/"),
+        None => filename.to_string(),
+    }
+}
+
+/// Removes the files the last sync wrote that this one didn't, such as pages that moved
+/// to another folder or were archived, along with folders left empty. Files the sync
+/// never wrote are left alone.
+fn remove_stale_files(sync_dir: &Path, previous: &SyncManifest, written: &HashSet<String>) {
+    for file in previous.files.iter().filter(|file| !written.contains(*file)) {
+        let relative = Path::new(file);
+        if !relative
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+        {
+            continue;
+        }
+        let path = sync_dir.join(relative);
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Removed stale file: {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => println!("Failed to remove stale file {}: {}", path.display(), e),
+        }
+        // Only empty folders can be removed, so this stops at the first one still in use
+        for folder in relative.ancestors().skip(1) {
+            if folder.as_os_str().is_empty() || fs::remove_dir(sync_dir.join(folder)).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// YAML front matter listing a page's relations by the files the other pages are synced to,
+/// relative to the sync directory. Parent and peer relations get their own lists; other
+/// types are listed with a direction when they're directed.
+fn front_matter(relations: &[PageRelation], folders: &HashMap<i64, PathBuf>) -> String {
+    let (mut parents, mut children, mut peers, mut others) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for relation in relations {
+        // JSON strings are valid YAML, and take care of quoting
+        let path = sync_file(folders, relation.page_id, &relation.filename);
+        let file = serde_json::Value::from(path).to_string();
+        match relation.relation_type.as_str() {
+            PARENT if relation.outgoing => children.push(file),
+            PARENT => parents.push(file),
+            PEER => peers.push(file),
+            relation_type => {
+                let mut entry = format!(
+                    "  - type: {}\n    page: {}\n",
+                    serde_json::Value::from(relation_type),
+                    file
+                );
+                if relation.directed {
+                    let direction = if relation.outgoing { "outgoing" } else { "incoming" };
+                    entry.push_str(&format!("    direction: {}\n", direction));
+                }
+                others.push(entry);
+            }
+        }
+    }
+
+    let mut yaml = String::from("---\n");
+    for (key, files) in [("parents", parents), ("children", children), ("peers", peers)] {
+        if !files.is_empty() {
+            yaml.push_str(&format!("{}: [{}]\n", key, files.join(", ")));
+        }
+    }
+    if !others.is_empty() {
+        yaml.push_str("relations:\n");
+        yaml.push_str(&others.concat());
+    }
+    yaml.push_str("---\n\n");
+    yaml
+}
+
 // Command to sync to a directory
 #[tauri::command]
 pub async fn sync_to_directory(app_handle: tauri::AppHandle) -> Result<(), String> {
@@ -49,6 +158,19 @@ pub async fn sync_to_directory(app_handle: tauri::AppHandle) -> Result<(), Strin
         return Err(format!("Failed to create directory: {}", e));
     }
 
+    // The sync path is one setting for every vault, so don't overwrite another vault's export
+    let vault_dir = current_vault_dir(&app_handle)?;
+    let vault = vault_dir.to_string_lossy().to_string();
+    let previous = read_manifest(sync_dir);
+    if let Some(other) = previous.vault.as_deref().filter(|other| *other != vault) {
+        return Err(format!(
+            "{} was last synced from the vault {}, not {}. Choose another sync directory.",
+            sync_dir.display(),
+            vault_name(&app_handle, Path::new(other)),
+            vault_name(&app_handle, &vault_dir)
+        ));
+    }
+
     println!("Getting database instance");
 
     // Get the database instance
@@ -133,6 +255,24 @@ pub async fn sync_to_directory(app_handle: tauri::AppHandle) -> Result<(), Strin
 
     println!("Found {} pages to export", pages.len());
 
+    let relations = all_relations(pool)
+        .await
+        .map_err(|e| format!("Failed to fetch page relations: {}", e))?;
+
+    // Classified pages can go in folders by code, with their images alongside them
+    let folders = if get_bool_setting(&app_handle, SYNC_BY_CLASSIFICATION_KEY, false) {
+        page_folders(pool)
+            .await
+            .map_err(|e| format!("Failed to fetch classifications: {}", e))?
+    } else {
+        HashMap::new()
+    };
+    let page_dir = |page_id: i64| match folders.get(&page_id) {
+        Some(folder) => sync_dir.join(folder),
+        None => sync_dir.to_path_buf(),
+    };
+    let mut written = HashSet::new();
+
     // 2. Write each page to the given directory
     for page in pages {
         println!("Processing page: {:?}", page);
@@ -163,10 +303,23 @@ pub async fn sync_to_directory(app_handle: tauri::AppHandle) -> Result<(), Strin
         );
 
         // Use the filename column directly instead of sanitizing the title
-        let file_path = sync_dir.join(filename);
+        let page_dir = page_dir(page_id);
+        fs::create_dir_all(&page_dir)
+            .map_err(|e| format!("Failed to create directory for page {}: {}", page_id, e))?;
+        let file = sync_file(&folders, page_id, filename);
+        let file_path = sync_dir.join(&file);
+
+        // Pages with relations start with front matter listing them
+        let contents = match relations.get(&page_id) {
+            Some(page_relations) => {
+                format!("{}{}", front_matter(page_relations, &folders), markdown)
+            }
+            None => markdown.to_string(),
+        };
 
-        fs::write(&file_path, markdown)
+        fs::write(&file_path, contents)
             .map_err(|e| format!("Failed to write page {}: {}", page_id, e))?;
+        written.insert(file);
 
         println!("Created markdown file: {}", file_path.display());
     }
@@ -174,16 +327,18 @@ pub async fn sync_to_directory(app_handle: tauri::AppHandle) -> Result<(), Strin
     // 3. Get all images from the database
     let images = db
         .select_query(
-            "SELECT ia.id, ia.page_id, ia.data, ia.file_extension
+            "SELECT ia.id, ia.page_id, ia.content_hash, ia.file_extension
          FROM image_attachments ia 
          JOIN pages p ON ia.page_id = p.id 
-         WHERE p.archived_at IS NULL",
+         WHERE p.archived_at IS NULL AND ia.content_hash IS NOT NULL",
             vec![],
         )
         .await
         .map_err(|e| format!("Failed to fetch images: {}", e))?;
 
-    // 4. Write each image to the given directory
+    let image_store = ImageStore::current(&app_handle)?;
+
+    // 4. Copy each image from the image store to the given directory
     for image in images {
         let image_id = image
             .get("id")
@@ -193,39 +348,37 @@ pub async fn sync_to_directory(app_handle: tauri::AppHandle) -> Result<(), Strin
             .get("page_id")
             .and_then(|v| v.as_i64())
             .ok_or("Invalid page ID")?;
-        let image_data_base64 = image
-            .get("data")
+        let content_hash = image
+            .get("content_hash")
             .and_then(|v| v.as_str())
-            .ok_or("Invalid image data")?;
+            .ok_or("Invalid content hash")?;
         let file_extension = image
             .get("file_extension")
             .and_then(|v| v.as_str())
             .ok_or("Invalid file extension")?;
 
-        // Decode the base64 string to binary data
-        let image_data = BASE64_STANDARD.decode(image_data_base64).map_err(|e| {
-            format!(
-                "Failed to decode base64 for image {}_{}: {}",
-                page_id, image_id, e
-            )
-        })?;
-
         let filename = format!("{}_{}.{}", page_id, image_id, file_extension);
-        let file_path = sync_dir.join(filename);
-
-        let mut file = fs::File::create(&file_path).map_err(|e| {
-            format!(
-                "Failed to create image file {}_{}: {}",
-                page_id, image_id, e
-            )
-        })?;
+        let file = sync_file(&folders, page_id, &filename);
+        let file_path = sync_dir.join(&file);
 
-        file.write_all(&image_data)
-            .map_err(|e| format!("Failed to write image data {}_{}: {}", page_id, image_id, e))?;
+        image_store.copy_to(content_hash, &file_path)?;
+        written.insert(file);
 
         println!("Created image file: {}", file_path.display());
     }
 
+    // 5. Remove what the last sync wrote and this one didn't, and remember what this one wrote
+    remove_stale_files(sync_dir, &previous, &written);
+    let mut files: Vec<String> = written.into_iter().collect();
+    files.sort();
+    let manifest = serde_json::to_string_pretty(&SyncManifest {
+        vault: Some(vault),
+        files,
+    })
+        .map_err(|e| format!("Failed to serialize sync manifest: {}", e))?;
+    fs::write(sync_dir.join(SYNC_MANIFEST), manifest)
+        .map_err(|e| format!("Failed to write sync manifest: {}", e))?;
+
     println!("Sync completed successfully to: {}", sync_path);
     Ok(())
 } 
\ No newline at end of file
@@ -37,6 +37,16 @@ pub fn create_app_menu<R: Runtime>(
         .build(app)
         .expect("failed to create sync menu item");
 
+    let back_up_now = MenuItemBuilder::new("Back Up Now")
+        .id("back_up_now")
+        .build(app)
+        .expect("failed to create back up now menu item");
+
+    let check_and_repair_database = MenuItemBuilder::new("Check & Repair Database")
+        .id("check_and_repair_database")
+        .build(app)
+        .expect("failed to create check and repair database menu item");
+
     // App submenu with native functionality
     let app_submenu = SubmenuBuilder::new(app, "NoteSponge")
         .about(Some(AboutMetadata {
@@ -59,6 +69,8 @@ pub fn create_app_menu<R: Runtime>(
     let file_submenu = SubmenuBuilder::new(app, "File")
         .item(&new_page)
         .item(&sync)
+        .item(&back_up_now)
+        .item(&check_and_repair_database)
         .separator()
         .item(&search)
         .separator()
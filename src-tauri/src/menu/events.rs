@@ -2,6 +2,19 @@ use tauri::{Emitter, Manager};
 
 /// Handles menu events and emits appropriate events to the frontend
 pub fn handle_menu_event(app: &tauri::AppHandle, event: tauri::menu::MenuEvent) {
+    // Menu items handled entirely in Rust
+    match event.id().0.as_str() {
+        "back_up_now" => {
+            crate::backup_commands::spawn_back_up_now(app.clone());
+            return;
+        }
+        "check_and_repair_database" => {
+            crate::repair_commands::spawn_check_and_repair(app.clone());
+            return;
+        }
+        _ => {}
+    }
+
     if let Some(window) = app.get_webview_window("main") {
         let id = event.id().0.as_str();
 
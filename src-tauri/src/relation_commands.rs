@@ -0,0 +1,244 @@
+// Formal relationships between pages (see migrations/08-page-relations.sql). `parent` and
+// `peer` are built in; any other type can be used too, directed or not. Parent relations
+// can't form cycles, and moving a page to the trash takes its children with it (see
+// trash_commands.rs).
+
+use crate::db::get_pool;
+use serde::Serialize;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+
+pub const PARENT: &str = "parent";
+pub const PEER: &str = "peer";
+
+/// Selects the IDs of every page below `?` in the parent/child hierarchy as `descendants`.
+/// `UNION` rather than `UNION ALL` stops it going round a cycle forever, should one exist.
+pub const DESCENDANTS_CTE: &str = "WITH RECURSIVE descendants(id) AS (
+         SELECT target_page_id FROM page_relations
+         WHERE source_page_id = ? AND relation_type = 'parent'
+         UNION
+         SELECT r.target_page_id FROM page_relations r
+         JOIN descendants d ON r.source_page_id = d.id
+         WHERE r.relation_type = 'parent'
+     )";
+
+/// A relation as seen from one of its pages
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRelation {
+    pub id: i64,
+    pub relation_type: String,
+    pub directed: bool,
+    /// Whether the relation points away from this page. In a `parent` relation that means
+    /// this page is the parent. Always false for undirected relations.
+    pub outgoing: bool,
+    /// The page on the other end
+    pub page_id: i64,
+    pub title: String,
+    pub filename: String,
+}
+
+/// Relations of pages that aren't in the trash, from the point of view of each page
+/// (`this_page_id`)
+const RELATIONS_SQL: &str = "SELECT this_page_id, r.id, r.relation_type, r.directed,
+            r.directed AND outgoing AS outgoing, p.id AS page_id, p.title, p.filename
+     FROM (
+         SELECT id, source_page_id AS this_page_id, target_page_id AS other_page_id,
+                1 AS outgoing FROM page_relations
+         UNION ALL
+         SELECT id, target_page_id, source_page_id, 0 FROM page_relations
+     ) ends
+     JOIN page_relations r ON r.id = ends.id
+     JOIN pages p ON p.id = ends.other_page_id
+     JOIN pages own ON own.id = ends.this_page_id
+     WHERE p.archived_at IS NULL AND own.archived_at IS NULL";
+
+#[derive(sqlx::FromRow)]
+struct RelationRow {
+    this_page_id: i64,
+    #[sqlx(flatten)]
+    relation: PageRelation,
+}
+
+pub async fn relations_of(
+    pool: &SqlitePool,
+    page_id: i64,
+) -> Result<Vec<PageRelation>, sqlx::Error> {
+    let rows: Vec<RelationRow> = sqlx::query_as(&format!(
+        "{} AND ends.this_page_id = ? ORDER BY r.relation_type, p.title",
+        RELATIONS_SQL
+    ))
+    .bind(page_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|row| row.relation).collect())
+}
+
+/// Relations of every page that isn't in the trash, by page ID
+pub async fn all_relations(
+    pool: &SqlitePool,
+) -> Result<HashMap<i64, Vec<PageRelation>>, sqlx::Error> {
+    let rows: Vec<RelationRow> = sqlx::query_as(&format!(
+        "{} ORDER BY r.relation_type, p.title",
+        RELATIONS_SQL
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut relations: HashMap<i64, Vec<PageRelation>> = HashMap::new();
+    for row in rows {
+        relations
+            .entry(row.this_page_id)
+            .or_default()
+            .push(row.relation);
+    }
+    Ok(relations)
+}
+
+/// Whether making `parent_id` the parent of `child_id` would make a page its own ancestor
+async fn would_create_cycle(
+    conn: &mut SqliteConnection,
+    parent_id: i64,
+    child_id: i64,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+        "{} SELECT EXISTS(SELECT 1 FROM descendants WHERE id = ?)",
+        DESCENDANTS_CTE
+    ))
+    .bind(child_id)
+    .bind(parent_id)
+    .fetch_one(conn)
+    .await
+}
+
+/// Relates two pages. `parent` relations are always directed and `peer` relations never
+/// are; other types are directed if `directed` says so. Relating two pages the same way
+/// twice does nothing.
+pub async fn add_relation(
+    pool: &SqlitePool,
+    source_page_id: i64,
+    target_page_id: i64,
+    relation_type: &str,
+    directed: bool,
+) -> Result<(), String> {
+    let relation_type = relation_type.trim().to_lowercase();
+    if relation_type.is_empty() {
+        return Err("Relation type can't be empty".to_string());
+    }
+    if source_page_id == target_page_id {
+        return Err("A page can't be related to itself".to_string());
+    }
+    let directed = match relation_type.as_str() {
+        PARENT => true,
+        PEER => false,
+        _ => directed,
+    };
+    // Undirected relations are stored one way round only, so they can't be added twice
+    let (source_page_id, target_page_id) = if directed {
+        (source_page_id, target_page_id)
+    } else {
+        (
+            source_page_id.min(target_page_id),
+            source_page_id.max(target_page_id),
+        )
+    };
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pages WHERE id IN (?, ?)")
+        .bind(source_page_id)
+        .bind(target_page_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to look up pages: {}", e))?;
+    if existing < 2 {
+        return Err(format!(
+            "Can't relate pages {} and {}: page not found",
+            source_page_id, target_page_id
+        ));
+    }
+
+    if relation_type == PARENT
+        && would_create_cycle(&mut tx, source_page_id, target_page_id)
+            .await
+            .map_err(|e| format!("Failed to check page hierarchy: {}", e))?
+    {
+        return Err(format!(
+            "Page {} can't be the parent of page {}, since it's already below it",
+            source_page_id, target_page_id
+        ));
+    }
+
+    sqlx::query(
+        "INSERT INTO page_relations (source_page_id, target_page_id, relation_type, directed)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT (source_page_id, target_page_id, relation_type) DO NOTHING",
+    )
+    .bind(source_page_id)
+    .bind(target_page_id)
+    .bind(&relation_type)
+    .bind(directed)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to add relation: {}", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to add relation: {}", e))
+}
+
+// Command to relate two pages. For `parent` relations, the source page is the parent.
+#[tauri::command]
+pub async fn add_page_relation(
+    app_handle: tauri::AppHandle,
+    source_page_id: i64,
+    target_page_id: i64,
+    relation_type: String,
+    directed: Option<bool>,
+) -> Result<Vec<PageRelation>, String> {
+    let pool = get_pool(&app_handle).await?;
+    add_relation(
+        &pool,
+        source_page_id,
+        target_page_id,
+        &relation_type,
+        directed.unwrap_or(false),
+    )
+    .await?;
+    relations_of(&pool, source_page_id)
+        .await
+        .map_err(|e| format!("Failed to list relations: {}", e))
+}
+
+// Command to remove a relation between two pages
+#[tauri::command]
+pub async fn remove_page_relation(
+    app_handle: tauri::AppHandle,
+    relation_id: i64,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let result = sqlx::query("DELETE FROM page_relations WHERE id = ?")
+        .bind(relation_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to remove relation {}: {}", relation_id, e))?;
+    if result.rows_affected() == 0 {
+        return Err(format!("Relation {} doesn't exist", relation_id));
+    }
+    Ok(())
+}
+
+// Command to list a page's relations to pages that aren't in the trash
+#[tauri::command]
+pub async fn list_page_relations(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+) -> Result<Vec<PageRelation>, String> {
+    let pool = get_pool(&app_handle).await?;
+    relations_of(&pool, page_id)
+        .await
+        .map_err(|e| format!("Failed to list relations: {}", e))
+}
@@ -0,0 +1,267 @@
+use crate::db::get_pool;
+use crate::lexical::plain_text;
+use crate::stats_commands::count_words;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::Emitter;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DanglingTagAssociation {
+    pub page_id: i64,
+    pub tag_id: i64,
+}
+
+/// What `check_database` found. When the check was run with repair enabled, each list
+/// holds the problems that were found and fixed, except `integrity_errors`, which lists
+/// the problems still present after the repair.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseCheckReport {
+    /// Output of `PRAGMA integrity_check`, empty when the database is intact
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    pub dangling_tag_associations: Vec<DanglingTagAssociation>,
+    /// Pages whose `plain_text` is empty even though their `lexical_json` has text
+    pub pages_missing_plain_text: Vec<i64>,
+    /// Pages whose tags in the search index don't match their tags
+    pub pages_with_stale_search_tags: Vec<i64>,
+    /// Whether a search index disagrees with the text it was built from
+    pub search_index_out_of_sync: bool,
+    pub repaired: bool,
+}
+
+impl DatabaseCheckReport {
+    pub fn is_healthy(&self) -> bool {
+        self.integrity_errors.is_empty()
+            && self.foreign_key_violations.is_empty()
+            && self.dangling_tag_associations.is_empty()
+            && self.pages_missing_plain_text.is_empty()
+            && self.pages_with_stale_search_tags.is_empty()
+            && !self.search_index_out_of_sync
+    }
+}
+
+async fn integrity_errors(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    let results: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await?;
+    Ok(results.into_iter().filter(|r| r != "ok").collect())
+}
+
+async fn foreign_key_violations(
+    pool: &SqlitePool,
+) -> Result<Vec<ForeignKeyViolation>, sqlx::Error> {
+    sqlx::query_as("SELECT \"table\", rowid, parent FROM pragma_foreign_key_check")
+        .fetch_all(pool)
+        .await
+}
+
+async fn dangling_tag_associations(
+    pool: &SqlitePool,
+) -> Result<Vec<DanglingTagAssociation>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT page_id, tag_id FROM tag_associations ta
+         WHERE NOT EXISTS (SELECT 1 FROM pages p WHERE p.id = ta.page_id)
+            OR NOT EXISTS (SELECT 1 FROM tags t WHERE t.id = ta.tag_id)",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns the pages whose `plain_text` is empty but whose `lexical_json` has text,
+/// along with the text the editor would have saved
+async fn pages_missing_plain_text(pool: &SqlitePool) -> Result<Vec<(i64, String)>, sqlx::Error> {
+    let rows: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, lexical_json FROM pages WHERE plain_text = ''")
+            .fetch_all(pool)
+            .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, lexical_json)| {
+            let text = plain_text(&lexical_json)?;
+            (!text.trim().is_empty()).then_some((id, text))
+        })
+        .collect())
+}
+
+/// Tags of each page, space separated, the way the search index stores them
+const PAGE_TAGS_SQL: &str = "COALESCE((
+    SELECT group_concat(t.tag, ' ') FROM tag_associations ta
+    JOIN tags t ON t.id = ta.tag_id
+    WHERE ta.page_id = p.id
+), '')";
+
+async fn pages_with_stale_search_tags(pool: &SqlitePool) -> Result<Vec<i64>, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT p.id FROM pages p
+         LEFT JOIN page_search_tags st ON st.page_id = p.id
+         WHERE st.page_id IS NULL OR st.tags != {}",
+        PAGE_TAGS_SQL
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+/// FTS5 reports a corrupt index as an error from its integrity check
+async fn search_index_out_of_sync(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    for index in ["pages_fts", "pages_title_fts"] {
+        let check = sqlx::query(&format!(
+            "INSERT INTO {0}({0}, rank) VALUES('integrity-check', 1)",
+            index
+        ))
+        .execute(pool)
+        .await;
+        match check {
+            Ok(_) => {}
+            Err(sqlx::Error::Database(_)) => return Ok(true),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
+
+/// Rebuilds `pages_fts`, the tags it indexes and `pages_title_fts` from the `pages` and
+/// `tags` tables
+pub async fn refresh_search_indexes(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM page_search_tags")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!(
+        "INSERT INTO page_search_tags (page_id, tags) SELECT p.id, {} FROM pages p",
+        PAGE_TAGS_SQL
+    ))
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("INSERT INTO pages_fts(pages_fts) VALUES('rebuild')")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO pages_title_fts(pages_title_fts) VALUES('rebuild')")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+async fn repair(pool: &SqlitePool, report: &DatabaseCheckReport) -> Result<(), sqlx::Error> {
+    // Corrupt indexes are the one kind of integrity error that can be fixed in place
+    if !report.integrity_errors.is_empty() {
+        sqlx::query("REINDEX").execute(pool).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    for association in &report.dangling_tag_associations {
+        sqlx::query("DELETE FROM tag_associations WHERE page_id = ? AND tag_id = ?")
+            .bind(association.page_id)
+            .bind(association.tag_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for violation in &report.foreign_key_violations {
+        let Some(rowid) = violation.rowid else {
+            continue;
+        };
+        // Table names come from `pragma_foreign_key_check`, so they can't be bound
+        sqlx::query(&format!(
+            "DELETE FROM \"{}\" WHERE rowid = ?",
+            violation.table.replace('"', "\"\"")
+        ))
+        .bind(rowid)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    for (page_id, text) in pages_missing_plain_text(pool).await? {
+        sqlx::query("UPDATE pages SET plain_text = ?, word_count = ? WHERE id = ?")
+            .bind(&text)
+            .bind(count_words(&text))
+            .bind(page_id)
+            .execute(pool)
+            .await?;
+    }
+
+    refresh_search_indexes(pool).await
+}
+
+/// Checks the database for corruption and inconsistencies, optionally fixing what it finds
+pub async fn check_database(
+    pool: &SqlitePool,
+    repair_problems: bool,
+) -> Result<DatabaseCheckReport, String> {
+    let check = async {
+        let mut report = DatabaseCheckReport {
+            integrity_errors: integrity_errors(pool).await?,
+            foreign_key_violations: foreign_key_violations(pool).await?,
+            dangling_tag_associations: dangling_tag_associations(pool).await?,
+            pages_missing_plain_text: pages_missing_plain_text(pool)
+                .await?
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect(),
+            pages_with_stale_search_tags: pages_with_stale_search_tags(pool).await?,
+            search_index_out_of_sync: search_index_out_of_sync(pool).await?,
+            repaired: false,
+        };
+
+        if repair_problems {
+            repair(pool, &report).await?;
+            report.integrity_errors = integrity_errors(pool).await?;
+            report.repaired = true;
+        }
+        Ok::<_, sqlx::Error>(report)
+    };
+
+    check
+        .await
+        .map_err(|e| format!("Database check failed: {}", e))
+}
+
+/// Runs a check and repair from the "Check & Repair Database" menu item and reports the result
+pub fn spawn_check_and_repair(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let result = match get_pool(&app_handle).await {
+            Ok(pool) => check_database(&pool, true).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(report) => {
+                if report.is_healthy() {
+                    println!("Database check found no problems");
+                } else {
+                    println!("Database check and repair finished: {:?}", report);
+                }
+                let _ = app_handle.emit("database_repaired", report);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                let _ = app_handle.emit("database_repair_failed", e);
+            }
+        }
+    });
+}
+
+// Command to check the database without changing anything
+#[tauri::command]
+pub async fn check_database_health(
+    app_handle: tauri::AppHandle,
+) -> Result<DatabaseCheckReport, String> {
+    let pool = get_pool(&app_handle).await?;
+    check_database(&pool, false).await
+}
+
+// Command to check the database and fix the problems found
+#[tauri::command]
+pub async fn repair_database(app_handle: tauri::AppHandle) -> Result<DatabaseCheckReport, String> {
+    let pool = get_pool(&app_handle).await?;
+    check_database(&pool, true).await
+}
@@ -0,0 +1,217 @@
+// Relevancy dates: a page can be given a date to resurface on. A background job, which
+// also runs at startup, moves pages whose date has passed to the "Resurfaced" queue and
+// emits `pages_resurfaced` with them. A page leaves the queue when it's dismissed, or
+// snoozed to resurface again later. Pages in the trash don't resurface until restored.
+
+use crate::db::get_pool;
+use crate::settings::get_u64_setting;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::Emitter;
+
+/// Settings key for how many days snoozing a resurfaced page puts it off, unless told
+/// otherwise
+pub const RESURFACE_SNOOZE_DAYS_KEY: &str = "resurface_snooze_days";
+pub const DEFAULT_RESURFACE_SNOOZE_DAYS: u64 = 7;
+
+/// A page in the "Resurfaced" queue
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ResurfacedPage {
+    pub page_id: i64,
+    pub title: String,
+    pub filename: String,
+    pub resurfaced_at: String,
+}
+
+/// A page waiting to resurface
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UpcomingResurface {
+    pub page_id: i64,
+    pub title: String,
+    pub filename: String,
+    pub resurface_at: String,
+}
+
+const QUEUE_SQL: &str = "SELECT rp.page_id, p.title, p.filename, rp.resurfaced_at
+     FROM resurfaced_pages rp
+     JOIN pages p ON p.id = rp.page_id
+     WHERE p.archived_at IS NULL";
+
+/// Sets when a page resurfaces, taking it out of the "Resurfaced" queue. `resurface_at`
+/// can be anything SQLite's `datetime()` understands; times with an offset are converted
+/// to UTC. `None` clears the date. Returns the date as stored.
+pub async fn set_resurface_at(
+    pool: &SqlitePool,
+    page_id: i64,
+    resurface_at: Option<&str>,
+) -> Result<Option<String>, String> {
+    let normalized: Option<String> = match resurface_at {
+        Some(date) => {
+            let normalized: Option<String> = sqlx::query_scalar("SELECT datetime(?)")
+                .bind(date)
+                .fetch_one(pool)
+                .await
+                .map_err(|e| format!("Failed to read date: {}", e))?;
+            Some(normalized.ok_or_else(|| format!("\"{}\" isn't a valid date", date))?)
+        }
+        None => None,
+    };
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let result = sqlx::query("UPDATE pages SET resurface_at = ? WHERE id = ?")
+        .bind(&normalized)
+        .bind(page_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to set resurface date: {}", e))?;
+    if result.rows_affected() == 0 {
+        return Err(format!("Page {} not found", page_id));
+    }
+    sqlx::query("DELETE FROM resurfaced_pages WHERE page_id = ?")
+        .bind(page_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to update resurfaced queue: {}", e))?;
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to set resurface date: {}", e))?;
+    Ok(normalized)
+}
+
+/// Moves pages whose resurface date has passed to the "Resurfaced" queue, clearing the
+/// date. Returns the pages that were moved.
+pub async fn resurface_due_pages(pool: &SqlitePool) -> Result<Vec<ResurfacedPage>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let due: Vec<i64> = sqlx::query_scalar(
+        "UPDATE pages SET resurface_at = NULL
+         WHERE archived_at IS NULL AND resurface_at IS NOT NULL
+         AND resurface_at <= CURRENT_TIMESTAMP
+         RETURNING id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    if due.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids = serde_json::to_string(&due).unwrap_or_default();
+    sqlx::query(
+        "INSERT INTO resurfaced_pages (page_id)
+         SELECT value FROM json_each(?) WHERE true
+         ON CONFLICT (page_id) DO UPDATE SET resurfaced_at = CURRENT_TIMESTAMP",
+    )
+    .bind(&ids)
+    .execute(&mut *tx)
+    .await?;
+    let pages = sqlx::query_as(&format!(
+        "{} AND rp.page_id IN (SELECT value FROM json_each(?)) ORDER BY p.title",
+        QUEUE_SQL
+    ))
+    .bind(&ids)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(pages)
+}
+
+/// Background job: moves pages that are due to the "Resurfaced" queue
+pub async fn run_resurface_check(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let pages = resurface_due_pages(&pool)
+        .await
+        .map_err(|e| format!("Failed to resurface pages: {}", e))?;
+
+    if !pages.is_empty() {
+        println!("Resurfaced {} pages", pages.len());
+        let _ = app_handle.emit("pages_resurfaced", pages);
+    }
+    Ok(())
+}
+
+// Command to set the date a page resurfaces on, or clear it with `None`. Returns the date
+// as stored, in UTC.
+#[tauri::command]
+pub async fn set_resurface_date(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+    resurface_at: Option<String>,
+) -> Result<Option<String>, String> {
+    let pool = get_pool(&app_handle).await?;
+    set_resurface_at(&pool, page_id, resurface_at.as_deref()).await
+}
+
+// Command to list pages waiting to resurface, soonest first
+#[tauri::command]
+pub async fn list_upcoming_resurfacing(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<UpcomingResurface>, String> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query_as::<_, UpcomingResurface>(
+        "SELECT id AS page_id, title, filename, resurface_at FROM pages
+         WHERE archived_at IS NULL AND resurface_at IS NOT NULL
+         ORDER BY resurface_at, id",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to list upcoming resurfacing: {}", e))
+}
+
+// Command to list the "Resurfaced" queue, most recently resurfaced first
+#[tauri::command]
+pub async fn list_resurfaced_pages(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ResurfacedPage>, String> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query_as::<_, ResurfacedPage>(&format!(
+        "{} ORDER BY rp.resurfaced_at DESC, p.title",
+        QUEUE_SQL
+    ))
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to list resurfaced pages: {}", e))
+}
+
+// Command to take a page out of the "Resurfaced" queue and have it resurface again in
+// `days`, or the `resurface_snooze_days` setting if not given. Returns the new date.
+#[tauri::command]
+pub async fn snooze_resurfaced_page(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+    days: Option<u64>,
+) -> Result<String, String> {
+    let days = days.unwrap_or_else(|| {
+        get_u64_setting(
+            &app_handle,
+            RESURFACE_SNOOZE_DAYS_KEY,
+            DEFAULT_RESURFACE_SNOOZE_DAYS,
+        )
+    });
+    let pool = get_pool(&app_handle).await?;
+    let snoozed_until: String = sqlx::query_scalar("SELECT datetime('now', ?)")
+        .bind(format!("+{} days", days))
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| format!("Failed to snooze page {}: {}", page_id, e))?;
+    set_resurface_at(&pool, page_id, Some(&snoozed_until)).await?;
+    Ok(snoozed_until)
+}
+
+// Command to take a page out of the "Resurfaced" queue without resurfacing it again
+#[tauri::command]
+pub async fn dismiss_resurfaced_page(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query("DELETE FROM resurfaced_pages WHERE page_id = ?")
+        .bind(page_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to dismiss page {}: {}", page_id, e))?;
+    Ok(())
+}
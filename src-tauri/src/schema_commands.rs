@@ -0,0 +1,60 @@
+// Schema versions. Every migration has a down migration (see `db::get_migrations`), so a
+// vault can be taken back to the schema an older version of NoteSponge expects before
+// switching to that version.
+
+use crate::backup_commands::create_pre_migration_copy;
+use crate::db::{get_pool, latest_schema_version, revert_migrations, schema_version};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaInfo {
+    pub app_version: String,
+    /// Schema version of the open vault's database
+    pub schema_version: i64,
+    /// Newest schema version this version of the app knows about
+    pub latest_schema_version: i64,
+}
+
+// Command to report the schema version of the open vault and the newest one the app supports
+#[tauri::command]
+pub async fn get_schema_info(app_handle: tauri::AppHandle) -> Result<SchemaInfo, String> {
+    let pool = get_pool(&app_handle).await?;
+    Ok(SchemaInfo {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version: schema_version(&pool).await?,
+        latest_schema_version: latest_schema_version(),
+    })
+}
+
+// Command to take the open vault back to schema version `target_version` for an older
+// version of NoteSponge. Saves a copy of the database first, then quits, since this
+// version of the app would upgrade the schema again as soon as it reopened the database.
+#[tauri::command]
+pub async fn downgrade_database(
+    app_handle: tauri::AppHandle,
+    target_version: i64,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let current = schema_version(&pool).await?;
+    if target_version < 1 || target_version >= current {
+        return Err(format!(
+            "Schema version must be between 1 and {}, got {}",
+            current - 1,
+            target_version
+        ));
+    }
+
+    let copy = create_pre_migration_copy(&app_handle, &pool).await?;
+    println!("Saved pre-downgrade copy: {}", copy.display());
+
+    revert_migrations(&pool, target_version).await?;
+    println!(
+        "Downgraded database from schema version {} to {}",
+        current, target_version
+    );
+
+    pool.close().await;
+    app_handle.exit(0);
+    Ok(())
+}
@@ -0,0 +1,475 @@
+// Search over pages. Text is matched against `pages_fts`, which indexes each page's title,
+// text and tags (see migrations/04-search-index.sql); the query language is described in
+// search_query.rs.
+
+use crate::db::get_pool;
+use crate::fuzzy::{fuzzy_match, subsequence_fuzzy_match, FuzzyMatch};
+use crate::search_index::{
+    chosen_tokenizer, indexed_tokenizer, rebuild_search_index, set_chosen_tokenizer,
+    SearchTokenizer,
+};
+use crate::search_query::{compile, Bind, CompiledQuery, QueryError};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use sqlx::query::QueryAs;
+use sqlx::sqlite::{Sqlite, SqliteArguments};
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// `bm25` weights for the title, text and tags columns. A match in the title counts ten
+/// times as much as one in the text.
+const TITLE_WEIGHT: f64 = 10.0;
+const TEXT_WEIGHT: f64 = 1.0;
+const TAGS_WEIGHT: f64 = 5.0;
+
+/// Roughly how many words of text a snippet shows around the best match
+const SNIPPET_TOKENS: i64 = 16;
+const SNIPPET_ELLIPSIS: &str = "…";
+/// How much of the text to show for pages found by filters alone, in characters
+const PREVIEW_LENGTH: i64 = 120;
+
+const DEFAULT_FUZZY_LIMIT: usize = 100;
+/// Most a page's viewing history can add to its fuzzy match score, for views and recency each
+const MAX_VIEW_COUNT_BOOST: f64 = 12.0;
+const MAX_RECENCY_BOOST: f64 = 10.0;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+/// Private use characters `highlight()` and `snippet()` wrap matches in, so they can be
+/// turned into offsets without being mistaken for anything a page might contain
+const MATCH_START: char = '\u{E000}';
+const MATCH_END: char = '\u{E001}';
+
+/// A match within some text, in UTF-16 code units so it can be used with JavaScript strings
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighlightedText {
+    pub text: String,
+    pub matches: Vec<MatchRange>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub id: i64,
+    pub title: HighlightedText,
+    pub filename: String,
+    /// The part of the page's text that best matches the query
+    pub snippet: HighlightedText,
+    /// Lower is better
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+    /// Number of matching pages across all pages of results
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+    /// Set, with no results, when the query couldn't be parsed
+    pub query_error: Option<QueryError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TitleMatch {
+    pub id: i64,
+    pub title: String,
+    /// Higher is better
+    pub score: f64,
+    pub matches: Vec<MatchRange>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SearchRow {
+    id: i64,
+    filename: String,
+    title_highlight: Option<String>,
+    snippet: Option<String>,
+    score: f64,
+}
+
+/// Removes the match markers from `marked`, recording where they were
+fn parse_highlights(marked: &str) -> HighlightedText {
+    let mut text = String::with_capacity(marked.len());
+    let mut matches = Vec::new();
+    let mut position = 0;
+    let mut start = None;
+
+    for c in marked.chars() {
+        match c {
+            MATCH_START => start = Some(position),
+            MATCH_END => {
+                if let Some(start) = start.take() {
+                    matches.push(MatchRange {
+                        start,
+                        end: position,
+                    });
+                }
+            }
+            _ => {
+                text.push(c);
+                position += c.len_utf16();
+            }
+        }
+    }
+    HighlightedText { text, matches }
+}
+
+fn bind_all<'q, O>(
+    mut query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    binds: &'q [Bind],
+) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+    for bind in binds {
+        query = match bind {
+            Bind::Text(text) => query.bind(text.as_str()),
+            Bind::Integer(n) => query.bind(*n),
+        };
+    }
+    query
+}
+
+async fn run_search(
+    pool: &SqlitePool,
+    compiled: CompiledQuery,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<SearchResult>, i64), sqlx::Error> {
+    let mut binds = Vec::new();
+    let mut conditions = Vec::new();
+    if let Some(fts) = compiled.fts.clone() {
+        conditions.push("pages_fts MATCH ?".to_string());
+        binds.push(Bind::Text(fts));
+    }
+    conditions.extend(compiled.conditions);
+    binds.extend(compiled.binds);
+    let where_clause = conditions.join(" AND ");
+
+    // Text queries are ranked by relevance, filter-only queries by recency
+    let (columns, from, order) = if compiled.fts.is_some() {
+        (
+            format!(
+                "highlight(pages_fts, 0, '{start}', '{end}') AS title_highlight,
+                 snippet(pages_fts, 1, '{start}', '{end}', '{ellipsis}', {tokens}) AS snippet,
+                 bm25(pages_fts, {title}, {text}, {tags}) AS score",
+                start = MATCH_START,
+                end = MATCH_END,
+                ellipsis = SNIPPET_ELLIPSIS,
+                tokens = SNIPPET_TOKENS,
+                title = TITLE_WEIGHT,
+                text = TEXT_WEIGHT,
+                tags = TAGS_WEIGHT,
+            ),
+            "pages_fts JOIN pages p ON p.id = pages_fts.rowid",
+            "score",
+        )
+    } else {
+        (
+            format!(
+                "p.title AS title_highlight,
+                 substr(p.plain_text, 1, {}) AS snippet,
+                 0.0 AS score",
+                PREVIEW_LENGTH
+            ),
+            "pages p",
+            "p.updated_at DESC",
+        )
+    };
+
+    let count_sql = format!("SELECT COUNT(*) FROM {} WHERE {}", from, where_clause);
+    let (total,): (i64,) = bind_all(sqlx::query_as(&count_sql), &binds)
+        .fetch_one(pool)
+        .await?;
+
+    let sql = format!(
+        "SELECT p.id, p.filename, {} FROM {} WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+        columns, from, where_clause, order
+    );
+    let rows: Vec<SearchRow> = bind_all(sqlx::query_as(&sql), &binds)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+    let results = rows
+        .into_iter()
+        .map(|row| SearchResult {
+            id: row.id,
+            title: parse_highlights(&row.title_highlight.unwrap_or_default()),
+            filename: row.filename,
+            snippet: parse_highlights(&row.snippet.unwrap_or_default()),
+            score: row.score,
+        })
+        .collect();
+    Ok((results, total))
+}
+
+/// Searches pages with a query in the language described in search_query.rs. A query that
+/// can't be parsed gives no results and says what's wrong with it.
+pub async fn search(
+    pool: &SqlitePool,
+    query: &str,
+    title_only: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<SearchResults, String> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let offset = offset.max(0);
+    let mut results = SearchResults {
+        results: Vec::new(),
+        total: 0,
+        offset,
+        limit,
+        query_error: None,
+    };
+    if query.trim().is_empty() {
+        return Ok(results);
+    }
+
+    let tokenizer = indexed_tokenizer(pool).await?;
+    let compiled = match compile(query, title_only, tokenizer, Utc::now()) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            results.query_error = Some(e);
+            return Ok(results);
+        }
+    };
+    let (pages, total) = run_search(pool, compiled, limit, offset)
+        .await
+        .map_err(|e| format!("Failed to search pages: {}", e))?;
+    results.results = pages;
+    results.total = total;
+    Ok(results)
+}
+
+// Command to search pages by title, text, tags, dates and state, best matches first
+#[tauri::command]
+pub async fn search_pages(
+    app_handle: tauri::AppHandle,
+    query: String,
+    title_only: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<SearchResults, String> {
+    let pool = get_pool(&app_handle).await?;
+    search(
+        &pool,
+        &query,
+        title_only.unwrap_or(false),
+        limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        offset.unwrap_or(0),
+    )
+    .await
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndexSettings {
+    /// The tokenizer the vault has chosen
+    pub tokenizer: SearchTokenizer,
+    /// The tokenizer the index was built with. Differs from `tokenizer` until the index is
+    /// rebuilt with `reindex_search`.
+    pub indexed_tokenizer: SearchTokenizer,
+}
+
+async fn search_index_settings(pool: &SqlitePool) -> Result<SearchIndexSettings, String> {
+    Ok(SearchIndexSettings {
+        tokenizer: chosen_tokenizer(pool).await?,
+        indexed_tokenizer: indexed_tokenizer(pool).await?,
+    })
+}
+
+// Command to get the open vault's search tokenizer and the one its index was built with
+#[tauri::command]
+pub async fn get_search_index_settings(
+    app_handle: tauri::AppHandle,
+) -> Result<SearchIndexSettings, String> {
+    let pool = get_pool(&app_handle).await?;
+    search_index_settings(&pool).await
+}
+
+// Command to choose the open vault's search tokenizer. Takes effect on `reindex_search`.
+#[tauri::command]
+pub async fn set_search_tokenizer(
+    app_handle: tauri::AppHandle,
+    tokenizer: SearchTokenizer,
+) -> Result<SearchIndexSettings, String> {
+    let pool = get_pool(&app_handle).await?;
+    set_chosen_tokenizer(&pool, tokenizer).await?;
+    search_index_settings(&pool).await
+}
+
+// Command to rebuild the search index with the open vault's chosen tokenizer
+#[tauri::command]
+pub async fn reindex_search(app_handle: tauri::AppHandle) -> Result<SearchIndexSettings, String> {
+    let pool = get_pool(&app_handle).await?;
+    let tokenizer = chosen_tokenizer(&pool).await?;
+    rebuild_search_index(&pool, tokenizer)
+        .await
+        .map_err(|e| format!("Failed to rebuild search index: {}", e))?;
+    println!("Rebuilt search index with the {:?} tokenizer", tokenizer);
+    search_index_settings(&pool).await
+}
+
+/// Titles of the pages that aren't in the trash, kept in memory for fuzzy title search so
+/// abbreviations can be matched without scanning the `pages` table. Tagged with the
+/// `title_index_version` they were read at (see migrations/14-title-index.sql).
+#[derive(Default)]
+pub struct TitleIndex(tokio::sync::Mutex<Option<(i64, IndexedTitles)>>);
+
+/// (page ID, title) pairs
+type IndexedTitles = Arc<Vec<(i64, String)>>;
+
+/// The indexed titles, read again first if they've changed
+async fn indexed_titles(
+    pool: &SqlitePool,
+    index: &TitleIndex,
+) -> Result<IndexedTitles, sqlx::Error> {
+    let version: i64 = sqlx::query_scalar("SELECT version FROM title_index_version")
+        .fetch_one(pool)
+        .await?;
+    let mut cached = index.0.lock().await;
+    if let Some((cached_version, titles)) = cached.as_ref() {
+        if *cached_version == version {
+            return Ok(titles.clone());
+        }
+    }
+
+    // Titles changed meanwhile are stored under the old version, so they're read again
+    // next time
+    let titles: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, title FROM pages WHERE archived_at IS NULL")
+            .fetch_all(pool)
+            .await?;
+    let titles = Arc::new(titles);
+    *cached = Some((version, titles.clone()));
+    Ok(titles)
+}
+
+/// Pages whose titles share a trigram with the query, the only ones that can match it with
+/// a typo
+async fn typo_candidates(pool: &SqlitePool, query: &str) -> Result<HashSet<i64>, sqlx::Error> {
+    let letters: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let trigrams: Vec<String> = letters
+        .windows(3)
+        .map(|w| format!("\"{}\"", w.iter().collect::<String>().replace('"', "\"\"")))
+        .collect();
+    if trigrams.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let ids: Vec<i64> =
+        sqlx::query_scalar("SELECT rowid FROM pages_title_fts WHERE pages_title_fts MATCH ?")
+            .bind(trigrams.join(" OR "))
+            .fetch_all(pool)
+            .await?;
+    Ok(ids.into_iter().collect())
+}
+
+#[derive(sqlx::FromRow)]
+struct ViewHistory {
+    id: i64,
+    view_count: i64,
+    last_viewed_at: Option<String>,
+}
+
+/// Extra score for pages viewed often or lately
+fn history_boost(history: Option<&ViewHistory>, now: NaiveDateTime) -> f64 {
+    let Some(history) = history else {
+        return 0.0;
+    };
+    let views = (history.view_count.max(0) as f64).ln_1p() * 4.0;
+    let recency = history
+        .last_viewed_at
+        .as_deref()
+        .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").ok())
+        .map(|viewed| {
+            let days = (now - viewed).num_minutes().max(0) as f64 / (24.0 * 60.0);
+            MAX_RECENCY_BOOST / (1.0 + days / 3.0)
+        })
+        .unwrap_or(0.0);
+    views.min(MAX_VIEW_COUNT_BOOST) + recency
+}
+
+/// Finds pages whose titles fuzzily match `query`, best first. Every title is scored before
+/// the best `limit` are picked.
+pub async fn fuzzy_find(
+    pool: &SqlitePool,
+    index: &TitleIndex,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<TitleMatch>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let search = async {
+        let titles = indexed_titles(pool, index).await?;
+        let typo_candidates = typo_candidates(pool, query).await?;
+        let found: Vec<(i64, String, FuzzyMatch)> = titles
+            .iter()
+            .filter_map(|(id, title)| {
+                let found = if typo_candidates.contains(id) {
+                    fuzzy_match(query, title)
+                } else {
+                    subsequence_fuzzy_match(query, title)
+                }?;
+                Some((*id, title.clone(), found))
+            })
+            .collect();
+
+        // Viewing history changes with every view, so it's looked up for the matches
+        // rather than kept in the index
+        let ids: Vec<i64> = found.iter().map(|(id, ..)| *id).collect();
+        let history: HashMap<i64, ViewHistory> = sqlx::query_as::<_, ViewHistory>(
+            "SELECT id, view_count, last_viewed_at FROM pages
+             WHERE id IN (SELECT value FROM json_each(?))",
+        )
+        .bind(serde_json::to_string(&ids).unwrap_or_default())
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|history| (history.id, history))
+        .collect();
+        Ok::<_, sqlx::Error>((found, history))
+    };
+    let (found, history) = search
+        .await
+        .map_err(|e| format!("Failed to search titles: {}", e))?;
+
+    let now = Utc::now().naive_utc();
+    let mut matches: Vec<TitleMatch> = found
+        .into_iter()
+        .map(|(id, title, found)| TitleMatch {
+            score: found.score + history_boost(history.get(&id), now),
+            id,
+            title,
+            matches: found.matches,
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches.truncate(limit);
+    Ok(matches)
+}
+
+// Command to find pages by title as the user types, tolerating typos
+#[tauri::command]
+pub async fn fuzzy_find_pages(
+    app_handle: tauri::AppHandle,
+    index: tauri::State<'_, TitleIndex>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<TitleMatch>, String> {
+    let pool = get_pool(&app_handle).await?;
+    fuzzy_find(&pool, &index, &query, limit.unwrap_or(DEFAULT_FUZZY_LIMIT)).await
+}
@@ -0,0 +1,111 @@
+// How the search index splits text into searchable tokens. Each vault picks a tokenizer,
+// stored in its `app_metadata`, and `pages_fts` is rebuilt with it on request. Until then
+// the index keeps the tokenizer it was built with, which is what queries have to go by.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+/// `app_metadata` key for the tokenizer the vault has chosen
+const TOKENIZER_KEY: &str = "search_tokenizer";
+
+/// Shortest text the trigram tokenizer can find through the index
+pub const MIN_TRIGRAM_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchTokenizer {
+    /// Words, with porter stemming and accents removed. Suits languages that separate
+    /// words with spaces.
+    Standard,
+    /// Every run of three characters, for Chinese, Japanese and Korean text, which isn't
+    /// split into words with spaces. Matches any text, but without stemming.
+    Cjk,
+}
+
+impl SearchTokenizer {
+    fn tokenize_option(self) -> &'static str {
+        match self {
+            SearchTokenizer::Standard => "porter unicode61 remove_diacritics 2",
+            SearchTokenizer::Cjk => "trigram remove_diacritics 1",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchTokenizer::Standard => "standard",
+            SearchTokenizer::Cjk => "cjk",
+        }
+    }
+}
+
+/// The tokenizer the vault has chosen, which `rebuild_search_index` builds the index with
+pub async fn chosen_tokenizer(pool: &SqlitePool) -> Result<SearchTokenizer, String> {
+    let value: Option<String> = sqlx::query_scalar("SELECT value FROM app_metadata WHERE key = ?")
+        .bind(TOKENIZER_KEY)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to read search settings: {}", e))?;
+    Ok(match value.as_deref() {
+        Some("cjk") => SearchTokenizer::Cjk,
+        _ => SearchTokenizer::Standard,
+    })
+}
+
+pub async fn set_chosen_tokenizer(
+    pool: &SqlitePool,
+    tokenizer: SearchTokenizer,
+) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO app_metadata (key, value) VALUES (?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+    )
+    .bind(TOKENIZER_KEY)
+    .bind(tokenizer.as_str())
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save search settings: {}", e))?;
+    Ok(())
+}
+
+/// The tokenizer `pages_fts` was built with, read from its definition
+pub async fn indexed_tokenizer(pool: &SqlitePool) -> Result<SearchTokenizer, String> {
+    let sql: Option<String> = sqlx::query_scalar(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'pages_fts'",
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to read search index: {}", e))?;
+    Ok(match sql {
+        Some(sql) if sql.contains("trigram") => SearchTokenizer::Cjk,
+        _ => SearchTokenizer::Standard,
+    })
+}
+
+/// Recreates `pages_fts` with `tokenizer` and indexes every page again. The triggers that
+/// keep it up to date refer to it by name, so they carry on working.
+pub async fn rebuild_search_index(
+    pool: &SqlitePool,
+    tokenizer: SearchTokenizer,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DROP TABLE IF EXISTS pages_fts")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!(
+        "CREATE VIRTUAL TABLE pages_fts USING fts5(
+             title,
+             plain_text,
+             tags,
+             content='pages_search',
+             content_rowid='id',
+             tokenize='{}'
+         )",
+        tokenizer.tokenize_option()
+    ))
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("INSERT INTO pages_fts(pages_fts) VALUES('rebuild')")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
@@ -0,0 +1,808 @@
+// The search query language. A query is a list of space separated terms, all of which
+// have to match:
+//
+//   word, word*, "a phrase"   text in the title, body or tags (`*` matches word prefixes)
+//   a OR b                    either of two text terms
+//   title:word                text in the title only
+//   tag:name                  pages tagged `name` or a tag nested under it, like `name/sub`;
+//                             if `name` is an alias, the tag it stands for
+//   is:archived               archived pages (they're left out otherwise); also is:tagged,
+//                             is:untagged
+//   created:DATE, updated:DATE, viewed:DATE
+//                             pages created, updated or last viewed on DATE
+//   viewed:N                  pages viewed N times
+//
+// Any term can be negated with a leading `-`, e.g. `-tag:done`. Dates and view counts can be
+// compared with `>`, `>=`, `<` and `<=`, e.g. `created:>2025-01-01` or `viewed:<3`. A DATE
+// is `2025-01-31`, `2025-01` or `2025` in local time, `today`, `yesterday`, `last-week`,
+// `last-month`, `last-year` (the past 7, 30 and 365 days), or `Nd` / `Nw` (the past N
+// days or weeks).
+//
+// With the trigram tokenizer (see search_index.rs), text shorter than three characters can't
+// be found through the index. It's matched by scanning pages instead, and can't be used
+// with OR.
+
+use crate::search_index::{SearchTokenizer, MIN_TRIGRAM_LENGTH};
+use crate::tag_commands::{normalize_tag, tag_or_descendant_condition};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use std::fmt;
+
+/// Timestamps are stored by `CURRENT_TIMESTAMP`, in UTC
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// What's wrong with a query and where, in UTF-16 code units so it can be used with
+/// JavaScript strings
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at {}..{})", self.message, self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Bind {
+    Text(String),
+    Integer(i64),
+}
+
+/// A query turned into SQL over `pages p`. `fts` is set when the query has text to match
+/// against `pages_fts`; `conditions` are ANDed into the WHERE clause and use `binds` in order.
+#[derive(Debug, Default)]
+pub struct CompiledQuery {
+    pub fts: Option<String>,
+    pub conditions: Vec<String>,
+    pub binds: Vec<Bind>,
+}
+
+#[derive(Debug)]
+struct Token {
+    negated: bool,
+    field: Option<String>,
+    value: String,
+    quoted: bool,
+    start: usize,
+    end: usize,
+    /// Where the value starts, after any `-` and `field:`
+    value_start: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> QueryError {
+        QueryError {
+            message: message.into(),
+            start: self.start,
+            end: self.end,
+        }
+    }
+
+    fn value_error(&self, message: impl Into<String>) -> QueryError {
+        QueryError {
+            message: message.into(),
+            start: self.value_start,
+            end: self.end,
+        }
+    }
+
+    fn is_or(&self) -> bool {
+        !self.negated && !self.quoted && self.field.is_none() && self.value == "OR"
+    }
+
+    fn is_positive_text(&self) -> bool {
+        !self.negated && !self.is_or() && matches!(self.field.as_deref(), None | Some("title"))
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    // (UTF-16 position, char) pairs, so errors point at the right place in JavaScript
+    let mut chars = Vec::new();
+    let mut position = 0;
+    for c in query.chars() {
+        chars.push((position, c));
+        position += c.len_utf16();
+    }
+    let end_of_query = position;
+    let position_at = |i: usize| chars.get(i).map_or(end_of_query, |(p, _)| *p);
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].1.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+
+        let negated =
+            chars[i].1 == '-' && chars.get(i + 1).is_some_and(|(_, c)| !c.is_whitespace());
+        if negated {
+            i += 1;
+        }
+
+        // `field:` is a run of letters followed by a colon
+        let mut field = None;
+        let field_len = chars[i..]
+            .iter()
+            .take_while(|(_, c)| c.is_ascii_alphabetic())
+            .count();
+        if field_len > 0 && chars.get(i + field_len).is_some_and(|(_, c)| *c == ':') {
+            field = Some(
+                chars[i..i + field_len]
+                    .iter()
+                    .map(|(_, c)| c.to_ascii_lowercase())
+                    .collect::<String>(),
+            );
+            i += field_len + 1;
+        }
+
+        let value_start = i;
+        let quoted = chars.get(i).is_some_and(|(_, c)| *c == '"');
+        let value: String = if quoted {
+            let Some(close) = chars[i + 1..].iter().position(|(_, c)| *c == '"') else {
+                return Err(QueryError {
+                    message: "Missing closing quote".to_string(),
+                    start: position_at(i),
+                    end: end_of_query,
+                });
+            };
+            let value = chars[i + 1..i + 1 + close].iter().map(|(_, c)| c).collect();
+            i += close + 2;
+            value
+        } else {
+            let len = chars[i..]
+                .iter()
+                .take_while(|(_, c)| !c.is_whitespace())
+                .count();
+            let value = chars[i..i + len].iter().map(|(_, c)| c).collect();
+            i += len;
+            value
+        };
+
+        tokens.push(Token {
+            negated,
+            field,
+            value,
+            quoted,
+            start: position_at(start),
+            end: position_at(i),
+            value_start: position_at(value_start),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Quotes text as an FTS5 phrase, so nothing in it is taken for FTS5 syntax
+fn fts_phrase(token: &Token, column: Option<&str>) -> Result<String, QueryError> {
+    let text = search_text(token);
+    let prefix = text.len() < token.value.len();
+    if text.trim().is_empty() {
+        return Err(token.value_error("Expected text to search for"));
+    }
+
+    let mut phrase = format!("\"{}\"", text.replace('"', "\"\""));
+    if prefix {
+        phrase.push_str(" *");
+    }
+    Ok(match column {
+        Some(column) => format!("{} : {}", column, phrase),
+        None => phrase,
+    })
+}
+
+/// Text without the `*` that makes it a prefix query
+fn search_text(token: &Token) -> &str {
+    match token.value.strip_suffix('*') {
+        Some(text) if !token.quoted => text,
+        _ => &token.value,
+    }
+}
+
+fn too_short_for_trigrams(token: &Token) -> bool {
+    search_text(token).chars().count() < MIN_TRIGRAM_LENGTH
+}
+
+/// Matches text anywhere in the title, body or tags, or only in `column`, without the index
+fn scan_condition(
+    token: &Token,
+    column: Option<&str>,
+    compiled: &mut CompiledQuery,
+) -> Result<String, QueryError> {
+    let text = search_text(token);
+    if text.trim().is_empty() {
+        return Err(token.value_error("Expected text to search for"));
+    }
+
+    // LIKE treats % and _ as wildcards, so they're escaped
+    let mut pattern = String::from("%");
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+
+    let columns = match column {
+        Some(column) => vec![column],
+        None => vec!["title", "plain_text", "tags"],
+    };
+    let matches: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            compiled.binds.push(Bind::Text(pattern.clone()));
+            format!("{} LIKE ? ESCAPE '\\'", column)
+        })
+        .collect();
+    Ok(format!(
+        "p.id IN (SELECT id FROM pages_search WHERE {})",
+        matches.join(" OR ")
+    ))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+fn split_comparison(value: &str) -> (Comparison, &str) {
+    for (prefix, comparison) in [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (comparison, rest);
+        }
+    }
+    (Comparison::Equal, value)
+}
+
+fn local_midnight(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// The span of time a date value covers, as [start, end)
+fn date_range(value: &str, now: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = now.with_timezone(&Local).date_naive();
+    // Ranges that reach the present run to the end of today, so they include the current second
+    let tomorrow = local_midnight(today.succ_opt()?)?;
+    let past = |duration: Duration| Some((now - duration, tomorrow));
+
+    match value.to_ascii_lowercase().as_str() {
+        "today" => return Some((local_midnight(today)?, tomorrow)),
+        "yesterday" => return Some((local_midnight(today.pred_opt()?)?, local_midnight(today)?)),
+        "last-week" => return past(Duration::days(7)),
+        "last-month" => return past(Duration::days(30)),
+        "last-year" => return past(Duration::days(365)),
+        _ => {}
+    }
+
+    if let Some(days) = value.strip_suffix('d').and_then(|n| n.parse::<i64>().ok()) {
+        return past(Duration::days(days));
+    }
+    if let Some(weeks) = value.strip_suffix('w').and_then(|n| n.parse::<i64>().ok()) {
+        return past(Duration::weeks(weeks));
+    }
+
+    let parts: Vec<&str> = value.split('-').collect();
+    let (first, next) = match parts.as_slice() {
+        [year] if year.len() == 4 => {
+            let year = year.parse().ok()?;
+            (
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+            )
+        }
+        [year, month] if year.len() == 4 => {
+            let first = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+            (first, first.checked_add_months(chrono::Months::new(1))?)
+        }
+        [_, _, _] => {
+            let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+            (day, day.succ_opt()?)
+        }
+        _ => return None,
+    };
+    Some((local_midnight(first)?, local_midnight(next)?))
+}
+
+fn timestamp(time: DateTime<Utc>) -> Bind {
+    Bind::Text(time.format(TIMESTAMP_FORMAT).to_string())
+}
+
+fn date_condition(
+    column: &str,
+    token: &Token,
+    compiled: &mut CompiledQuery,
+    now: DateTime<Utc>,
+) -> Result<String, QueryError> {
+    let (comparison, value) = split_comparison(&token.value);
+    let (start, end) = date_range(value, now).ok_or_else(|| {
+        token.value_error(format!(
+            "`{}` isn't a date. Use e.g. 2025-01-31, today or last-week",
+            value
+        ))
+    })?;
+
+    let condition = match comparison {
+        Comparison::Equal => {
+            compiled.binds.push(timestamp(start));
+            compiled.binds.push(timestamp(end));
+            return Ok(format!("({0} >= ? AND {0} < ?)", column));
+        }
+        Comparison::Greater => (">=", end),
+        Comparison::GreaterOrEqual => (">=", start),
+        Comparison::Less => ("<", start),
+        Comparison::LessOrEqual => ("<", end),
+    };
+    compiled.binds.push(timestamp(condition.1));
+    Ok(format!("{} {} ?", column, condition.0))
+}
+
+fn count_condition(
+    column: &str,
+    token: &Token,
+    compiled: &mut CompiledQuery,
+) -> Result<String, QueryError> {
+    let (comparison, value) = split_comparison(&token.value);
+    let count: i64 = value
+        .parse()
+        .map_err(|_| token.value_error(format!("`{}` isn't a number", value)))?;
+    let operator = match comparison {
+        Comparison::Equal => "=",
+        Comparison::Greater => ">",
+        Comparison::GreaterOrEqual => ">=",
+        Comparison::Less => "<",
+        Comparison::LessOrEqual => "<=",
+    };
+    compiled.binds.push(Bind::Integer(count));
+    Ok(format!("{} {} ?", column, operator))
+}
+
+const HAS_TAGS: &str = "EXISTS (SELECT 1 FROM tag_associations ta WHERE ta.page_id = p.id)";
+
+/// Parses `query` and compiles it to SQL for an index built with `tokenizer`. With
+/// `title_only`, text terms only match titles.
+pub fn compile(
+    query: &str,
+    title_only: bool,
+    tokenizer: SearchTokenizer,
+    now: DateTime<Utc>,
+) -> Result<CompiledQuery, QueryError> {
+    let tokens = tokenize(query)?;
+    let mut compiled = CompiledQuery::default();
+    // FTS5 binds AND tighter than OR, so each run of ORed terms is kept together to be
+    // parenthesized, and `x a OR b` means `x AND (a OR b)`. The ANDs have to be written out,
+    // since FTS5 doesn't allow leaving them out next to parentheses.
+    let mut fts_groups: Vec<Vec<String>> = Vec::new();
+    let mut after_or = false;
+    let mut excluded_text: Vec<String> = Vec::new();
+    let mut archived = None;
+    let text_column = title_only.then_some("title");
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_or() {
+            let between_text = i > 0
+                && tokens[i - 1].is_positive_text()
+                && tokens.get(i + 1).is_some_and(|t| t.is_positive_text());
+            if !between_text {
+                return Err(token.error("OR has to go between two search terms"));
+            }
+            after_or = true;
+            continue;
+        }
+
+        let condition = match token.field.as_deref() {
+            None | Some("title") => {
+                let column = token.field.as_deref().or(text_column);
+                if tokenizer == SearchTokenizer::Cjk && too_short_for_trigrams(token) {
+                    let next_to_or = (i > 0 && tokens[i - 1].is_or())
+                        || tokens.get(i + 1).is_some_and(|t| t.is_or());
+                    if next_to_or {
+                        return Err(token.value_error(format!(
+                            "Text shorter than {} characters can't be used with OR",
+                            MIN_TRIGRAM_LENGTH
+                        )));
+                    }
+                    scan_condition(token, column, &mut compiled)?
+                } else {
+                    let phrase = fts_phrase(token, column)?;
+                    if token.negated {
+                        excluded_text.push(phrase);
+                    } else if after_or {
+                        after_or = false;
+                        if let Some(group) = fts_groups.last_mut() {
+                            group.push(phrase);
+                        }
+                    } else {
+                        fts_groups.push(vec![phrase]);
+                    }
+                    continue;
+                }
+            }
+            Some("tag") => {
+                let tag = normalize_tag(&token.value)
+                    .map_err(|_| token.value_error("Expected a tag name"))?;
+                // Aliases never have pages of their own, so they're swapped for their tag
+                compiled.binds.push(Bind::Text(tag.clone()));
+                compiled.binds.push(Bind::Text(tag));
+                format!(
+                    "EXISTS (SELECT 1 FROM tag_associations ta JOIN tags t ON t.id = ta.tag_id
+                      JOIN (SELECT ? AS tag
+                            UNION SELECT target.tag FROM tags alias
+                            JOIN tags target ON target.id = alias.alias_of
+                            WHERE alias.tag = ?) wanted
+                      WHERE ta.page_id = p.id AND {})",
+                    tag_or_descendant_condition("t.tag", "wanted.tag")
+                )
+            }
+            Some("is") => match token.value.to_ascii_lowercase().as_str() {
+                "archived" => {
+                    archived = Some(!token.negated);
+                    continue;
+                }
+                "tagged" => HAS_TAGS.to_string(),
+                "untagged" => format!("NOT {}", HAS_TAGS),
+                other => {
+                    return Err(token.value_error(format!(
+                        "Unknown state `{}`. Use archived, tagged or untagged",
+                        other
+                    )))
+                }
+            },
+            Some("created") => date_condition("p.created_at", token, &mut compiled, now)?,
+            Some("updated") => date_condition("p.updated_at", token, &mut compiled, now)?,
+            Some("viewed") => {
+                // A bare number is a view count, anything else a date
+                let (_, value) = split_comparison(&token.value);
+                if value.parse::<i64>().is_ok() {
+                    count_condition("p.view_count", token, &mut compiled)?
+                } else {
+                    date_condition("p.last_viewed_at", token, &mut compiled, now)?
+                }
+            }
+            Some(field) => {
+                return Err(token.error(format!(
+                    "Unknown filter `{}:`. Use title, tag, is, created, updated or viewed, \
+                     or put text with a colon in quotes",
+                    field
+                )))
+            }
+        };
+        compiled.conditions.push(if token.negated {
+            format!("NOT ({})", condition)
+        } else {
+            condition
+        });
+    }
+
+    compiled.conditions.push(match archived {
+        Some(true) => "p.archived_at IS NOT NULL".to_string(),
+        _ => "p.archived_at IS NULL".to_string(),
+    });
+    if !excluded_text.is_empty() {
+        compiled
+            .conditions
+            .push("p.id NOT IN (SELECT rowid FROM pages_fts WHERE pages_fts MATCH ?)".to_string());
+        compiled.binds.push(Bind::Text(excluded_text.join(" OR ")));
+    }
+    if !fts_groups.is_empty() {
+        let terms: Vec<String> = fts_groups
+            .into_iter()
+            .map(|group| match group.len() {
+                1 => group.join(""),
+                _ => format!("({})", group.join(" OR ")),
+            })
+            .collect();
+        compiled.fts = Some(terms.join(" AND "));
+    }
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 15, 12, 0, 0).unwrap()
+    }
+
+    fn compile_with(query: &str, tokenizer: SearchTokenizer) -> CompiledQuery {
+        compile(query, false, tokenizer, now()).unwrap()
+    }
+
+    fn fts(query: &str) -> Option<String> {
+        compile_with(query, SearchTokenizer::Standard).fts
+    }
+
+    /// The conditions a query adds, without the one every query gets for the trash
+    fn conditions(compiled: &CompiledQuery) -> &[String] {
+        &compiled.conditions[..compiled.conditions.len() - 1]
+    }
+
+    fn binds(compiled: &CompiledQuery) -> Vec<String> {
+        compiled
+            .binds
+            .iter()
+            .map(|bind| match bind {
+                Bind::Text(text) => text.clone(),
+                Bind::Integer(n) => n.to_string(),
+            })
+            .collect()
+    }
+
+    fn error(query: &str) -> (String, usize, usize) {
+        let e = compile(query, false, SearchTokenizer::Standard, now()).unwrap_err();
+        (e.message, e.start, e.end)
+    }
+
+    fn midnight(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        local_midnight(NaiveDate::from_ymd_opt(year, month, day).unwrap()).unwrap()
+    }
+
+    fn stamp(time: DateTime<Utc>) -> String {
+        time.format(TIMESTAMP_FORMAT).to_string()
+    }
+
+    #[test]
+    fn terms_are_anded() {
+        assert_eq!(fts("apple pie").as_deref(), Some("\"apple\" AND \"pie\""));
+    }
+
+    #[test]
+    fn or_runs_are_grouped() {
+        assert_eq!(
+            fts("x a OR b").as_deref(),
+            Some("\"x\" AND (\"a\" OR \"b\")")
+        );
+        assert_eq!(
+            fts("a OR b OR c y").as_deref(),
+            Some("(\"a\" OR \"b\" OR \"c\") AND \"y\"")
+        );
+        assert_eq!(
+            fts("a OR b c OR title:d").as_deref(),
+            Some("(\"a\" OR \"b\") AND (\"c\" OR title : \"d\")")
+        );
+    }
+
+    #[test]
+    fn or_needs_text_on_both_sides() {
+        assert!(compile("OR a", false, SearchTokenizer::Standard, Utc::now()).is_err());
+        assert!(compile("a OR tag:b", false, SearchTokenizer::Standard, Utc::now()).is_err());
+    }
+
+    #[test]
+    fn quoted_phrases_and_prefixes() {
+        assert_eq!(fts("\"big cat\"").as_deref(), Some("\"big cat\""));
+        assert_eq!(fts("cat*").as_deref(), Some("\"cat\" *"));
+        // Inside quotes, `*`, `OR` and colons are just text
+        assert_eq!(fts("\"cat*\"").as_deref(), Some("\"cat*\""));
+        assert_eq!(
+            fts("a \"OR\" b").as_deref(),
+            Some("\"a\" AND \"OR\" AND \"b\"")
+        );
+        assert_eq!(fts("\"tag:x\"").as_deref(), Some("\"tag:x\""));
+        assert_eq!(fts("say\"hi\"").as_deref(), Some("\"say\"\"hi\"\"\""));
+        assert_eq!(
+            fts("title:\"big cat\"").as_deref(),
+            Some("title : \"big cat\"")
+        );
+
+        let title_only = compile("cat", true, SearchTokenizer::Standard, now()).unwrap();
+        assert_eq!(title_only.fts.as_deref(), Some("title : \"cat\""));
+
+        assert_eq!(
+            error("a \"big cat"),
+            ("Missing closing quote".to_string(), 2, 10)
+        );
+        assert_eq!(
+            error("\"\""),
+            ("Expected text to search for".to_string(), 0, 2)
+        );
+    }
+
+    #[test]
+    fn negated_terms() {
+        let compiled = compile_with("cat -dog -tag:done", SearchTokenizer::Standard);
+        assert_eq!(compiled.fts.as_deref(), Some("\"cat\""));
+        assert!(conditions(&compiled)[0].starts_with("NOT (EXISTS"));
+        assert_eq!(
+            compiled.conditions.last().unwrap(),
+            "p.id NOT IN (SELECT rowid FROM pages_fts WHERE pages_fts MATCH ?)"
+        );
+        assert_eq!(binds(&compiled), ["done", "done", "\"dog\""]);
+
+        // Only excluded text, so there's nothing to match against the index
+        let compiled = compile_with("-dog -cat", SearchTokenizer::Standard);
+        assert_eq!(compiled.fts, None);
+        assert_eq!(binds(&compiled), ["\"dog\" OR \"cat\""]);
+
+        // A lone `-` is text
+        assert_eq!(fts("a - b").as_deref(), Some("\"a\" AND \"-\" AND \"b\""));
+    }
+
+    #[test]
+    fn tags_match_descendants_and_aliases() {
+        let compiled = compile_with("tag:Project//Web/", SearchTokenizer::Standard);
+        assert_eq!(binds(&compiled), ["project/web", "project/web"]);
+        let condition = &conditions(&compiled)[0];
+        assert!(condition.contains("JOIN tags target ON target.id = alias.alias_of"));
+        assert!(condition.contains(&tag_or_descendant_condition("t.tag", "wanted.tag")));
+
+        assert_eq!(error("tag: a"), ("Expected a tag name".to_string(), 4, 4));
+        assert_eq!(error("tag:/"), ("Expected a tag name".to_string(), 4, 5));
+    }
+
+    #[test]
+    fn is_filters() {
+        let archived = compile_with("is:archived", SearchTokenizer::Standard);
+        assert_eq!(archived.conditions, ["p.archived_at IS NOT NULL"]);
+        let not_archived = compile_with("-is:Archived", SearchTokenizer::Standard);
+        assert_eq!(not_archived.conditions, ["p.archived_at IS NULL"]);
+
+        let tagged = compile_with("is:tagged", SearchTokenizer::Standard);
+        assert_eq!(conditions(&tagged), [HAS_TAGS]);
+        let untagged = compile_with("is:untagged", SearchTokenizer::Standard);
+        assert_eq!(conditions(&untagged), [format!("NOT {}", HAS_TAGS)]);
+        let not_untagged = compile_with("-is:untagged", SearchTokenizer::Standard);
+        assert_eq!(
+            conditions(&not_untagged),
+            [format!("NOT (NOT {})", HAS_TAGS)]
+        );
+
+        let (message, start, end) = error("is:pinned");
+        assert!(message.starts_with("Unknown state `pinned`"));
+        assert_eq!((start, end), (3, 9));
+        let (message, start, end) = error("a color:red");
+        assert!(message.starts_with("Unknown filter `color:`"));
+        assert_eq!((start, end), (2, 11));
+    }
+
+    #[test]
+    fn date_ranges() {
+        let today = now().with_timezone(&Local).date_naive();
+        let tomorrow = local_midnight(today.succ_opt().unwrap()).unwrap();
+        let range = |value: &str| date_range(value, now());
+
+        assert_eq!(
+            range("2025-01-31"),
+            Some((midnight(2025, 1, 31), midnight(2025, 2, 1)))
+        );
+        assert_eq!(
+            range("2024-12"),
+            Some((midnight(2024, 12, 1), midnight(2025, 1, 1)))
+        );
+        assert_eq!(
+            range("2024"),
+            Some((midnight(2024, 1, 1), midnight(2025, 1, 1)))
+        );
+        assert_eq!(
+            range("Today"),
+            Some((local_midnight(today).unwrap(), tomorrow))
+        );
+        assert_eq!(
+            range("yesterday"),
+            Some((
+                local_midnight(today.pred_opt().unwrap()).unwrap(),
+                local_midnight(today).unwrap()
+            ))
+        );
+        assert_eq!(
+            range("last-week"),
+            Some((now() - Duration::days(7), tomorrow))
+        );
+        assert_eq!(range("3d"), Some((now() - Duration::days(3), tomorrow)));
+        assert_eq!(range("2w"), Some((now() - Duration::weeks(2), tomorrow)));
+
+        for invalid in ["2025-13", "2025-02-30", "25", "soon", "d", ""] {
+            assert_eq!(range(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn date_filters() {
+        let compiled = compile_with("created:2025-01", SearchTokenizer::Standard);
+        assert_eq!(
+            conditions(&compiled),
+            ["(p.created_at >= ? AND p.created_at < ?)"]
+        );
+        assert_eq!(
+            binds(&compiled),
+            [stamp(midnight(2025, 1, 1)), stamp(midnight(2025, 2, 1))]
+        );
+
+        for (query, condition, bound) in [
+            (
+                "updated:>2025-01-31",
+                "p.updated_at >= ?",
+                midnight(2025, 2, 1),
+            ),
+            (
+                "updated:>=2025-01-31",
+                "p.updated_at >= ?",
+                midnight(2025, 1, 31),
+            ),
+            (
+                "viewed:<2025-01-31",
+                "p.last_viewed_at < ?",
+                midnight(2025, 1, 31),
+            ),
+            (
+                "viewed:<=2025-01-31",
+                "p.last_viewed_at < ?",
+                midnight(2025, 2, 1),
+            ),
+        ] {
+            let compiled = compile_with(query, SearchTokenizer::Standard);
+            assert_eq!(conditions(&compiled), [condition], "{}", query);
+            assert_eq!(binds(&compiled), [stamp(bound)], "{}", query);
+        }
+
+        let (message, start, end) = error("created:>soon");
+        assert!(message.starts_with("`soon` isn't a date"));
+        assert_eq!((start, end), (8, 13));
+    }
+
+    #[test]
+    fn view_counts() {
+        let compiled = compile_with("viewed:>=10 viewed:0", SearchTokenizer::Standard);
+        assert_eq!(
+            conditions(&compiled),
+            ["p.view_count >= ?", "p.view_count = ?"]
+        );
+        assert_eq!(binds(&compiled), ["10", "0"]);
+    }
+
+    #[test]
+    fn short_cjk_text_is_scanned() {
+        let compiled = compile_with("東京 tokyo", SearchTokenizer::Cjk);
+        assert_eq!(compiled.fts.as_deref(), Some("\"tokyo\""));
+        assert_eq!(
+            conditions(&compiled),
+            ["p.id IN (SELECT id FROM pages_search WHERE \
+              title LIKE ? ESCAPE '\\' OR plain_text LIKE ? ESCAPE '\\' OR tags LIKE ? ESCAPE '\\')"]
+        );
+        assert_eq!(binds(&compiled), ["%東京%", "%東京%", "%東京%"]);
+
+        // LIKE wildcards are escaped, and `*` is dropped as usual
+        let compiled = compile("title:5% -a_*", false, SearchTokenizer::Cjk, now()).unwrap();
+        assert_eq!(compiled.fts, None);
+        assert_eq!(binds(&compiled), ["%5\\%%", "%a\\_%", "%a\\_%", "%a\\_%"]);
+        assert!(conditions(&compiled)[1].starts_with("NOT (p.id IN"));
+
+        // The standard tokenizer finds short words through the index
+        assert_eq!(fts("東京").as_deref(), Some("\"東京\""));
+
+        let e = compile("東 OR tokyo", false, SearchTokenizer::Cjk, now()).unwrap_err();
+        assert_eq!((e.start, e.end), (0, 1));
+    }
+
+    #[test]
+    fn error_positions_are_in_utf16_code_units() {
+        // 😀 is two UTF-16 code units
+        let (_, start, end) = error("😀 is:nope");
+        assert_eq!((start, end), (6, 10));
+        let (_, start, end) = error("😀😀 x:y");
+        assert_eq!((start, end), (5, 8));
+        let (_, start, end) = error("é \"😀");
+        assert_eq!((start, end), (2, 5));
+        let (_, start, end) = error("😀 OR");
+        assert_eq!((start, end), (3, 5));
+    }
+}
@@ -0,0 +1,49 @@
+use serde_json::Value as JsonValue;
+use tauri_plugin_store::StoreExt;
+
+/// The settings store shared with the frontend (see tauriSettingsStore.ts)
+pub const SETTINGS_STORE: &str = "settings.json";
+
+pub fn get_setting(app_handle: &tauri::AppHandle, key: &str) -> Option<JsonValue> {
+    match app_handle.store(SETTINGS_STORE) {
+        Ok(store) => store.get(key),
+        Err(e) => {
+            eprintln!("Failed to load settings store: {}", e);
+            None
+        }
+    }
+}
+
+/// Reads a boolean setting, falling back to `default` if it's missing or invalid
+pub fn get_bool_setting(app_handle: &tauri::AppHandle, key: &str, default: bool) -> bool {
+    get_setting(app_handle, key)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(default)
+}
+
+/// Reads a non-negative integer setting, falling back to `default` if it's missing or invalid
+pub fn get_u64_setting(app_handle: &tauri::AppHandle, key: &str, default: u64) -> u64 {
+    get_setting(app_handle, key)
+        .and_then(|v| v.as_u64())
+        .unwrap_or(default)
+}
+
+/// Reads a string setting, returning `None` if it's missing or not a string
+pub fn get_string_setting(app_handle: &tauri::AppHandle, key: &str) -> Option<String> {
+    get_setting(app_handle, key).and_then(|v| v.as_str().map(String::from))
+}
+
+/// Writes a setting and saves the store
+pub fn set_setting(
+    app_handle: &tauri::AppHandle,
+    key: &str,
+    value: JsonValue,
+) -> Result<(), String> {
+    let store = app_handle
+        .store(SETTINGS_STORE)
+        .map_err(|e| format!("Failed to load settings store: {}", e))?;
+    store.set(key, value);
+    store
+        .save()
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
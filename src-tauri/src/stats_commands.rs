@@ -0,0 +1,264 @@
+// Statistics about a vault over time, for graphing. Timestamps are stored in UTC; they're
+// put into days, weeks and months in the computer's time zone with SQLite's `localtime`,
+// which takes daylight saving time at each timestamp into account. Writing activity is
+// recorded in `page_activity` by local day as it happens (see migration 15), so edits made
+// before then aren't counted. Periods with nothing in them are left out of every series, as
+// are timestamps SQLite can't read.
+
+use crate::db::get_pool;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+/// How many tags get a series in `tag_counts`, most used first
+const TOP_TAGS: i64 = 10;
+/// How many pages `most_viewed` lists
+const TOP_PAGES: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StatsGranularity {
+    #[default]
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+impl StatsGranularity {
+    /// SQL for the local date that starts the period `column` falls in, as `YYYY-MM-DD`
+    fn period_of(self, column: &str) -> String {
+        match self {
+            StatsGranularity::Day => format!("date({}, 'localtime')", column),
+            StatsGranularity::Week => {
+                format!("date({}, 'localtime', 'weekday 0', '-6 days')", column)
+            }
+            StatsGranularity::Month => format!("strftime('%Y-%m-01', {}, 'localtime')", column),
+        }
+    }
+
+    /// Like `period_of`, for a column that already holds a local date
+    fn period_of_date(self, column: &str) -> String {
+        match self {
+            StatsGranularity::Day => format!("date({})", column),
+            StatsGranularity::Week => format!("date({}, 'weekday 0', '-6 days')", column),
+            StatsGranularity::Month => format!("strftime('%Y-%m-01', {})", column),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityPoint {
+    /// First day of the period
+    pub period: String,
+    pub created: i64,
+    /// Pages whose text was edited in the period
+    pub updated: i64,
+    /// Pages in the trash that went there in the period
+    pub archived: i64,
+    #[sqlx(skip)]
+    pub total_created: i64,
+    /// Pages that existed and weren't in the trash at the end of the period
+    #[sqlx(skip)]
+    pub total_pages: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodCount {
+    pub period: String,
+    pub count: i64,
+}
+
+/// How many pages had a tag at the end of each period it was added to pages in
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagSeries {
+    pub tag: String,
+    pub points: Vec<PeriodCount>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewedPage {
+    pub page_id: i64,
+    pub title: String,
+    pub view_count: i64,
+    pub last_viewed_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStats {
+    pub granularity: StatsGranularity,
+    pub activity: Vec<ActivityPoint>,
+    /// Series for the most used tags. Tags taken off a page, and pages in the trash, aren't
+    /// counted at any point.
+    pub tag_counts: Vec<TagSeries>,
+    /// Words added to pages in each period. Each page's net change is counted per day, so
+    /// rewording a sentence adds nothing and deleting text doesn't count against other pages.
+    /// Pages in the trash aren't counted.
+    pub words_written: Vec<PeriodCount>,
+    pub most_viewed: Vec<ViewedPage>,
+}
+
+async fn activity(
+    pool: &SqlitePool,
+    granularity: StatsGranularity,
+) -> Result<Vec<ActivityPoint>, sqlx::Error> {
+    let mut points: Vec<ActivityPoint> = sqlx::query_as(&format!(
+        "SELECT period, SUM(created) AS created, SUM(updated) AS updated,
+                SUM(archived) AS archived
+         FROM (
+             SELECT {} AS period, 1 AS created, 0 AS updated, 0 AS archived
+             FROM pages WHERE created_at IS NOT NULL
+             UNION ALL
+             SELECT period, 0, 1, 0 FROM (
+                 SELECT DISTINCT {} AS period, page_id FROM page_activity WHERE edits > 0
+             )
+             UNION ALL
+             SELECT {}, 0, 0, 1 FROM pages WHERE archived_at IS NOT NULL
+         )
+         WHERE period IS NOT NULL
+         GROUP BY period ORDER BY period",
+        granularity.period_of("created_at"),
+        granularity.period_of_date("day"),
+        granularity.period_of("archived_at"),
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let (mut total_created, mut total_archived) = (0, 0);
+    for point in &mut points {
+        total_created += point.created;
+        total_archived += point.archived;
+        point.total_created = total_created;
+        point.total_pages = total_created - total_archived;
+    }
+    Ok(points)
+}
+
+async fn tag_counts(
+    pool: &SqlitePool,
+    granularity: StatsGranularity,
+) -> Result<Vec<TagSeries>, sqlx::Error> {
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(&format!(
+        "WITH live AS (
+             SELECT ta.tag_id, ta.created_at FROM tag_associations ta
+             JOIN pages p ON p.id = ta.page_id
+             WHERE p.archived_at IS NULL
+         ),
+         top_tags AS (
+             SELECT tag_id, COUNT(*) AS uses FROM live
+             GROUP BY tag_id ORDER BY uses DESC LIMIT ?
+         )
+         SELECT t.tag, {} AS period, COUNT(*)
+         FROM live
+         JOIN top_tags tt ON tt.tag_id = live.tag_id
+         JOIN tags t ON t.id = live.tag_id
+         WHERE period IS NOT NULL
+         GROUP BY t.id, period
+         ORDER BY tt.uses DESC, t.tag, period",
+        granularity.period_of("live.created_at"),
+    ))
+    .bind(TOP_TAGS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut series: Vec<TagSeries> = Vec::new();
+    for (tag, period, added) in rows {
+        match series.last_mut() {
+            Some(last) if last.tag == tag => {
+                let count = last.points.last().map_or(0, |point| point.count) + added;
+                last.points.push(PeriodCount { period, count });
+            }
+            _ => series.push(TagSeries {
+                tag,
+                points: vec![PeriodCount {
+                    period,
+                    count: added,
+                }],
+            }),
+        }
+    }
+    Ok(series)
+}
+
+async fn words_written(
+    pool: &SqlitePool,
+    granularity: StatsGranularity,
+) -> Result<Vec<PeriodCount>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "SELECT {} AS period, SUM(MAX(pa.words_delta, 0)) AS count
+         FROM page_activity pa
+         JOIN pages p ON p.id = pa.page_id
+         WHERE p.archived_at IS NULL AND period IS NOT NULL
+         GROUP BY period
+         HAVING count > 0
+         ORDER BY period",
+        granularity.period_of_date("pa.day"),
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+/// Counts words the way `pages.word_count` is counted when a page is saved
+pub fn count_words(text: &str) -> i64 {
+    text.split_whitespace().count() as i64
+}
+
+/// Fills in `word_count` for pages saved without one. Runs after every migration so the next
+/// edit to an older page only counts the words it changes.
+pub async fn backfill_word_counts(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let pages: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, plain_text FROM pages WHERE word_count IS NULL")
+            .fetch_all(pool)
+            .await?;
+
+    let mut tx = pool.begin().await?;
+    for (page_id, text) in &pages {
+        sqlx::query("UPDATE pages SET word_count = ? WHERE id = ?")
+            .bind(count_words(text))
+            .bind(page_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(pages.len() as u64)
+}
+
+pub async fn vault_stats(
+    pool: &SqlitePool,
+    granularity: StatsGranularity,
+) -> Result<VaultStats, sqlx::Error> {
+    let most_viewed = sqlx::query_as(
+        "SELECT id AS page_id, title, view_count, last_viewed_at FROM pages
+         WHERE archived_at IS NULL AND view_count > 0
+         ORDER BY view_count DESC, last_viewed_at DESC
+         LIMIT ?",
+    )
+    .bind(TOP_PAGES)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(VaultStats {
+        granularity,
+        activity: activity(pool, granularity).await?,
+        tag_counts: tag_counts(pool, granularity).await?,
+        words_written: words_written(pool, granularity).await?,
+        most_viewed,
+    })
+}
+
+// Command to get time series of page activity, tag use and words written per day, week or
+// month, along with the most viewed pages
+#[tauri::command]
+pub async fn get_vault_stats(
+    app_handle: tauri::AppHandle,
+    granularity: Option<StatsGranularity>,
+) -> Result<VaultStats, String> {
+    let pool = get_pool(&app_handle).await?;
+    vault_stats(&pool, granularity.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to get vault stats: {}", e))
+}
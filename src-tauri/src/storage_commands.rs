@@ -0,0 +1,216 @@
+use crate::db::{database_path, get_pool, sidecar_path};
+use crate::image_store::ImageStore;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// How many pages and attachments to list in the "largest" sections of the report
+const LARGEST_LIMIT: usize = 10;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TableUsage {
+    /// Table or index name as reported by `dbstat`
+    pub name: String,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PageUsage {
+    pub id: i64,
+    pub title: String,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentUsage {
+    pub id: i64,
+    pub page_id: i64,
+    pub original_filename: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageReport {
+    pub database_bytes: u64,
+    pub wal_bytes: u64,
+    /// Space inside the database file that VACUUM would give back
+    pub free_bytes: i64,
+    /// Title, plain text and Markdown of all pages
+    pub page_text_bytes: i64,
+    pub lexical_json_bytes: i64,
+    pub search_index_bytes: i64,
+    /// Image files in the image store, each counted once however many attachments share it
+    pub image_bytes: u64,
+    pub thumbnail_bytes: u64,
+    pub tables: Vec<TableUsage>,
+    pub largest_pages: Vec<PageUsage>,
+    pub largest_attachments: Vec<AttachmentUsage>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionResult {
+    /// Size of the database file plus its WAL before compacting
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+pub fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn database_size(db_path: &Path) -> u64 {
+    file_size(db_path) + file_size(&sidecar_path(db_path, "-wal"))
+}
+
+fn directory_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+async fn storage_report(
+    pool: &SqlitePool,
+    db_path: &Path,
+    image_store: &ImageStore,
+) -> Result<StorageReport, sqlx::Error> {
+    let free_bytes: i64 = sqlx::query_scalar(
+        "SELECT freelist_count * page_size FROM pragma_freelist_count, pragma_page_size",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    // Lengths are taken of the BLOB casts so they count bytes, not characters
+    let (page_text_bytes, lexical_json_bytes): (i64, i64) = sqlx::query_as(
+        "SELECT
+            COALESCE(SUM(length(CAST(title AS BLOB)) + length(CAST(plain_text AS BLOB))
+                + length(CAST(markdown_text AS BLOB))), 0),
+            COALESCE(SUM(length(CAST(lexical_json AS BLOB))), 0)
+         FROM pages",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let tables: Vec<TableUsage> = sqlx::query_as(
+        "SELECT name, SUM(pgsize) AS bytes FROM dbstat GROUP BY name ORDER BY bytes DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+    // FTS5 keeps its indexes in shadow tables named after the virtual tables
+    let search_index_bytes = tables
+        .iter()
+        .filter(|t| t.name.starts_with("pages_fts_") || t.name.starts_with("pages_title_fts_"))
+        .map(|t| t.bytes)
+        .sum();
+
+    let largest_pages: Vec<PageUsage> = sqlx::query_as(
+        "SELECT id, title,
+            length(CAST(lexical_json AS BLOB)) + length(CAST(plain_text AS BLOB))
+                + length(CAST(markdown_text AS BLOB)) AS bytes
+         FROM pages ORDER BY bytes DESC LIMIT ?",
+    )
+    .bind(LARGEST_LIMIT as i64)
+    .fetch_all(pool)
+    .await?;
+
+    let attachments: Vec<(i64, i64, String, String)> = sqlx::query_as(
+        "SELECT id, page_id, original_filename, content_hash FROM image_attachments
+         WHERE content_hash IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut image_sizes: HashMap<String, u64> = HashMap::new();
+    let mut largest_attachments: Vec<AttachmentUsage> = attachments
+        .into_iter()
+        .map(|(id, page_id, original_filename, content_hash)| {
+            let bytes = *image_sizes
+                .entry(content_hash)
+                .or_insert_with_key(|hash| file_size(&image_store.path_for(hash)));
+            AttachmentUsage {
+                id,
+                page_id,
+                original_filename,
+                bytes,
+            }
+        })
+        .collect();
+    largest_attachments.sort_by_key(|a| std::cmp::Reverse(a.bytes));
+    largest_attachments.truncate(LARGEST_LIMIT);
+
+    Ok(StorageReport {
+        database_bytes: file_size(db_path),
+        wal_bytes: file_size(&sidecar_path(db_path, "-wal")),
+        free_bytes,
+        page_text_bytes,
+        lexical_json_bytes,
+        search_index_bytes,
+        image_bytes: image_sizes.values().sum(),
+        thumbnail_bytes: directory_size(&image_store.dir.join("thumbnails")),
+        tables,
+        largest_pages,
+        largest_attachments,
+    })
+}
+
+/// Checkpoints the WAL, merges the search indexes, rebuilds the file without free pages,
+/// and refreshes query planner statistics
+async fn compact(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO pages_fts(pages_fts) VALUES('optimize')")
+        .execute(pool)
+        .await?;
+    sqlx::query("INSERT INTO pages_title_fts(pages_title_fts) VALUES('optimize')")
+        .execute(pool)
+        .await?;
+    sqlx::query("VACUUM").execute(pool).await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool)
+        .await?;
+    sqlx::query("PRAGMA optimize").execute(pool).await?;
+    Ok(())
+}
+
+// Command to report how much space the database and image store use, and on what
+#[tauri::command]
+pub async fn get_storage_report(app_handle: tauri::AppHandle) -> Result<StorageReport, String> {
+    let pool = get_pool(&app_handle).await?;
+    let db_path = database_path(&app_handle)?;
+    storage_report(&pool, &db_path, &ImageStore::current(&app_handle)?)
+        .await
+        .map_err(|e| format!("Failed to measure storage usage: {}", e))
+}
+
+// Command to compact the database and report its size before and after
+#[tauri::command]
+pub async fn compact_database(app_handle: tauri::AppHandle) -> Result<CompactionResult, String> {
+    let pool = get_pool(&app_handle).await?;
+    let db_path = database_path(&app_handle)?;
+
+    let bytes_before = database_size(&db_path);
+    compact(&pool)
+        .await
+        .map_err(|e| format!("Failed to compact database: {}", e))?;
+    let bytes_after = database_size(&db_path);
+
+    println!(
+        "Compacted database: {} -> {} bytes",
+        bytes_before, bytes_after
+    );
+    Ok(CompactionResult {
+        bytes_before,
+        bytes_after,
+    })
+}
@@ -0,0 +1,505 @@
+// Clusters of tags that tend to be used together, and the pages in them. Tags are connected
+// in a co-occurrence graph when they're on the same pages more often than chance, weighted by
+// how much their pages overlap, and the graph is split into clusters with the Louvain method.
+// The result is cached (see migrations/13-tag-clusters.sql) and rebuilt when it's asked for
+// after tags have changed, or by a background job that emits `tag_clusters_updated`. Pages in
+// the trash are left out.
+
+use crate::db::get_pool;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use tauri::Emitter;
+
+/// Louvain stops moving tags between clusters at a level after this many passes
+const MAX_PASSES: usize = 20;
+/// How many of its most used tags a cluster is named after
+const NAME_TAGS: usize = 3;
+
+/// Two tags used on the same pages, by tag ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedEdge {
+    source_tag_id: i64,
+    target_tag_id: i64,
+    co_occurrences: i64,
+    jaccard: f64,
+    npmi: f64,
+}
+
+/// What's kept in `tag_graph_cache`. Names and titles are looked up when it's read, so
+/// renaming a tag or page doesn't make it stale.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedGraph {
+    edges: Vec<CachedEdge>,
+    /// Tag IDs in each cluster, biggest cluster first
+    clusters: Vec<Vec<i64>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagEdge {
+    pub source: String,
+    pub target: String,
+    /// Pages with both tags
+    pub co_occurrences: i64,
+    /// Pages with both tags out of pages with either
+    pub jaccard: f64,
+    /// Normalized pointwise mutual information: above 0 when the tags are used together more
+    /// often than chance, 1 when they're always used together
+    pub npmi: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterTag {
+    pub tag: String,
+    pub page_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterPage {
+    pub page_id: i64,
+    pub title: String,
+    pub filename: String,
+    /// How many of the page's tags are in the cluster
+    pub matching_tags: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCluster {
+    /// The cluster's most used tags
+    pub name: String,
+    /// Most used first
+    pub tags: Vec<ClusterTag>,
+    /// Each page is in the cluster with most of its tags, and only that one
+    pub pages: Vec<ClusterPage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagGraph {
+    pub edges: Vec<TagEdge>,
+    /// Clusters of at least two tags, biggest first. Tags that aren't connected to any other
+    /// tag are left out.
+    pub clusters: Vec<TagCluster>,
+}
+
+/// Louvain community detection over nodes `0..node_count`: each node joins the neighboring
+/// community that raises modularity most until none does, then communities become the nodes
+/// of the next level, until a level changes nothing. Nodes are visited in order, so the
+/// result is the same every time. Returns each node's community.
+fn louvain(node_count: usize, edges: &[(usize, usize, f64)]) -> Vec<usize> {
+    // Weighted adjacency of the current level, including communities' links to themselves
+    let mut adjacency: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); node_count];
+    for &(a, b, weight) in edges {
+        *adjacency[a].entry(b).or_default() += weight;
+        *adjacency[b].entry(a).or_default() += weight;
+    }
+    let total_weight: f64 = adjacency.iter().flat_map(|links| links.values()).sum();
+    // The node each original node is part of at the current level
+    let mut membership: Vec<usize> = (0..node_count).collect();
+    if total_weight == 0.0 {
+        return membership;
+    }
+
+    loop {
+        let degree: Vec<f64> = adjacency.iter().map(|links| links.values().sum()).collect();
+        let mut community: Vec<usize> = (0..adjacency.len()).collect();
+        let mut community_degree = degree.clone();
+        let mut changed = false;
+
+        for _ in 0..MAX_PASSES {
+            let mut moved = false;
+            for node in 0..adjacency.len() {
+                let current = community[node];
+                community_degree[current] -= degree[node];
+
+                let mut links: BTreeMap<usize, f64> = BTreeMap::new();
+                for (&neighbor, &weight) in &adjacency[node] {
+                    if neighbor != node {
+                        *links.entry(community[neighbor]).or_default() += weight;
+                    }
+                }
+                // Proportional to the change in modularity from adding the node to `c`
+                let gain = |c: usize, weight: f64| {
+                    weight - community_degree[c] * degree[node] / total_weight
+                };
+                let mut best = current;
+                let mut best_gain = gain(current, links.get(&current).copied().unwrap_or(0.0));
+                for (&c, &weight) in &links {
+                    if gain(c, weight) > best_gain + 1e-12 {
+                        best = c;
+                        best_gain = gain(c, weight);
+                    }
+                }
+
+                community_degree[best] += degree[node];
+                if best != current {
+                    community[node] = best;
+                    moved = true;
+                }
+            }
+            if !moved {
+                break;
+            }
+            changed = true;
+        }
+        if !changed {
+            return membership;
+        }
+
+        // Number the communities from 0 and make each one a node of the next level
+        let mut numbers: BTreeMap<usize, usize> = BTreeMap::new();
+        for &c in &community {
+            let next = numbers.len();
+            numbers.entry(c).or_insert(next);
+        }
+        let community: Vec<usize> = community.iter().map(|c| numbers[c]).collect();
+        let mut next_adjacency: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); numbers.len()];
+        for (node, links) in adjacency.iter().enumerate() {
+            for (&neighbor, &weight) in links {
+                *next_adjacency[community[node]]
+                    .entry(community[neighbor])
+                    .or_default() += weight;
+            }
+        }
+        for member in &mut membership {
+            *member = community[*member];
+        }
+        adjacency = next_adjacency;
+    }
+}
+
+/// Builds the co-occurrence graph from (page ID, tag ID) pairs sorted by page and tag, and
+/// clusters it
+fn build_graph(associations: &[(i64, i64)]) -> CachedGraph {
+    let mut page_tags: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+    let mut tag_pages: HashMap<i64, i64> = HashMap::new();
+    for &(page_id, tag_id) in associations {
+        page_tags.entry(page_id).or_default().push(tag_id);
+        *tag_pages.entry(tag_id).or_default() += 1;
+    }
+    let page_count = page_tags.len() as f64;
+
+    let mut pairs: BTreeMap<(i64, i64), i64> = BTreeMap::new();
+    for tags in page_tags.values() {
+        for (i, &a) in tags.iter().enumerate() {
+            for &b in &tags[i + 1..] {
+                *pairs.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+    }
+
+    let edges: Vec<CachedEdge> = pairs
+        .into_iter()
+        .filter_map(|((a, b), both)| {
+            let (pages_a, pages_b) = (tag_pages[&a] as f64, tag_pages[&b] as f64);
+            let both_share = both as f64 / page_count;
+            let pmi = (both_share / ((pages_a / page_count) * (pages_b / page_count))).ln();
+            let npmi = if both_share >= 1.0 {
+                1.0
+            } else {
+                pmi / -both_share.ln()
+            };
+            (npmi > 0.0).then(|| CachedEdge {
+                source_tag_id: a,
+                target_tag_id: b,
+                co_occurrences: both,
+                jaccard: both as f64 / (pages_a + pages_b - both as f64),
+                npmi,
+            })
+        })
+        .collect();
+
+    let mut tag_ids: Vec<i64> = edges
+        .iter()
+        .flat_map(|edge| [edge.source_tag_id, edge.target_tag_id])
+        .collect();
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+    let index: HashMap<i64, usize> = tag_ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let weighted: Vec<(usize, usize, f64)> = edges
+        .iter()
+        .map(|edge| {
+            (
+                index[&edge.source_tag_id],
+                index[&edge.target_tag_id],
+                edge.jaccard,
+            )
+        })
+        .collect();
+
+    let mut clusters: BTreeMap<usize, Vec<i64>> = BTreeMap::new();
+    for (node, community) in louvain(tag_ids.len(), &weighted).into_iter().enumerate() {
+        clusters.entry(community).or_default().push(tag_ids[node]);
+    }
+    let mut clusters: Vec<Vec<i64>> = clusters
+        .into_values()
+        .filter(|tags| tags.len() > 1)
+        .collect();
+    clusters.sort_by_key(|tags| std::cmp::Reverse(tags.len()));
+
+    CachedGraph { edges, clusters }
+}
+
+/// The cached graph, rebuilt first if tags have changed since it was cached or `refresh`
+/// is set. Also returns whether it was rebuilt.
+async fn cached_graph(pool: &SqlitePool, refresh: bool) -> Result<(CachedGraph, bool), String> {
+    let (version, cached_version, graph): (i64, Option<i64>, Option<String>) =
+        sqlx::query_as("SELECT version, cached_version, graph FROM tag_graph_cache WHERE id = 1")
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to read tag cluster cache: {}", e))?;
+    if !refresh && cached_version == Some(version) {
+        if let Some(Ok(graph)) = graph.map(|graph| serde_json::from_str(&graph)) {
+            return Ok((graph, false));
+        }
+    }
+
+    let associations: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT ta.page_id, ta.tag_id FROM tag_associations ta
+         JOIN pages p ON p.id = ta.page_id
+         WHERE p.archived_at IS NULL
+         ORDER BY ta.page_id, ta.tag_id",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to read tags: {}", e))?;
+    let graph = build_graph(&associations);
+
+    // Stored under the version read before building it, so changes made meanwhile leave it
+    // stale
+    let json = serde_json::to_string(&graph)
+        .map_err(|e| format!("Failed to serialize tag clusters: {}", e))?;
+    sqlx::query("UPDATE tag_graph_cache SET cached_version = ?, graph = ? WHERE id = 1")
+        .bind(version)
+        .bind(json)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to cache tag clusters: {}", e))?;
+    Ok((graph, true))
+}
+
+/// Fills in the cached graph with tag names and the pages in each cluster
+async fn describe_graph(pool: &SqlitePool, graph: CachedGraph) -> Result<TagGraph, sqlx::Error> {
+    let tags: HashMap<i64, (String, i64)> = sqlx::query_as::<_, (i64, String, i64)>(
+        "SELECT t.id, t.tag, COUNT(p.id) FROM tags t
+         LEFT JOIN tag_associations ta ON ta.tag_id = t.id
+         LEFT JOIN pages p ON p.id = ta.page_id AND p.archived_at IS NULL
+         GROUP BY t.id",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(id, tag, page_count)| (id, (tag, page_count)))
+    .collect();
+    let page_rows: Vec<(i64, String, String, i64)> = sqlx::query_as(
+        "SELECT p.id, p.title, p.filename, ta.tag_id FROM tag_associations ta
+         JOIN pages p ON p.id = ta.page_id
+         WHERE p.archived_at IS NULL
+         ORDER BY p.title, p.id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let tag_name = |id: &i64| tags.get(id).map(|(tag, _)| tag.clone()).unwrap_or_default();
+    let edges = graph
+        .edges
+        .iter()
+        .map(|edge| TagEdge {
+            source: tag_name(&edge.source_tag_id),
+            target: tag_name(&edge.target_tag_id),
+            co_occurrences: edge.co_occurrences,
+            jaccard: edge.jaccard,
+            npmi: edge.npmi,
+        })
+        .collect();
+
+    let mut clusters: Vec<TagCluster> = graph
+        .clusters
+        .iter()
+        .map(|tag_ids| {
+            let mut cluster_tags: Vec<ClusterTag> = tag_ids
+                .iter()
+                .filter_map(|id| tags.get(id))
+                .map(|(tag, page_count)| ClusterTag {
+                    tag: tag.clone(),
+                    page_count: *page_count,
+                })
+                .collect();
+            cluster_tags.sort_by(|a, b| b.page_count.cmp(&a.page_count).then(a.tag.cmp(&b.tag)));
+            let name = cluster_tags
+                .iter()
+                .take(NAME_TAGS)
+                .map(|tag| tag.tag.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            TagCluster {
+                name,
+                tags: cluster_tags,
+                pages: Vec::new(),
+            }
+        })
+        .collect();
+
+    let cluster_of: HashMap<i64, usize> = graph
+        .clusters
+        .iter()
+        .enumerate()
+        .flat_map(|(cluster, tag_ids)| tag_ids.iter().map(move |&id| (id, cluster)))
+        .collect();
+    // Pages in title order, with how many of their tags are in each cluster
+    let mut pages: Vec<(i64, String, String, BTreeMap<usize, i64>)> = Vec::new();
+    for (page_id, title, filename, tag_id) in page_rows {
+        if pages.last().is_none_or(|(id, ..)| *id != page_id) {
+            pages.push((page_id, title, filename, BTreeMap::new()));
+        }
+        if let (Some(&cluster), Some((.., matches))) = (cluster_of.get(&tag_id), pages.last_mut()) {
+            *matches.entry(cluster).or_default() += 1;
+        }
+    }
+    for (page_id, title, filename, matches) in pages {
+        // The first of the clusters with the most matching tags
+        let best = matches.into_iter().fold(
+            None,
+            |best: Option<(usize, i64)>, (cluster, count)| match best {
+                Some((_, best_count)) if best_count >= count => best,
+                _ => Some((cluster, count)),
+            },
+        );
+        if let Some((cluster, matching_tags)) = best {
+            clusters[cluster].pages.push(ClusterPage {
+                page_id,
+                title,
+                filename,
+                matching_tags,
+            });
+        }
+    }
+
+    Ok(TagGraph { edges, clusters })
+}
+
+pub async fn tag_graph(pool: &SqlitePool, refresh: bool) -> Result<TagGraph, String> {
+    let (graph, _) = cached_graph(pool, refresh).await?;
+    describe_graph(pool, graph)
+        .await
+        .map_err(|e| format!("Failed to describe tag clusters: {}", e))
+}
+
+/// Background job: rebuilds the tag clusters if tags have changed, and emits
+/// `tag_clusters_updated` with them
+pub async fn run_tag_cluster_refresh(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let (graph, rebuilt) = cached_graph(&pool, false).await?;
+    if !rebuilt {
+        return Ok(());
+    }
+
+    let graph = describe_graph(&pool, graph)
+        .await
+        .map_err(|e| format!("Failed to describe tag clusters: {}", e))?;
+    println!("Rebuilt {} tag clusters", graph.clusters.len());
+    let _ = app_handle.emit("tag_clusters_updated", graph);
+    Ok(())
+}
+
+// Command to get the tag co-occurrence graph and its clusters, rebuilding them if tags have
+// changed or `refresh` is set
+#[tauri::command]
+pub async fn get_tag_clusters(
+    app_handle: tauri::AppHandle,
+    refresh: Option<bool>,
+) -> Result<TagGraph, String> {
+    let pool = get_pool(&app_handle).await?;
+    tag_graph(&pool, refresh.unwrap_or(false)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (page ID, tag ID) pairs for pages with the given tags, in the order `build_graph` reads
+    /// them
+    fn associations(pages: &[&[i64]]) -> Vec<(i64, i64)> {
+        let mut associations: Vec<(i64, i64)> = pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page, tags)| tags.iter().map(move |&tag| (page as i64, tag)))
+            .collect();
+        associations.sort_unstable();
+        associations
+    }
+
+    fn edge(graph: &CachedGraph, a: i64, b: i64) -> Option<&CachedEdge> {
+        graph
+            .edges
+            .iter()
+            .find(|edge| edge.source_tag_id == a && edge.target_tag_id == b)
+    }
+
+    #[test]
+    fn louvain_separates_disjoint_cliques() {
+        let mut edges = Vec::new();
+        for clique in [[0, 1, 2, 3], [4, 5, 6, 7]] {
+            for (i, &a) in clique.iter().enumerate() {
+                for &b in &clique[i + 1..] {
+                    edges.push((a, b, 1.0));
+                }
+            }
+        }
+        // A weak bridge doesn't merge them
+        edges.push((3, 4, 0.1));
+
+        let communities = louvain(8, &edges);
+        assert!(communities[..4].iter().all(|&c| c == communities[0]));
+        assert!(communities[4..].iter().all(|&c| c == communities[4]));
+        assert_ne!(communities[0], communities[4]);
+        assert_eq!(communities, louvain(8, &edges));
+    }
+
+    #[test]
+    fn louvain_leaves_unconnected_nodes_alone() {
+        assert_eq!(louvain(3, &[]), vec![0, 1, 2]);
+        assert_eq!(louvain(0, &[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn only_tags_used_together_more_than_chance_are_connected() {
+        // Tags 1 and 2 are always used together; tag 3 is mostly used without them
+        let graph = build_graph(&associations(&[
+            &[1, 2],
+            &[1, 2],
+            &[1, 2],
+            &[1, 2, 3],
+            &[3],
+            &[3],
+            &[3],
+        ]));
+
+        let together = edge(&graph, 1, 2).unwrap();
+        assert_eq!(together.co_occurrences, 4);
+        assert!((together.jaccard - 1.0).abs() < 1e-9);
+        assert!(together.npmi > 0.0);
+        assert!(edge(&graph, 1, 3).is_none());
+        assert!(edge(&graph, 2, 3).is_none());
+        assert_eq!(graph.clusters, vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn tags_used_on_different_topics_form_separate_clusters() {
+        let mut pages: Vec<&[i64]> = Vec::new();
+        pages.extend([&[1, 2, 3, 4][..]; 5]);
+        pages.extend([&[1, 2, 3][..]; 5]);
+        pages.extend([&[10, 11, 12, 13][..]; 5]);
+        pages.extend([&[10, 12, 13][..]; 5]);
+        pages.push(&[4, 10]);
+
+        let graph = build_graph(&associations(&pages));
+        assert_eq!(graph.clusters, vec![vec![1, 2, 3, 4], vec![10, 11, 12, 13]]);
+    }
+}
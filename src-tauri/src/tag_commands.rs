@@ -0,0 +1,390 @@
+// Tag management: renaming and merging tags, aliases (see migrations/12-tag-aliases.sql) and
+// nested tags. A nested tag is one whose name is a path, like `project/website`; it counts as
+// being under `project`, whether or not a `project` tag exists.
+
+use crate::db::get_pool;
+use serde::Serialize;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::{BTreeMap, HashSet};
+
+const TAG_SEPARATOR: char = '/';
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TagAlias {
+    pub alias: String,
+    /// The tag the alias stands for
+    pub tag: String,
+}
+
+/// A level of the nested tag hierarchy
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagNode {
+    /// The last part of the path, e.g. `website` for `project/website`
+    pub name: String,
+    pub tag: String,
+    /// False for a level that only exists because tags are nested under it
+    pub exists: bool,
+    /// Pages tagged with exactly this tag
+    pub page_count: i64,
+    /// Pages tagged with this tag or one nested under it
+    pub total_page_count: i64,
+    pub children: Vec<TagNode>,
+}
+
+/// SQL matching the tag in the SQL expression `tag`, or any tag nested under it, in `column`
+pub fn tag_or_descendant_condition(column: &str, tag: &str) -> String {
+    format!(
+        "({0} = {1} COLLATE NOCASE OR lower(substr({0}, 1, length({1}) + 1)) = lower({1}) || '/')",
+        column, tag
+    )
+}
+
+/// Tags are lowercase, and nested tags have no blank levels or spaces around the `/`
+pub fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag
+        .split(TAG_SEPARATOR)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+        .to_lowercase();
+    if tag.is_empty() {
+        return Err("Tag can't be empty".to_string());
+    }
+    Ok(tag)
+}
+
+/// The tag's ID, and the ID of the tag it stands for if it's an alias
+async fn find_tag(
+    conn: &mut SqliteConnection,
+    tag: &str,
+) -> Result<Option<(i64, Option<i64>)>, String> {
+    sqlx::query_as("SELECT id, alias_of FROM tags WHERE tag = ?")
+        .bind(tag)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| format!("Failed to look up tag \"{}\": {}", tag, e))
+}
+
+/// The ID of the tag `tag` stands for, creating the tag if there isn't one
+async fn canonical_tag_id(conn: &mut SqliteConnection, tag: &str) -> Result<i64, String> {
+    match find_tag(conn, tag).await? {
+        Some((id, alias_of)) => Ok(alias_of.unwrap_or(id)),
+        None => sqlx::query_scalar("INSERT INTO tags (tag) VALUES (?) RETURNING id")
+            .bind(tag)
+            .fetch_one(conn)
+            .await
+            .map_err(|e| format!("Failed to create tag \"{}\": {}", tag, e)),
+    }
+}
+
+/// Moves the pages and aliases of tag `source_id` to tag `target_id`. Pages that already
+/// have both keep one. `source_id` then becomes an alias of `target_id`, or is deleted.
+async fn merge_tag(
+    conn: &mut SqliteConnection,
+    source_id: i64,
+    target_id: i64,
+    keep_as_alias: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO tag_associations (page_id, tag_id, created_at)
+         SELECT page_id, ?, created_at FROM tag_associations WHERE tag_id = ?",
+    )
+    .bind(target_id)
+    .bind(source_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM tag_associations WHERE tag_id = ?")
+        .bind(source_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("UPDATE tags SET alias_of = ? WHERE alias_of = ?")
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let query = if keep_as_alias {
+        sqlx::query("UPDATE tags SET alias_of = ? WHERE id = ?").bind(target_id)
+    } else {
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+    };
+    query.bind(source_id).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Renames a tag, along with the tags nested under it. Where a new name is already taken,
+/// the tag is merged into the one with that name.
+pub async fn rename(pool: &SqlitePool, tag: &str, new_name: &str) -> Result<(), String> {
+    let (tag, new_name) = (normalize_tag(tag)?, normalize_tag(new_name)?);
+    if tag == new_name {
+        return Ok(());
+    }
+    if new_name.starts_with(&format!("{}/", tag)) {
+        return Err(format!("\"{}\" can't be nested under itself", tag));
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut tags: Vec<(i64, String, Option<i64>)> = sqlx::query_as(
+        "SELECT id, tag, alias_of FROM tags
+         WHERE tag = ? OR substr(tag, 1, length(?) + 1) = ? || '/'",
+    )
+    .bind(&tag)
+    .bind(&tag)
+    .bind(&tag)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to look up tag \"{}\": {}", tag, e))?;
+    if tags.is_empty() {
+        return Err(format!("No tag named \"{}\"", tag));
+    }
+
+    // Shorter names first, so a name is free by the time a longer one is renamed to it
+    tags.sort_by_key(|(_, name, _)| name.len());
+    for (id, name, alias_of) in tags {
+        let renamed = format!("{}{}", new_name, &name[tag.len()..]);
+        let failed = |e: sqlx::Error| format!("Failed to rename \"{}\": {}", name, e);
+        match find_tag(&mut tx, &renamed).await? {
+            None => {
+                sqlx::query("UPDATE tags SET tag = ? WHERE id = ?")
+                    .bind(&renamed)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(failed)?;
+            }
+            // An alias can't take a name that's in use
+            Some(_) if alias_of.is_some() => {
+                sqlx::query("DELETE FROM tags WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(failed)?;
+            }
+            // Renaming a tag to one of its own aliases
+            Some((other_id, Some(target_id))) if target_id == id => {
+                sqlx::query("DELETE FROM tags WHERE id = ?")
+                    .bind(other_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(failed)?;
+                sqlx::query("UPDATE tags SET tag = ? WHERE id = ?")
+                    .bind(&renamed)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(failed)?;
+            }
+            Some((other_id, other_alias_of)) => {
+                merge_tag(&mut tx, id, other_alias_of.unwrap_or(other_id), false)
+                    .await
+                    .map_err(failed)?;
+            }
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to rename \"{}\": {}", tag, e))
+}
+
+/// Merges `tags` into `into`, creating it if needed. With `keep_as_aliases`, the merged
+/// tags become aliases of `into`, so using them later still tags pages with `into`.
+pub async fn merge(
+    pool: &SqlitePool,
+    tags: &[String],
+    into: &str,
+    keep_as_aliases: bool,
+) -> Result<(), String> {
+    let into = normalize_tag(into)?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let target_id = canonical_tag_id(&mut tx, &into).await?;
+
+    for tag in tags {
+        let tag = normalize_tag(tag)?;
+        let Some((id, _)) = find_tag(&mut tx, &tag).await? else {
+            return Err(format!("No tag named \"{}\"", tag));
+        };
+        if id == target_id {
+            continue;
+        }
+        merge_tag(&mut tx, id, target_id, keep_as_aliases)
+            .await
+            .map_err(|e| format!("Failed to merge \"{}\" into \"{}\": {}", tag, into, e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to merge tags into \"{}\": {}", into, e))
+}
+
+/// Makes `alias` stand for `tag`. If `alias` is already a tag, its pages move to `tag`.
+pub async fn add_alias(pool: &SqlitePool, alias: &str, tag: &str) -> Result<(), String> {
+    let (alias, tag) = (normalize_tag(alias)?, normalize_tag(tag)?);
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let target_id = canonical_tag_id(&mut tx, &tag).await?;
+
+    match find_tag(&mut tx, &alias).await? {
+        Some((id, _)) if id == target_id => {
+            return Err(format!("\"{}\" can't be an alias of itself", alias));
+        }
+        Some((id, _)) => merge_tag(&mut tx, id, target_id, true)
+            .await
+            .map_err(|e| format!("Failed to add alias \"{}\": {}", alias, e))?,
+        None => {
+            sqlx::query("INSERT INTO tags (tag, alias_of) VALUES (?, ?)")
+                .bind(&alias)
+                .bind(target_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to add alias \"{}\": {}", alias, e))?;
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to add alias \"{}\": {}", alias, e))
+}
+
+/// Every tag that isn't an alias, arranged by nesting, with counts of pages that aren't in
+/// the trash
+pub async fn tag_tree(pool: &SqlitePool) -> Result<Vec<TagNode>, sqlx::Error> {
+    let rows: Vec<(String, Option<i64>)> = sqlx::query_as(
+        "SELECT t.tag, p.id FROM tags t
+         LEFT JOIN tag_associations ta ON ta.tag_id = t.id
+         LEFT JOIN pages p ON p.id = ta.page_id AND p.archived_at IS NULL
+         WHERE t.alias_of IS NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    #[derive(Default)]
+    struct Level {
+        exists: bool,
+        pages: HashSet<i64>,
+        children: BTreeMap<String, Level>,
+    }
+
+    fn finish(name: String, tag: String, level: Level) -> (TagNode, HashSet<i64>) {
+        let mut all_pages = level.pages.clone();
+        let children = level
+            .children
+            .into_iter()
+            .map(|(child_name, child)| {
+                let child_tag = format!("{}/{}", tag, child_name);
+                let (node, pages) = finish(child_name, child_tag, child);
+                all_pages.extend(pages);
+                node
+            })
+            .collect();
+        let node = TagNode {
+            name,
+            tag,
+            exists: level.exists,
+            page_count: level.pages.len() as i64,
+            total_page_count: all_pages.len() as i64,
+            children,
+        };
+        (node, all_pages)
+    }
+
+    let mut root = Level::default();
+    for (tag, page_id) in rows {
+        let level = tag.split(TAG_SEPARATOR).fold(&mut root, |level, part| {
+            level.children.entry(part.to_string()).or_default()
+        });
+        level.exists = true;
+        level.pages.extend(page_id);
+    }
+    Ok(root
+        .children
+        .into_iter()
+        .map(|(name, level)| finish(name.clone(), name, level).0)
+        .collect())
+}
+
+// Command to rename a tag and the tags nested under it, merging into any tag that already
+// has the new name
+#[tauri::command]
+pub async fn rename_tag(
+    app_handle: tauri::AppHandle,
+    tag: String,
+    new_name: String,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    rename(&pool, &tag, &new_name).await
+}
+
+// Command to merge tags into one. Unless `keep_as_aliases` is false, the merged tags become
+// aliases of it.
+#[tauri::command]
+pub async fn merge_tags(
+    app_handle: tauri::AppHandle,
+    tags: Vec<String>,
+    into: String,
+    keep_as_aliases: Option<bool>,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    merge(&pool, &tags, &into, keep_as_aliases.unwrap_or(true)).await
+}
+
+// Command to make a tag name stand for another tag
+#[tauri::command]
+pub async fn add_tag_alias(
+    app_handle: tauri::AppHandle,
+    alias: String,
+    tag: String,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    add_alias(&pool, &alias, &tag).await
+}
+
+// Command to remove an alias. Pages tagged through it keep the tag it stood for.
+#[tauri::command]
+pub async fn remove_tag_alias(app_handle: tauri::AppHandle, alias: String) -> Result<(), String> {
+    let alias = normalize_tag(&alias)?;
+    let pool = get_pool(&app_handle).await?;
+    let result = sqlx::query("DELETE FROM tags WHERE tag = ? AND alias_of IS NOT NULL")
+        .bind(&alias)
+        .execute(&pool)
+        .await
+        .map_err(|e| format!("Failed to remove alias \"{}\": {}", alias, e))?;
+    if result.rows_affected() == 0 {
+        return Err(format!("\"{}\" isn't an alias", alias));
+    }
+    Ok(())
+}
+
+// Command to list tag aliases, by the tag they stand for
+#[tauri::command]
+pub async fn list_tag_aliases(app_handle: tauri::AppHandle) -> Result<Vec<TagAlias>, String> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query_as::<_, TagAlias>(
+        "SELECT a.tag AS alias, t.tag FROM tags a
+         JOIN tags t ON t.id = a.alias_of
+         ORDER BY t.tag, a.tag",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to list tag aliases: {}", e))
+}
+
+// Command to get every tag arranged by nesting, with page counts
+#[tauri::command]
+pub async fn get_tag_tree(app_handle: tauri::AppHandle) -> Result<Vec<TagNode>, String> {
+    let pool = get_pool(&app_handle).await?;
+    tag_tree(&pool)
+        .await
+        .map_err(|e| format!("Failed to get tag tree: {}", e))
+}
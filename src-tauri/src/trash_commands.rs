@@ -0,0 +1,211 @@
+use crate::db::get_pool;
+use crate::relation_commands::DESCENDANTS_CTE;
+use crate::settings::get_u64_setting;
+use serde::Serialize;
+use sqlx::{SqliteConnection, SqlitePool};
+
+/// Settings key for how many days archived pages are kept before being purged
+pub const TRASH_RETENTION_DAYS_KEY: &str = "trash_retention_days";
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedPage {
+    pub id: i64,
+    pub title: String,
+    pub archived_at: String,
+}
+
+/// Deletes an archived page along with its images and tag associations.
+/// The `pages_bd` trigger removes the page from `pages_fts`.
+async fn delete_archived_page(pool: &SqlitePool, page_id: i64) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let is_archived = sqlx::query("SELECT 1 FROM pages WHERE id = ? AND archived_at IS NOT NULL")
+        .bind(page_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+    if !is_archived {
+        return Ok(false);
+    }
+
+    sqlx::query("DELETE FROM image_attachments WHERE page_id = ?")
+        .bind(page_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM tag_associations WHERE page_id = ?")
+        .bind(page_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM pages WHERE id = ?")
+        .bind(page_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Moves a page and every page below it in the parent/child hierarchy to the trash, all
+/// with the same `archived_at`, so they can be restored together. Returns the IDs of the
+/// pages that were moved.
+pub async fn archive_page(pool: &SqlitePool, page_id: i64) -> Result<Vec<i64>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let archived = archive_page_in(&mut tx, page_id).await?;
+    tx.commit().await?;
+    Ok(archived)
+}
+
+/// `archive_page` on a connection that's already in a transaction
+pub async fn archive_page_in(
+    conn: &mut SqliteConnection,
+    page_id: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let archived_at: String = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP")
+        .fetch_one(&mut *conn)
+        .await?;
+    sqlx::query_scalar(&format!(
+        "{} UPDATE pages SET archived_at = ?
+         WHERE archived_at IS NULL AND (id = ? OR id IN (SELECT id FROM descendants))
+         RETURNING id",
+        DESCENDANTS_CTE
+    ))
+    .bind(page_id)
+    .bind(&archived_at)
+    .bind(page_id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Takes a page out of the trash, along with the pages below it that went to the trash
+/// with it. Returns the IDs of the pages that were restored.
+pub async fn restore_archived_page(
+    pool: &SqlitePool,
+    page_id: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let archived_at: Option<String> = sqlx::query_scalar(
+        "SELECT archived_at FROM pages WHERE id = ? AND archived_at IS NOT NULL",
+    )
+    .bind(page_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(archived_at) = archived_at else {
+        return Ok(Vec::new());
+    };
+    let restored: Vec<i64> = sqlx::query_scalar(&format!(
+        "{} UPDATE pages SET archived_at = NULL
+         WHERE archived_at = ? AND (id = ? OR id IN (SELECT id FROM descendants))
+         RETURNING id",
+        DESCENDANTS_CTE
+    ))
+    .bind(page_id)
+    .bind(&archived_at)
+    .bind(page_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(restored)
+}
+
+/// Permanently deletes pages that have been archived for longer than `retention_days`
+pub async fn purge_expired_trash(
+    pool: &SqlitePool,
+    retention_days: u64,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let expired_ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM pages
+         WHERE archived_at IS NOT NULL
+         AND archived_at < datetime('now', ?)",
+    )
+    .bind(format!("-{} days", retention_days))
+    .fetch_all(pool)
+    .await?;
+
+    let mut purged = Vec::new();
+    for page_id in expired_ids {
+        if delete_archived_page(pool, page_id).await? {
+            purged.push(page_id);
+        }
+    }
+    Ok(purged)
+}
+
+/// Background job: purges the trash according to the retention setting.
+/// A retention of 0 days disables purging.
+pub async fn run_trash_purge(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let retention_days = get_u64_setting(
+        &app_handle,
+        TRASH_RETENTION_DAYS_KEY,
+        DEFAULT_TRASH_RETENTION_DAYS,
+    );
+    if retention_days == 0 {
+        return Ok(());
+    }
+
+    let pool = get_pool(&app_handle).await?;
+    let purged = purge_expired_trash(&pool, retention_days)
+        .await
+        .map_err(|e| format!("Failed to purge trash: {}", e))?;
+
+    if !purged.is_empty() {
+        println!("Purged {} pages from the trash: {:?}", purged.len(), purged);
+    }
+    Ok(())
+}
+
+// Command to move a page, and the pages below it in the parent/child hierarchy, to the trash
+#[tauri::command]
+pub async fn move_page_to_trash(app_handle: tauri::AppHandle, page_id: i64) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    archive_page(&pool, page_id)
+        .await
+        .map_err(|e| format!("Failed to move page {} to trash: {}", page_id, e))?;
+    Ok(())
+}
+
+// Command to list archived pages, most recently archived first
+#[tauri::command]
+pub async fn list_trashed_pages(app_handle: tauri::AppHandle) -> Result<Vec<TrashedPage>, String> {
+    let pool = get_pool(&app_handle).await?;
+    sqlx::query_as::<_, TrashedPage>(
+        "SELECT id, title, archived_at FROM pages
+         WHERE archived_at IS NOT NULL
+         ORDER BY archived_at DESC",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to list trashed pages: {}", e))
+}
+
+// Command to restore an archived page, and the pages that were moved to the trash with it
+#[tauri::command]
+pub async fn restore_page(app_handle: tauri::AppHandle, page_id: i64) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let restored = restore_archived_page(&pool, page_id)
+        .await
+        .map_err(|e| format!("Failed to restore page {}: {}", page_id, e))?;
+
+    if restored.is_empty() {
+        return Err(format!("Page {} is not in the trash", page_id));
+    }
+    Ok(())
+}
+
+// Command to permanently delete an archived page
+#[tauri::command]
+pub async fn delete_page_permanently(
+    app_handle: tauri::AppHandle,
+    page_id: i64,
+) -> Result<(), String> {
+    let pool = get_pool(&app_handle).await?;
+    let deleted = delete_archived_page(&pool, page_id)
+        .await
+        .map_err(|e| format!("Failed to delete page {}: {}", page_id, e))?;
+
+    if !deleted {
+        return Err(format!("Page {} is not in the trash", page_id));
+    }
+    Ok(())
+}
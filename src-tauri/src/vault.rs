@@ -0,0 +1,140 @@
+// A vault is a directory holding a database (`notesponge.db`), its image store (`images/`)
+// and, by default, its backups (`backups/`). The app data directory is the default vault;
+// others can live anywhere on disk. Exactly one vault is open at a time.
+
+use crate::settings::{get_setting, get_string_setting, set_setting};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tauri::Manager;
+
+pub const DATABASE_FILE_NAME: &str = "notesponge.db";
+pub const DEFAULT_VAULT_NAME: &str = "Default";
+
+/// Settings key for the directory of the vault that was open last
+pub const CURRENT_VAULT_KEY: &str = "current_vault";
+/// Settings key for the list of recently opened vaults
+pub const RECENT_VAULTS_KEY: &str = "recent_vaults";
+const MAX_RECENT_VAULTS: usize = 10;
+
+/// Directory of the vault that is currently open
+pub struct CurrentVault(RwLock<PathBuf>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentVault {
+    pub name: String,
+    pub path: String,
+    pub last_opened_at: String,
+}
+
+pub fn default_vault_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+impl CurrentVault {
+    /// Picks up the vault that was open last, falling back to the default vault
+    /// if there isn't one or it has since been moved or deleted
+    pub fn load(app_handle: &tauri::AppHandle) -> Result<Self, String> {
+        let dir = match get_string_setting(app_handle, CURRENT_VAULT_KEY).map(PathBuf::from) {
+            Some(dir) if dir.join(DATABASE_FILE_NAME).is_file() => dir,
+            Some(dir) => {
+                eprintln!(
+                    "Vault {} is missing, opening the default vault",
+                    dir.display()
+                );
+                default_vault_dir(app_handle)?
+            }
+            None => default_vault_dir(app_handle)?,
+        };
+        Ok(Self(RwLock::new(dir)))
+    }
+}
+
+/// Returns the directory of the open vault, creating it if needed
+pub fn current_vault_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = match app_handle.try_state::<CurrentVault>() {
+        Some(vault) => vault.0.read().unwrap().clone(),
+        None => default_vault_dir(app_handle)?,
+    };
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create vault directory: {}", e))?;
+    Ok(dir)
+}
+
+pub fn is_default_vault(app_handle: &tauri::AppHandle, dir: &Path) -> bool {
+    default_vault_dir(app_handle).is_ok_and(|default| default == dir)
+}
+
+/// Makes `dir` the open vault and remembers it for next launch.
+/// Only call this while the database is closed.
+pub fn set_current_vault(app_handle: &tauri::AppHandle, dir: PathBuf) -> Result<(), String> {
+    set_setting(
+        app_handle,
+        CURRENT_VAULT_KEY,
+        dir.to_string_lossy().to_string().into(),
+    )?;
+    if let Some(vault) = app_handle.try_state::<CurrentVault>() {
+        *vault.0.write().unwrap() = dir;
+    }
+    Ok(())
+}
+
+pub fn recent_vaults(app_handle: &tauri::AppHandle) -> Vec<RecentVault> {
+    get_setting(app_handle, RECENT_VAULTS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the name a vault was last opened under, or its directory name
+pub fn vault_name(app_handle: &tauri::AppHandle, dir: &Path) -> String {
+    if is_default_vault(app_handle, dir) {
+        return DEFAULT_VAULT_NAME.to_string();
+    }
+    let path = dir.to_string_lossy();
+    recent_vaults(app_handle)
+        .into_iter()
+        .find(|v| v.path == path)
+        .map(|v| v.name)
+        .unwrap_or_else(|| {
+            dir.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string())
+        })
+}
+
+/// Moves a vault to the top of the recent vaults list
+pub fn add_recent_vault(
+    app_handle: &tauri::AppHandle,
+    name: String,
+    dir: &Path,
+) -> Result<(), String> {
+    let path = dir.to_string_lossy().to_string();
+    let mut recent = recent_vaults(app_handle);
+    recent.retain(|v| v.path != path);
+    recent.insert(
+        0,
+        RecentVault {
+            name,
+            path,
+            last_opened_at: Utc::now().to_rfc3339(),
+        },
+    );
+    recent.truncate(MAX_RECENT_VAULTS);
+
+    let value = serde_json::to_value(recent).map_err(|e| e.to_string())?;
+    set_setting(app_handle, RECENT_VAULTS_KEY, value)
+}
+
+pub fn remove_recent_vault(app_handle: &tauri::AppHandle, dir: &Path) -> Result<(), String> {
+    let path = dir.to_string_lossy();
+    let mut recent = recent_vaults(app_handle);
+    recent.retain(|v| v.path != path);
+
+    let value = serde_json::to_value(recent).map_err(|e| e.to_string())?;
+    set_setting(app_handle, RECENT_VAULTS_KEY, value)
+}
@@ -0,0 +1,192 @@
+use crate::db::{
+    check_schema_version, get_pool, initialize_database, is_encrypted, open_pool, set_current_key,
+    with_database_closed,
+};
+use crate::image_store::ImageStore;
+use crate::vault::{
+    add_recent_vault, current_vault_dir, default_vault_dir, is_default_vault, recent_vaults,
+    remove_recent_vault, set_current_vault, vault_name, DATABASE_FILE_NAME, DEFAULT_VAULT_NAME,
+};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{Emitter, Manager};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultInfo {
+    pub name: String,
+    pub path: String,
+    pub is_current: bool,
+    pub is_default: bool,
+    pub encrypted: bool,
+    /// None for the default vault if it has never been switched away from
+    pub last_opened_at: Option<String>,
+}
+
+fn describe_vault(app_handle: &tauri::AppHandle, dir: &Path) -> VaultInfo {
+    let path = dir.to_string_lossy().to_string();
+    VaultInfo {
+        name: vault_name(app_handle, dir),
+        is_current: current_vault_dir(app_handle).is_ok_and(|current| current == dir),
+        is_default: is_default_vault(app_handle, dir),
+        encrypted: is_encrypted(&dir.join(DATABASE_FILE_NAME)),
+        last_opened_at: recent_vaults(app_handle)
+            .into_iter()
+            .find(|v| v.path == path)
+            .map(|v| v.last_opened_at),
+        path,
+    }
+}
+
+/// Closes page windows, which show pages from the old vault, and reloads the rest
+fn refresh_windows(app_handle: &tauri::AppHandle) {
+    for (label, window) in app_handle.webview_windows() {
+        let result = if label.starts_with("page_") {
+            window.close()
+        } else {
+            window.eval("window.location.reload()")
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to refresh window {}: {}", label, e);
+        }
+    }
+}
+
+/// Closes the open vault and opens the one in `dir`
+async fn switch_to(
+    app_handle: &tauri::AppHandle,
+    dir: PathBuf,
+    name: String,
+    passphrase: Option<String>,
+) -> Result<VaultInfo, String> {
+    let db_path = dir.join(DATABASE_FILE_NAME);
+    let key = if is_encrypted(&db_path) {
+        Some(
+            passphrase
+                .ok_or_else(|| "This vault is encrypted, enter its passphrase".to_string())?,
+        )
+    } else {
+        None
+    };
+
+    // Make sure the new vault opens, and that this version of the app can read it,
+    // before closing the current one
+    let pool = open_pool(&db_path, key.as_deref()).await?;
+    let checked = check_schema_version(&pool).await;
+    pool.close().await;
+    checked?;
+
+    let switch = |dir: PathBuf, key: Option<String>| {
+        set_current_vault(app_handle, dir)?;
+        set_current_key(app_handle, key);
+        Ok::<_, String>(())
+    };
+    if get_pool(app_handle).await.is_ok() {
+        with_database_closed(app_handle, |_| async { switch(dir.clone(), key) }).await?;
+    } else {
+        // The current vault is still locked, so there is nothing to close
+        switch(dir.clone(), key.clone())?;
+        initialize_database(app_handle, key).await?;
+    }
+
+    let pool = get_pool(app_handle).await?;
+    ImageStore::current(app_handle)?
+        .upgrade_attachments(&pool)
+        .await;
+
+    add_recent_vault(app_handle, name, &dir)?;
+    let vault = describe_vault(app_handle, &dir);
+    println!("Opened vault {} at {}", vault.name, vault.path);
+
+    let _ = app_handle.emit("vault_changed", vault.clone());
+    refresh_windows(app_handle);
+    Ok(vault)
+}
+
+// Command to describe the open vault
+#[tauri::command]
+pub async fn get_current_vault(app_handle: tauri::AppHandle) -> Result<VaultInfo, String> {
+    Ok(describe_vault(
+        &app_handle,
+        &current_vault_dir(&app_handle)?,
+    ))
+}
+
+// Command to list the default vault and recently opened vaults, most recent first
+#[tauri::command]
+pub async fn list_vaults(app_handle: tauri::AppHandle) -> Result<Vec<VaultInfo>, String> {
+    let default_dir = default_vault_dir(&app_handle)?;
+    let mut vaults: Vec<VaultInfo> = recent_vaults(&app_handle)
+        .into_iter()
+        .map(|v| describe_vault(&app_handle, Path::new(&v.path)))
+        .collect();
+    if !vaults.iter().any(|v| v.is_default) {
+        vaults.push(describe_vault(&app_handle, &default_dir));
+    }
+    Ok(vaults)
+}
+
+// Command to create an empty vault in `path` and switch to it
+#[tauri::command]
+pub async fn create_vault(
+    app_handle: tauri::AppHandle,
+    name: String,
+    path: String,
+) -> Result<VaultInfo, String> {
+    let dir = PathBuf::from(&path);
+    if dir.join(DATABASE_FILE_NAME).exists() {
+        return Err(format!("There is already a vault in {}", path));
+    }
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Vault name can't be empty".to_string());
+    }
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create vault directory: {}", e))?;
+
+    switch_to(&app_handle, dir, name, None).await
+}
+
+// Command to open an existing vault from anywhere on disk and switch to it
+#[tauri::command]
+pub async fn open_vault(
+    app_handle: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<VaultInfo, String> {
+    let dir = PathBuf::from(&path);
+    if !dir.join(DATABASE_FILE_NAME).is_file() {
+        return Err(format!("{} is not a vault", path));
+    }
+    let name = vault_name(&app_handle, &dir);
+    switch_to(&app_handle, dir, name, passphrase).await
+}
+
+// Command to switch to a vault from the recent vaults list
+#[tauri::command]
+pub async fn switch_vault(
+    app_handle: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<VaultInfo, String> {
+    let dir = PathBuf::from(&path);
+    if !dir.join(DATABASE_FILE_NAME).is_file() {
+        remove_recent_vault(&app_handle, &dir)?;
+        return Err(format!("Vault {} has been moved or deleted", path));
+    }
+    let name = vault_name(&app_handle, &dir);
+    switch_to(&app_handle, dir, name, passphrase).await
+}
+
+// Command to close the open vault and go back to the default vault
+#[tauri::command]
+pub async fn close_vault(
+    app_handle: tauri::AppHandle,
+    passphrase: Option<String>,
+) -> Result<VaultInfo, String> {
+    let dir = default_vault_dir(&app_handle)?;
+    if current_vault_dir(&app_handle)? == dir {
+        return Err("The default vault can't be closed".to_string());
+    }
+    switch_to(&app_handle, dir, DEFAULT_VAULT_NAME.to_string(), passphrase).await
+}
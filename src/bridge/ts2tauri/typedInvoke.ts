@@ -38,12 +38,878 @@ type DisableEditorMenusCommand = {
   result: void;
 };
 
-type InvokeCommand = SyncToDirectoryCommand | UpdateEditorStateCommand | DisableEditorMenusCommand;
+export type TrashedPage = {
+  id: number;
+  title: string;
+  archivedAt: string;
+};
+
+type MovePageToTrashCommand = {
+  cmd: "move_page_to_trash";
+  args: { pageId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type ListTrashedPagesCommand = {
+  cmd: "list_trashed_pages";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: TrashedPage[];
+};
+
+type RestorePageCommand = {
+  cmd: "restore_page";
+  args: { pageId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type DeletePagePermanentlyCommand = {
+  cmd: "delete_page_permanently";
+  args: { pageId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+export type MaintenanceReport = {
+  tagsDeleted: number;
+  imagesDeleted: number;
+  imageBytesReclaimed: number;
+};
+
+type RunMaintenanceCommand = {
+  cmd: "run_maintenance";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: MaintenanceReport;
+};
+
+type CreateImageAttachmentCommand = {
+  cmd: "create_image_attachment";
+  args: {
+    pageId: number;
+    mimeType: string;
+    data: string;
+    originalFilename: string;
+    fileExtension: string;
+  };
+  result: { id: number; fileExtension: string };
+};
+
+export type ImageAttachment = {
+  dataUrl: string;
+  width: number | null;
+  height: number | null;
+  originalFilename: string;
+  fileExtension: string;
+};
+
+type GetImageAttachmentCommand = {
+  cmd: "get_image_attachment";
+  args: { id: number };
+  result: ImageAttachment | null;
+};
+
+export type ThumbnailSize = "small" | "medium";
+
+type GetImageThumbnailCommand = {
+  cmd: "get_image_thumbnail";
+  args: { id: number; size: ThumbnailSize };
+  result: string | null;
+};
+
+export type RecompressionReport = {
+  imagesProcessed: number;
+  imagesChanged: number;
+  bytesBefore: number;
+  bytesAfter: number;
+  /** Hashes of images left as they were because they couldn't be read, decoded or saved */
+  skippedImages: string[];
+};
+
+type RecompressAllImagesCommand = {
+  cmd: "recompress_all_images";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: RecompressionReport;
+};
+
+export type Snapshot = {
+  fileName: string;
+  path: string;
+  createdAt: string;
+  sizeBytes: number;
+  encrypted: boolean;
+  pageCount: number | null;
+};
+
+type BackUpNowCommand = {
+  cmd: "back_up_now";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: Snapshot;
+};
+
+type ListSnapshotsCommand = {
+  cmd: "list_snapshots";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: Snapshot[];
+};
+
+export type RestoreResult = {
+  restoredFrom: string;
+  safetyCopyPath: string;
+  schemaVersion: number;
+  /** Hashes of images the snapshot refers to that couldn't be restored */
+  missingImages: string[];
+};
+
+type RestoreSnapshotCommand = {
+  cmd: "restore_snapshot";
+  args: { path: string; passphrase?: string };
+  result: RestoreResult;
+};
+
+export type DatabaseCheckReport = {
+  integrityErrors: string[];
+  foreignKeyViolations: { table: string; rowid: number | null; parent: string }[];
+  danglingTagAssociations: { pageId: number; tagId: number }[];
+  pagesMissingPlainText: number[];
+  pagesWithStaleSearchTags: number[];
+  searchIndexOutOfSync: boolean;
+  repaired: boolean;
+};
+
+type CheckDatabaseHealthCommand = {
+  cmd: "check_database_health";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: DatabaseCheckReport;
+};
+
+type RepairDatabaseCommand = {
+  cmd: "repair_database";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: DatabaseCheckReport;
+};
+
+export type StorageReport = {
+  databaseBytes: number;
+  walBytes: number;
+  freeBytes: number;
+  pageTextBytes: number;
+  lexicalJsonBytes: number;
+  searchIndexBytes: number;
+  imageBytes: number;
+  thumbnailBytes: number;
+  tables: { name: string; bytes: number }[];
+  largestPages: { id: number; title: string; bytes: number }[];
+  largestAttachments: { id: number; pageId: number; originalFilename: string; bytes: number }[];
+};
+
+export type CompactionResult = {
+  bytesBefore: number;
+  bytesAfter: number;
+};
+
+type GetStorageReportCommand = {
+  cmd: "get_storage_report";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: StorageReport;
+};
+
+type CompactDatabaseCommand = {
+  cmd: "compact_database";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: CompactionResult;
+};
+
+export type DatabaseStatus = {
+  encrypted: boolean;
+  unlocked: boolean;
+  error: string | null;
+  /** Images are stored unencrypted even when the database is encrypted */
+  unencryptedImageCount: number;
+};
+
+type GetDatabaseStatusCommand = {
+  cmd: "get_database_status";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: DatabaseStatus;
+};
+
+type UnlockDatabaseCommand = {
+  cmd: "unlock_database";
+  args: { passphrase: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type EnableEncryptionCommand = {
+  cmd: "enable_encryption";
+  args: { passphrase: string; allowUnencryptedImages?: boolean };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type DisableEncryptionCommand = {
+  cmd: "disable_encryption";
+  args: { passphrase: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type ChangePassphraseCommand = {
+  cmd: "change_passphrase";
+  args: { currentPassphrase: string; newPassphrase: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+export type MatchRange = {
+  /** In UTF-16 code units, like JavaScript string indices */
+  start: number;
+  end: number;
+};
+
+export type HighlightedText = {
+  text: string;
+  matches: MatchRange[];
+};
+
+export type QueryError = {
+  message: string;
+  /** In UTF-16 code units, like JavaScript string indices */
+  start: number;
+  end: number;
+};
+
+export type SearchResult = {
+  id: number;
+  title: HighlightedText;
+  filename: string;
+  snippet: HighlightedText;
+  score: number;
+};
+
+export type SearchResults = {
+  results: SearchResult[];
+  total: number;
+  offset: number;
+  limit: number;
+  /** Set, with no results, when the query couldn't be parsed */
+  queryError: QueryError | null;
+};
+
+type SearchPagesCommand = {
+  cmd: "search_pages";
+  args: { query: string; titleOnly?: boolean; limit?: number; offset?: number };
+  result: SearchResults;
+};
+
+export type TitleMatch = {
+  id: number;
+  title: string;
+  score: number;
+  matches: MatchRange[];
+};
+
+type FuzzyFindPagesCommand = {
+  cmd: "fuzzy_find_pages";
+  args: { query: string; limit?: number };
+  result: TitleMatch[];
+};
+
+/** "standard" stems words and ignores accents; "cjk" indexes runs of three characters */
+export type SearchTokenizer = "standard" | "cjk";
+
+export type SearchIndexSettings = {
+  tokenizer: SearchTokenizer;
+  /** Differs from `tokenizer` until the index is rebuilt */
+  indexedTokenizer: SearchTokenizer;
+};
+
+type GetSearchIndexSettingsCommand = {
+  cmd: "get_search_index_settings";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: SearchIndexSettings;
+};
+
+type SetSearchTokenizerCommand = {
+  cmd: "set_search_tokenizer";
+  args: { tokenizer: SearchTokenizer };
+  result: SearchIndexSettings;
+};
+
+type ReindexSearchCommand = {
+  cmd: "reindex_search";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: SearchIndexSettings;
+};
+
+export type Backlink = {
+  /** The linking page */
+  pageId: number;
+  title: string;
+  filename: string;
+  /** Which of the linking page's links this is, counting from 0 */
+  position: number;
+  /** Text around the link, with the link itself as the match */
+  context: HighlightedText;
+};
+
+type GetBacklinksCommand = {
+  cmd: "get_backlinks";
+  args: { pageId: number };
+  result: Backlink[];
+};
+
+export type PageFinding = {
+  pageId: number;
+  title: string;
+};
+
+export type BrokenLink = {
+  /** The page the link is on */
+  pageId: number;
+  title: string;
+  targetPageId: number;
+  /** Title of the linked page if it's in the trash, null if it doesn't exist */
+  targetTitle: string | null;
+  position: number;
+};
+
+export type Duplicate = {
+  value: string;
+  pages: PageFinding[];
+};
+
+export type LargeImage = {
+  pageId: number;
+  title: string;
+  attachmentId: number;
+  originalFilename: string;
+  bytes: number;
+};
+
+export type VaultHealthReport = {
+  brokenLinks: BrokenLink[];
+  /** Pages with no tags and no links to or from other pages */
+  orphanPages: PageFinding[];
+  untitledPages: PageFinding[];
+  emptyPages: PageFinding[];
+  duplicateTitles: Duplicate[];
+  duplicateFilenames: Duplicate[];
+  largeImages: LargeImage[];
+  largeImageBytes: number;
+};
+
+type GetVaultHealthReportCommand = {
+  cmd: "get_vault_health_report";
+  args: { largeImageBytes?: number };
+  result: VaultHealthReport;
+};
+
+type ExportVaultHealthReportCommand = {
+  cmd: "export_vault_health_report";
+  args: { path: string; largeImageBytes?: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+/** A relation as seen from one of its pages */
+export type PageRelation = {
+  id: number;
+  /** "parent", "peer" or any other type */
+  relationType: string;
+  directed: boolean;
+  /** Whether the relation points away from this page; for "parent", that this page is the parent */
+  outgoing: boolean;
+  /** The page on the other end */
+  pageId: number;
+  title: string;
+  filename: string;
+};
+
+type AddPageRelationCommand = {
+  cmd: "add_page_relation";
+  args: { sourcePageId: number; targetPageId: number; relationType: string; directed?: boolean };
+  result: PageRelation[];
+};
+
+type RemovePageRelationCommand = {
+  cmd: "remove_page_relation";
+  args: { relationId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type ListPageRelationsCommand = {
+  cmd: "list_page_relations";
+  args: { pageId: number };
+  result: PageRelation[];
+};
+
+type ExportVaultJsonCommand = {
+  cmd: "export_vault_json";
+  args: { path: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+export type FleetingPage = {
+  id: number;
+  title: string;
+  expiresAt: string;
+};
+
+/** Payload of the "fleeting_pages_archived" event; `restore_page` brings a page back */
+export type FleetingPagesArchived = {
+  pages: { id: number; title: string }[];
+};
+
+type MarkPageFleetingCommand = {
+  cmd: "mark_page_fleeting";
+  args: { pageId: number; lifetimeDays?: number };
+  /** The expiry date */
+  result: string;
+};
+
+type MakePagePermanentCommand = {
+  cmd: "make_page_permanent";
+  args: { pageId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type ListFleetingPagesCommand = {
+  cmd: "list_fleeting_pages";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: FleetingPage[];
+};
+
+/** A page in the "Resurfaced" queue; the "pages_resurfaced" event carries a list of these */
+export type ResurfacedPage = {
+  pageId: number;
+  title: string;
+  filename: string;
+  resurfacedAt: string;
+};
+
+export type UpcomingResurface = {
+  pageId: number;
+  title: string;
+  filename: string;
+  resurfaceAt: string;
+};
+
+type SetResurfaceDateCommand = {
+  cmd: "set_resurface_date";
+  /** Any date SQLite understands, e.g. an ISO 8601 string; null clears it */
+  args: { pageId: number; resurfaceAt: string | null };
+  /** The date as stored, in UTC */
+  result: string | null;
+};
+
+type ListUpcomingResurfacingCommand = {
+  cmd: "list_upcoming_resurfacing";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: UpcomingResurface[];
+};
+
+type ListResurfacedPagesCommand = {
+  cmd: "list_resurfaced_pages";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: ResurfacedPage[];
+};
+
+type SnoozeResurfacedPageCommand = {
+  cmd: "snooze_resurfaced_page";
+  args: { pageId: number; days?: number };
+  /** The date the page resurfaces again */
+  result: string;
+};
+
+type DismissResurfacedPageCommand = {
+  cmd: "dismiss_resurfaced_page";
+  args: { pageId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+/** Weeks start on Monday */
+export type StatsGranularity = "day" | "week" | "month";
+
+export type ActivityPoint = {
+  /** First day of the period, "YYYY-MM-DD" in local time */
+  period: string;
+  created: number;
+  /** Pages whose text was edited in the period */
+  updated: number;
+  /** Pages in the trash that went there in the period */
+  archived: number;
+  totalCreated: number;
+  /** Pages that existed and weren't in the trash at the end of the period */
+  totalPages: number;
+};
+
+export type PeriodCount = {
+  period: string;
+  count: number;
+};
+
+export type VaultStats = {
+  granularity: StatsGranularity;
+  /** Periods with no activity are left out */
+  activity: ActivityPoint[];
+  /** How many pages had each of the most used tags, at the end of each period it was added in */
+  tagCounts: { tag: string; points: PeriodCount[] }[];
+  /** Words added to pages in each period, netted per page and day; trashed pages aren't counted */
+  wordsWritten: PeriodCount[];
+  mostViewed: { pageId: number; title: string; viewCount: number; lastViewedAt: string | null }[];
+};
+
+type GetVaultStatsCommand = {
+  cmd: "get_vault_stats";
+  args: { granularity?: StatsGranularity };
+  result: VaultStats;
+};
+
+/** An area ("10-19"), category ("11") or ID ("11.01") */
+export type Classification = {
+  id: number;
+  code: string;
+  title: string;
+  parentId: number | null;
+  /** Pages in this classification itself, not counting ones below it */
+  pageCount: number;
+};
+
+export type ClassificationOutlineNode = {
+  id: number;
+  code: string;
+  title: string;
+  pages: { pageId: number; title: string; filename: string }[];
+  children: ClassificationOutlineNode[];
+};
+
+type CreateClassificationCommand = {
+  cmd: "create_classification";
+  args: { code: string; title: string };
+  /** The new classification's ID */
+  result: number;
+};
+
+type RenameClassificationCommand = {
+  cmd: "rename_classification";
+  args: { classificationId: number; title: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type RenumberClassificationCommand = {
+  cmd: "renumber_classification";
+  args: { classificationId: number; code: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type MoveClassificationCommand = {
+  cmd: "move_classification";
+  args: { classificationId: number; parentId: number };
+  /** The new code */
+  result: string;
+};
+
+type DeleteClassificationCommand = {
+  cmd: "delete_classification";
+  args: { classificationId: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type ListClassificationsCommand = {
+  cmd: "list_classifications";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: Classification[];
+};
+
+type SetPageClassificationCommand = {
+  cmd: "set_page_classification";
+  args: { pageId: number; classificationId: number | null };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type GetClassificationOutlineCommand = {
+  cmd: "get_classification_outline";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: ClassificationOutlineNode[];
+};
+
+type ExportClassificationOutlineCommand = {
+  cmd: "export_classification_outline";
+  args: { path: string; format: "markdown" | "opml" };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+export type TagAlias = {
+  alias: string;
+  /** The tag the alias stands for */
+  tag: string;
+};
+
+/** A level of the nested tag hierarchy, e.g. "website" in "project/website" */
+export type TagNode = {
+  name: string;
+  tag: string;
+  /** False for a level that only exists because tags are nested under it */
+  exists: boolean;
+  pageCount: number;
+  /** Pages tagged with this tag or one nested under it */
+  totalPageCount: number;
+  children: TagNode[];
+};
+
+type RenameTagCommand = {
+  cmd: "rename_tag";
+  args: { tag: string; newName: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type MergeTagsCommand = {
+  cmd: "merge_tags";
+  args: { tags: string[]; into: string; keepAsAliases?: boolean };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type AddTagAliasCommand = {
+  cmd: "add_tag_alias";
+  args: { alias: string; tag: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type RemoveTagAliasCommand = {
+  cmd: "remove_tag_alias";
+  args: { alias: string };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+type ListTagAliasesCommand = {
+  cmd: "list_tag_aliases";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: TagAlias[];
+};
+
+type GetTagTreeCommand = {
+  cmd: "get_tag_tree";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: TagNode[];
+};
+
+export type TagEdge = {
+  source: string;
+  target: string;
+  coOccurrences: number;
+  jaccard: number;
+  /** Above 0 when the tags are used together more often than chance, 1 when always */
+  npmi: number;
+};
+
+export type ClusterTag = {
+  tag: string;
+  pageCount: number;
+};
+
+export type ClusterPage = {
+  pageId: number;
+  title: string;
+  filename: string;
+  matchingTags: number;
+};
+
+export type TagCluster = {
+  name: string;
+  tags: ClusterTag[];
+  pages: ClusterPage[];
+};
+
+/** Also emitted with the `tag_clusters_updated` event */
+export type TagGraph = {
+  edges: TagEdge[];
+  clusters: TagCluster[];
+};
+
+type GetTagClustersCommand = {
+  cmd: "get_tag_clusters";
+  args: { refresh?: boolean };
+  result: TagGraph;
+};
+
+export type SchemaInfo = {
+  appVersion: string;
+  schemaVersion: number;
+  latestSchemaVersion: number;
+};
+
+type GetSchemaInfoCommand = {
+  cmd: "get_schema_info";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: SchemaInfo;
+};
+
+type DowngradeDatabaseCommand = {
+  cmd: "downgrade_database";
+  args: { targetVersion: number };
+  // biome-ignore lint/suspicious/noConfusingVoidType: It has no return value
+  result: void;
+};
+
+export type VaultInfo = {
+  name: string;
+  path: string;
+  isCurrent: boolean;
+  isDefault: boolean;
+  encrypted: boolean;
+  lastOpenedAt: string | null;
+};
+
+type GetCurrentVaultCommand = {
+  cmd: "get_current_vault";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: VaultInfo;
+};
+
+type ListVaultsCommand = {
+  cmd: "list_vaults";
+  // biome-ignore lint/complexity/noBannedTypes: It has no arguments
+  args: {};
+  result: VaultInfo[];
+};
+
+type CreateVaultCommand = {
+  cmd: "create_vault";
+  args: { name: string; path: string };
+  result: VaultInfo;
+};
+
+type OpenVaultCommand = {
+  cmd: "open_vault";
+  args: { path: string; passphrase?: string };
+  result: VaultInfo;
+};
+
+type SwitchVaultCommand = {
+  cmd: "switch_vault";
+  args: { path: string; passphrase?: string };
+  result: VaultInfo;
+};
+
+type CloseVaultCommand = {
+  cmd: "close_vault";
+  args: { passphrase?: string };
+  result: VaultInfo;
+};
+
+type InvokeCommand =
+  | SyncToDirectoryCommand
+  | UpdateEditorStateCommand
+  | DisableEditorMenusCommand
+  | MovePageToTrashCommand
+  | ListTrashedPagesCommand
+  | RestorePageCommand
+  | DeletePagePermanentlyCommand
+  | RunMaintenanceCommand
+  | CreateImageAttachmentCommand
+  | GetImageAttachmentCommand
+  | GetImageThumbnailCommand
+  | RecompressAllImagesCommand
+  | BackUpNowCommand
+  | ListSnapshotsCommand
+  | RestoreSnapshotCommand
+  | CheckDatabaseHealthCommand
+  | RepairDatabaseCommand
+  | GetStorageReportCommand
+  | CompactDatabaseCommand
+  | GetDatabaseStatusCommand
+  | UnlockDatabaseCommand
+  | EnableEncryptionCommand
+  | DisableEncryptionCommand
+  | ChangePassphraseCommand
+  | SearchPagesCommand
+  | FuzzyFindPagesCommand
+  | GetSearchIndexSettingsCommand
+  | SetSearchTokenizerCommand
+  | ReindexSearchCommand
+  | GetBacklinksCommand
+  | GetVaultHealthReportCommand
+  | ExportVaultHealthReportCommand
+  | AddPageRelationCommand
+  | RemovePageRelationCommand
+  | ListPageRelationsCommand
+  | ExportVaultJsonCommand
+  | MarkPageFleetingCommand
+  | MakePagePermanentCommand
+  | ListFleetingPagesCommand
+  | SetResurfaceDateCommand
+  | ListUpcomingResurfacingCommand
+  | ListResurfacedPagesCommand
+  | SnoozeResurfacedPageCommand
+  | DismissResurfacedPageCommand
+  | GetVaultStatsCommand
+  | CreateClassificationCommand
+  | RenameClassificationCommand
+  | RenumberClassificationCommand
+  | MoveClassificationCommand
+  | DeleteClassificationCommand
+  | ListClassificationsCommand
+  | SetPageClassificationCommand
+  | GetClassificationOutlineCommand
+  | ExportClassificationOutlineCommand
+  | RenameTagCommand
+  | MergeTagsCommand
+  | AddTagAliasCommand
+  | RemoveTagAliasCommand
+  | ListTagAliasesCommand
+  | GetTagTreeCommand
+  | GetTagClustersCommand
+  | GetSchemaInfoCommand
+  | DowngradeDatabaseCommand
+  | GetCurrentVaultCommand
+  | ListVaultsCommand
+  | CreateVaultCommand
+  | OpenVaultCommand
+  | SwitchVaultCommand
+  | CloseVaultCommand;
+
+type CommandNamed<C extends InvokeCommand["cmd"]> = Extract<InvokeCommand, { cmd: C }>;
 
-export default async function invoke<T extends InvokeCommand>(
-  cmd: T["cmd"],
-  args?: T["args"],
+export default async function invoke<C extends InvokeCommand["cmd"]>(
+  cmd: C,
+  args?: CommandNamed<C>["args"],
   options?: InvokeOptions,
-): Promise<T["result"]> {
+): Promise<CommandNamed<C>["result"]> {
   return await tauriInvoke(cmd, args, options);
 }
@@ -0,0 +1,99 @@
+import invoke from "@/bridge/ts2tauri/typedInvoke";
+import AppTheme from "@/components/AppTheme";
+import { Box, Button, Flex, Text, TextField } from "@radix-ui/themes";
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
+
+type GateState = "checking" | "locked" | "unlocked" | "failed";
+
+/**
+ * Renders its children only once the database is open. An encrypted database
+ * isn't opened at launch, so this asks for the passphrase first. A database that
+ * couldn't be opened, e.g. one from a newer version of the app, shows why instead.
+ */
+export default function DatabaseUnlockGate({ children }: { children: React.ReactNode }) {
+  const [state, setState] = useState<GateState>("checking");
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const [isUnlocking, setIsUnlocking] = useState(false);
+
+  useEffect(() => {
+    invoke("get_database_status").then((status) => {
+      if (status.unlocked) {
+        setState("unlocked");
+      } else if (status.error && !status.encrypted) {
+        setError(status.error);
+        setState("failed");
+      } else {
+        setState("locked");
+      }
+    });
+
+    // Another window may unlock the database first
+    const unlisten = listen("database_unlocked", () => setState("unlocked"));
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const handleUnlock = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setIsUnlocking(true);
+    try {
+      await invoke("unlock_database", { passphrase });
+      setState("unlocked");
+    } catch (err) {
+      setError(typeof err === "string" ? err : "Failed to unlock the database");
+    } finally {
+      setIsUnlocking(false);
+    }
+  };
+
+  if (state === "unlocked") return <>{children}</>;
+  if (state === "checking") return null;
+
+  if (state === "failed") {
+    return (
+      <AppTheme>
+        <Flex align="center" justify="center" style={{ minHeight: "100vh" }}>
+          <Box p="4" style={{ width: "100%", maxWidth: 360 }}>
+            <Text as="p" size="2" color="red">
+              {error}
+            </Text>
+          </Box>
+        </Flex>
+      </AppTheme>
+    );
+  }
+
+  return (
+    <AppTheme>
+      <Flex align="center" justify="center" style={{ minHeight: "100vh" }}>
+        <Box p="4" style={{ width: "100%", maxWidth: 360 }}>
+          <form onSubmit={handleUnlock}>
+            <Text as="p" size="2" mb="2" weight="medium">
+              Enter your passphrase to unlock your notes
+            </Text>
+            <Flex gap="3">
+              <TextField.Root
+                type="password"
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                placeholder="Passphrase"
+                size="3"
+                style={{ width: "100%" }}
+                autoFocus
+              />
+              <Button type="submit" size="3" disabled={!passphrase || isUnlocking}>
+                Unlock
+              </Button>
+            </Flex>
+            <Text as="p" size="2" color="red" mt="1" style={{ height: "1.5em" }}>
+              {error}
+            </Text>
+          </form>
+        </Box>
+      </Flex>
+    </AppTheme>
+  );
+}
@@ -0,0 +1,5 @@
+import invoke, { type Backlink } from "@/bridge/ts2tauri/typedInvoke";
+
+export async function getBacklinks(pageId: number): Promise<Backlink[]> {
+  return invoke("get_backlinks", { pageId });
+}
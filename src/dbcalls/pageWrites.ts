@@ -16,6 +16,8 @@ export async function updatePageViewedAt(id: number): Promise<void> {
 export async function upsertPageContent(page: PageData, editorState: EditorState, title: string): Promise<PageData> {
   const db = await getDB();
   const plainText = editorState.read(() => $getRoot().getTextContent() ?? "");
+  // Counted like Rust's split_whitespace (see stats_commands.rs)
+  const wordCount = plainText.split(/\s+/).filter(Boolean).length;
   const serializedState = JSON.stringify(editorState.toJSON());
 
   await populatePageExportCache(editorState);
@@ -34,10 +36,10 @@ export async function upsertPageContent(page: PageData, editorState: EditorState
     // For new or non-existent pages, do a simple insert
     result = await execute(
       db,
-      `INSERT INTO pages (id, title, filename, lexical_json, plain_text, markdown_text)
-       VALUES ($1, $2, $3, $4, $5, $6)
+      `INSERT INTO pages (id, title, filename, lexical_json, plain_text, markdown_text, word_count)
+       VALUES ($1, $2, $3, $4, $5, $6, $7)
        RETURNING id`,
-      [page.id, title, filename, serializedState, plainText, markdownText],
+      [page.id, title, filename, serializedState, plainText, markdownText, wordCount],
     );
   } else {
     // For existing pages, do an explicit update
@@ -49,10 +51,11 @@ export async function upsertPageContent(page: PageData, editorState: EditorState
            lexical_json = $4,
            plain_text = $5,
            markdown_text = $6,
+           word_count = $7,
            updated_at = CURRENT_TIMESTAMP
        WHERE id = $1
        RETURNING id`,
-      [page.id, title, filename, serializedState, plainText, markdownText],
+      [page.id, title, filename, serializedState, plainText, markdownText, wordCount],
     );
 
     if (!result.rowsAffected) {
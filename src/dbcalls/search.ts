@@ -1,53 +1,12 @@
-import type { DBPage } from "@/dbcalls/types";
-import { getDB } from "@/services/foundation/db";
-import { select } from "@/services/foundation/db";
-import type { PageData } from "@/types";
+import invoke, { type SearchResults, type TitleMatch } from "@/bridge/ts2tauri/typedInvoke";
 
-export async function fuzzyFindPagesByTitle(query: string): Promise<PageData[]> {
-  const db = await getDB();
-  // Convert query 'abc' into '%a%b%c%' pattern
-  const fuzzyQuery = `%${query.split("").join("%")}%`;
-
-  const results = await select<DBPage[]>(
-    db,
-    `SELECT *
-     FROM pages
-     WHERE title LIKE $1
-     AND archived_at IS NULL
-     ORDER BY last_viewed_at DESC NULLS LAST, title ASC LIMIT 100`,
-    [fuzzyQuery],
-  );
-
-  return results.map((dbPage) => ({
-    id: dbPage.id,
-    title: dbPage.title,
-    filename: dbPage.filename,
-    lexicalState: JSON.parse(dbPage.lexical_json),
-  }));
+export async function fuzzyFindPagesByTitle(query: string): Promise<TitleMatch[]> {
+  return invoke("fuzzy_find_pages", { query });
 }
 
-export async function searchPages(query: string, titleOnly = false): Promise<PageData[]> {
-  const sqliteQuery = titleOnly ? `title:${query}` : query;
-  const db = await getDB();
-
-  const results = await select<DBPage[]>(
-    db,
-    `SELECT p.*, fts.rank
-     FROM pages p
-     JOIN (
-       SELECT rowid, rank
-       FROM pages_fts
-       WHERE pages_fts MATCH $1
-     ) AS fts ON p.id = fts.rowid
-     WHERE p.archived_at IS NULL
-     ORDER BY fts.rank LIMIT 100`,
-    [sqliteQuery],
-  );
-
-  return results.map((dbPage) => ({
-    id: dbPage.id,
-    title: dbPage.title,
-    filename: dbPage.filename,
-    lexicalState: JSON.parse(dbPage.lexical_json),
-  }));
+export async function searchPages(
+  query: string,
+  { titleOnly = false, limit, offset }: { titleOnly?: boolean; limit?: number; offset?: number } = {},
+): Promise<SearchResults> {
+  return invoke("search_pages", { query, titleOnly, limit, offset });
 }
@@ -38,11 +38,12 @@ export async function setPageTags(pageId: number, tags: string[]): Promise<void>
     lowerTags,
   );
 
-  // Get the IDs of all tags we want to set
+  // Get the IDs of all tags we want to set, with aliases resolved to the tags they stand for
   const tagIds = await select<{ id: number }[]>(
     db,
-    `SELECT id FROM tags WHERE tag IN (${lowerTags.map((_, i) => `$${i + 1}`).join(", ")})
-    ORDER BY tag`, // Ensure consistent order
+    `SELECT DISTINCT COALESCE(alias_of, id) AS id FROM tags
+    WHERE tag IN (${lowerTags.map((_, i) => `$${i + 1}`).join(", ")})
+    ORDER BY id`, // Ensure consistent order
     lowerTags,
   );
 
@@ -65,6 +66,7 @@ export async function setPageTags(pageId: number, tags: string[]): Promise<void>
   );
 }
 
+/** Finds pages tagged with `tag`, the tag it's an alias of, or a tag nested under it */
 export async function findPagesByTag(tag: string): Promise<PageData[]> {
   const db = await getDB();
   const result = await select<DBPage[]>(
@@ -74,6 +76,8 @@ export async function findPagesByTag(tag: string): Promise<PageData[]> {
      JOIN tag_associations ta ON ta.page_id = p.id
      JOIN tags t ON t.id = ta.tag_id
      WHERE t.tag = $1
+        OR substr(t.tag, 1, length($1) + 1) = $1 || '/'
+        OR t.id = (SELECT alias_of FROM tags WHERE tag = $1)
      ORDER BY p.title
      LIMIT 100`,
     [tag.toLowerCase()],
@@ -100,7 +104,8 @@ export async function fuzzyFindTags(query: string): Promise<{ tag: string; count
     `SELECT t.tag, COUNT(DISTINCT ta.page_id) as count
      FROM tags t
      LEFT JOIN tag_associations ta ON ta.tag_id = t.id
-     WHERE t.tag LIKE $1
+     WHERE t.alias_of IS NULL
+       AND (t.tag LIKE $1 OR EXISTS (SELECT 1 FROM tags a WHERE a.alias_of = t.id AND a.tag LIKE $1))
      GROUP BY t.id, t.tag
      ORDER BY count DESC, t.tag ASC
      LIMIT 100`,
@@ -123,10 +128,11 @@ export async function getPopularTags(): Promise<{ tag: string; count: number }[]
 
 export async function getAllTags(): Promise<string[]> {
   const db = await getDB();
-  const results = await select<{ tag: string }[]>(db, "SELECT DISTINCT tag FROM tags ORDER BY tag ASC");
+  const results = await select<{ tag: string }[]>(db, "SELECT DISTINCT tag FROM tags WHERE alias_of IS NULL ORDER BY tag ASC");
   return results.map((r) => r.tag);
 }
 
+/** Deletes tags with no pages. Aliases, and tags with aliases, are kept. */
 export async function cleanupOrphanedTags(): Promise<number> {
   const db = await getDB();
   const result = await execute(
@@ -136,7 +142,8 @@ export async function cleanupOrphanedTags(): Promise<number> {
        SELECT t.id 
        FROM tags t
        LEFT JOIN tag_associations ta ON ta.tag_id = t.id
-       WHERE ta.page_id IS NULL
+       WHERE ta.page_id IS NULL AND t.alias_of IS NULL
+         AND NOT EXISTS (SELECT 1 FROM tags a WHERE a.alias_of = t.id)
      )`,
   );
   return result.rowsAffected;
@@ -1,9 +1,12 @@
+import DatabaseUnlockGate from "@/components/DatabaseUnlockGate";
 import CollectionsWindow from "@/featuregroups/windows/collections/CollectionsWindow";
 import React from "react";
 import ReactDOM from "react-dom/client";
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
-    <CollectionsWindow />
+    <DatabaseUnlockGate>
+      <CollectionsWindow />
+    </DatabaseUnlockGate>
   </React.StrictMode>,
 );
@@ -1,3 +1,4 @@
+import DatabaseUnlockGate from "@/components/DatabaseUnlockGate";
 import PageWindow from "@/featuregroups/windows/page/PageWindow";
 import React from "react";
 import ReactDOM from "react-dom/client";
@@ -5,7 +6,9 @@ import ReactDOM from "react-dom/client";
 // biome-ignore lint/style/noNonNullAssertion: it's totally there
 ReactDOM.createRoot(document.getElementById("root")!).render(
   <React.StrictMode>
-    <PageWindow />
+    <DatabaseUnlockGate>
+      <PageWindow />
+    </DatabaseUnlockGate>
   </React.StrictMode>,
 );
 
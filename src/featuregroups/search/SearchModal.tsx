@@ -1,8 +1,8 @@
+import type { MatchRange } from "@/bridge/ts2tauri/typedInvoke";
 import { listPages } from "@/dbcalls/pages";
 import { fuzzyFindPagesByTitle } from "@/dbcalls/search";
 import { navigateToPage } from "@/services/windowRouting";
 import { openModalsAtom, searchModalStateAtom } from "@/state/modalState";
-import type { PageData } from "@/types";
 import { Link1Icon, MagnifyingGlassIcon } from "@radix-ui/react-icons";
 import { Box, Dialog, Flex, ScrollArea, Text, TextField, VisuallyHidden } from "@radix-ui/themes";
 import { useAtom, useAtomValue } from "jotai";
@@ -11,10 +11,29 @@ import "./SearchModal.css";
 import { dispatchEditorCommand } from "@/state/editorState";
 import { INSERT_INTERNAL_LINK_COMMAND } from "../texteditor/plugins/internallink/commands";
 
+type PageOption = {
+  id: number;
+  title: string;
+  /** Parts of the title that matched the query */
+  matches?: MatchRange[];
+};
+
+function HighlightedTitle({ title, matches = [] }: { title: string; matches?: MatchRange[] }) {
+  const parts = [];
+  let position = 0;
+  for (const { start, end } of matches) {
+    if (start > position) parts.push(title.slice(position, start));
+    parts.push(<mark key={start}>{title.slice(start, end)}</mark>);
+    position = end;
+  }
+  parts.push(title.slice(position));
+  return <Text className="SearchModal__title">{parts}</Text>;
+}
+
 export default function SearchModal() {
   const [openModals, setOpenModals] = useAtom(openModalsAtom);
   const searchModalState = useAtomValue(searchModalStateAtom);
-  const [pages, setPages] = useState<PageData[]>([]);
+  const [pages, setPages] = useState<PageOption[]>([]);
   const [searchQuery, setSearchQuery] = useState("");
   const [selectedIndex, setSelectedIndex] = useState(0);
 
@@ -134,7 +153,11 @@ export default function SearchModal() {
                   <Text color="gray" weight="medium">
                     {page.id}.
                   </Text>
-                  <Text>{page.title || "Untitled"}</Text>
+                  {page.title ? (
+                    <HighlightedTitle title={page.title} matches={page.matches} />
+                  ) : (
+                    <Text>Untitled</Text>
+                  )}
                 </Flex>
               ))}
             </Box>
@@ -5,7 +5,6 @@ import PageWindowContents from "@/featuregroups/windows/page/PageWindowContents"
 import { useEditorMenu } from "@/featuregroups/windows/page/menu";
 import useDeriveLinksFromEditorState from "@/featuregroups/windows/page/useDeriveLinksFromEditorState";
 import { useUpdatePageFromEditorState } from "@/featuregroups/windows/page/useUpdatePageFromEditorState";
-import { useCleanupUnusedImagesOnMountAndUnmount } from "@/flowHooks/useCleanupUnusedImagesOnMountAndUnmount";
 import useKeepWindowTitleUpdated from "@/flowHooks/useKeepWindowTitleUpdated";
 import performBoot from "@/flows/performBoot";
 import useLoadPagesAsNeeded from "@/jankysync/hooks/useLoadPagesAsNeeded";
@@ -25,7 +24,6 @@ export default function PageWindow() {
   useUpdateWindowFocus();
   useLoadPagesAsNeeded();
   useKeepWindowTitleUpdated();
-  useCleanupUnusedImagesOnMountAndUnmount();
   useDeriveLinksFromEditorState();
   useUpdatePageFromEditorState();
 
@@ -0,0 +1,126 @@
+import invoke, { type DatabaseStatus } from "@/bridge/ts2tauri/typedInvoke";
+import { Box, Button, Flex, Text, TextField } from "@radix-ui/themes";
+import { useEffect, useState } from "react";
+
+export default function EncryptionSettings() {
+  const [status, setStatus] = useState<DatabaseStatus | null>(null);
+  const [currentPassphrase, setCurrentPassphrase] = useState("");
+  const [newPassphrase, setNewPassphrase] = useState("");
+  const [confirmPassphrase, setConfirmPassphrase] = useState("");
+  const [message, setMessage] = useState<{ text: string; isError: boolean } | null>(null);
+  const [isWorking, setIsWorking] = useState(false);
+
+  const loadStatus = () => invoke("get_database_status").then(setStatus);
+
+  useEffect(() => {
+    loadStatus();
+  }, []);
+
+  const run = async (action: () => Promise<void>, success: string) => {
+    setIsWorking(true);
+    try {
+      await action();
+      setMessage({ text: success, isError: false });
+      setCurrentPassphrase("");
+      setNewPassphrase("");
+      setConfirmPassphrase("");
+      await loadStatus();
+    } catch (err) {
+      setMessage({ text: typeof err === "string" ? err : "Something went wrong", isError: true });
+    } finally {
+      setIsWorking(false);
+    }
+  };
+
+  if (!status) return null;
+
+  const passphrasesMatch = newPassphrase !== "" && newPassphrase === confirmPassphrase;
+
+  return (
+    <Box style={{ width: "100%", marginTop: "20px" }}>
+      <Text as="p" size="2" mb="2" weight="medium">
+        Encryption
+      </Text>
+      <Text as="p" size="2" color="gray" mb="2">
+        {status.encrypted
+          ? "Your notes are encrypted. You'll be asked for the passphrase each time NoteSponge starts."
+          : "Encrypt your notes with a passphrase. There is no way to recover a forgotten passphrase."}
+      </Text>
+      {status.unencryptedImageCount > 0 && (
+        <Text as="p" size="2" color="orange" mb="2">
+          {`Images aren't encrypted: your ${status.unencryptedImageCount} images and their thumbnails stay readable on disk.`}
+        </Text>
+      )}
+      <Flex direction="column" gap="2">
+        {status.encrypted && (
+          <TextField.Root
+            type="password"
+            value={currentPassphrase}
+            onChange={(e) => setCurrentPassphrase(e.target.value)}
+            placeholder="Current passphrase"
+            size="3"
+          />
+        )}
+        <TextField.Root
+          type="password"
+          value={newPassphrase}
+          onChange={(e) => setNewPassphrase(e.target.value)}
+          placeholder="New passphrase"
+          size="3"
+        />
+        <TextField.Root
+          type="password"
+          value={confirmPassphrase}
+          onChange={(e) => setConfirmPassphrase(e.target.value)}
+          placeholder="Confirm new passphrase"
+          size="3"
+        />
+        <Flex gap="3">
+          {status.encrypted ? (
+            <>
+              <Button
+                size="3"
+                disabled={isWorking || !currentPassphrase || !passphrasesMatch}
+                onClick={() =>
+                  run(
+                    () => invoke("change_passphrase", { currentPassphrase, newPassphrase }),
+                    "Passphrase changed",
+                  )
+                }
+              >
+                Change Passphrase
+              </Button>
+              <Button
+                size="3"
+                variant="soft"
+                color="red"
+                disabled={isWorking || !currentPassphrase}
+                onClick={() =>
+                  run(() => invoke("disable_encryption", { passphrase: currentPassphrase }), "Encryption turned off")
+                }
+              >
+                Turn Off Encryption
+              </Button>
+            </>
+          ) : (
+            <Button
+              size="3"
+              disabled={isWorking || !passphrasesMatch}
+              onClick={() =>
+                run(
+                  () => invoke("enable_encryption", { passphrase: newPassphrase, allowUnencryptedImages: true }),
+                  "Notes encrypted",
+                )
+              }
+            >
+              Encrypt Notes
+            </Button>
+          )}
+        </Flex>
+        <Text size="2" color={message?.isError ? "red" : "green"} style={{ height: "1.5em" }}>
+          {message?.text}
+        </Text>
+      </Flex>
+    </Box>
+  );
+}
@@ -0,0 +1,69 @@
+import invoke, { type SearchIndexSettings, type SearchTokenizer } from "@/bridge/ts2tauri/typedInvoke";
+import { Box, Button, Flex, Select, Text } from "@radix-ui/themes";
+import { useEffect, useState } from "react";
+
+const TOKENIZER_LABELS: Record<SearchTokenizer, string> = {
+  standard: "Most languages (matches word forms, ignores accents)",
+  cjk: "Chinese, Japanese and Korean",
+};
+
+export default function SearchSettings() {
+  const [settings, setSettings] = useState<SearchIndexSettings | null>(null);
+  const [message, setMessage] = useState<{ text: string; isError: boolean } | null>(null);
+  const [isReindexing, setIsReindexing] = useState(false);
+
+  useEffect(() => {
+    invoke("get_search_index_settings").then(setSettings);
+  }, []);
+
+  const handleTokenizerChange = async (tokenizer: SearchTokenizer) => {
+    setMessage(null);
+    setSettings(await invoke("set_search_tokenizer", { tokenizer }));
+  };
+
+  const handleReindex = async () => {
+    setIsReindexing(true);
+    try {
+      setSettings(await invoke("reindex_search"));
+      setMessage({ text: "Search index rebuilt", isError: false });
+    } catch (err) {
+      setMessage({ text: typeof err === "string" ? err : "Something went wrong", isError: true });
+    } finally {
+      setIsReindexing(false);
+    }
+  };
+
+  if (!settings) return null;
+
+  const needsReindex = settings.tokenizer !== settings.indexedTokenizer;
+
+  return (
+    <Box style={{ width: "100%", marginTop: "20px" }}>
+      <Text as="p" size="2" mb="2" weight="medium">
+        Search Language
+      </Text>
+      <Flex gap="3">
+        <Select.Root
+          value={settings.tokenizer}
+          onValueChange={(value) => handleTokenizerChange(value as SearchTokenizer)}
+          size="3"
+        >
+          <Select.Trigger style={{ flexGrow: 1 }} />
+          <Select.Content>
+            {Object.entries(TOKENIZER_LABELS).map(([value, label]) => (
+              <Select.Item key={value} value={value}>
+                {label}
+              </Select.Item>
+            ))}
+          </Select.Content>
+        </Select.Root>
+        <Button size="3" disabled={isReindexing} onClick={handleReindex}>
+          {isReindexing ? "Reindexing…" : "Reindex"}
+        </Button>
+      </Flex>
+      <Text as="p" size="2" mt="1" color={message?.isError ? "red" : "gray"} style={{ height: "1.5em" }}>
+        {needsReindex ? "Reindex to search with the new language setting." : message?.text}
+      </Text>
+    </Box>
+  );
+}
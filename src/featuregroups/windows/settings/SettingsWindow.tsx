@@ -1,5 +1,8 @@
 import { getTauriSettingsStore } from "@/bridge/tauri2ts/tauriSettingsStore";
 import AppTheme from "@/components/AppTheme";
+import EncryptionSettings from "@/featuregroups/windows/settings/EncryptionSettings";
+import SearchSettings from "@/featuregroups/windows/settings/SearchSettings";
+import VaultSettings from "@/featuregroups/windows/settings/VaultSettings";
 import { useSettingsMenu } from "@/featuregroups/windows/settings/menu";
 import { useDisableEditorMenuOnFocus } from "@/flowHooks/windowFocusHooks";
 import { resetLLMClient } from "@/services/foundation/llm";
@@ -156,6 +159,9 @@ export default function SettingsWindow() {
                 </Flex>
               </Form.Field>
             </Form.Root>
+            <VaultSettings />
+            <SearchSettings />
+            <EncryptionSettings />
           </Box>
         </Flex>
       </AppTheme>
@@ -0,0 +1,109 @@
+import invoke, { type VaultInfo } from "@/bridge/ts2tauri/typedInvoke";
+import { Box, Button, Flex, Text, TextField } from "@radix-ui/themes";
+import { open } from "@tauri-apps/plugin-dialog";
+import { useEffect, useState } from "react";
+
+export default function VaultSettings() {
+  const [vaults, setVaults] = useState<VaultInfo[]>([]);
+  const [newVaultName, setNewVaultName] = useState("");
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const [isWorking, setIsWorking] = useState(false);
+
+  const loadVaults = () => invoke("list_vaults").then(setVaults);
+
+  useEffect(() => {
+    loadVaults();
+  }, []);
+
+  // Switching reloads this window, so there's nothing to do on success
+  const run = async (action: () => Promise<unknown>) => {
+    setIsWorking(true);
+    setError(null);
+    try {
+      await action();
+    } catch (err) {
+      setError(typeof err === "string" ? err : "Something went wrong");
+      await loadVaults();
+    } finally {
+      setIsWorking(false);
+    }
+  };
+
+  const pickDirectory = async () => {
+    const selected = await open({ directory: true, multiple: false });
+    return typeof selected === "string" ? selected : null;
+  };
+
+  const handleCreate = () =>
+    run(async () => {
+      const path = await pickDirectory();
+      if (path) await invoke("create_vault", { name: newVaultName, path });
+    });
+
+  const handleOpen = () =>
+    run(async () => {
+      const path = await pickDirectory();
+      if (path) await invoke("open_vault", { path, passphrase: passphrase || undefined });
+    });
+
+  const handleSwitch = (vault: VaultInfo) =>
+    run(() => invoke("switch_vault", { path: vault.path, passphrase: passphrase || undefined }));
+
+  return (
+    <Box style={{ width: "100%", marginTop: "20px" }}>
+      <Text as="p" size="2" mb="2" weight="medium">
+        Vaults
+      </Text>
+      <Flex direction="column" gap="2">
+        {vaults.map((vault) => (
+          <Flex key={vault.path} gap="3" align="center" justify="between">
+            <Box style={{ minWidth: 0 }}>
+              <Text as="p" size="2">
+                {vault.name}
+                {vault.encrypted && " (encrypted)"}
+              </Text>
+              <Text as="p" size="1" color="gray" truncate>
+                {vault.path}
+              </Text>
+            </Box>
+            {vault.isCurrent ? (
+              <Text size="2" color="gray">
+                Open
+              </Text>
+            ) : (
+              <Button size="2" variant="soft" disabled={isWorking} onClick={() => handleSwitch(vault)}>
+                Switch
+              </Button>
+            )}
+          </Flex>
+        ))}
+        <TextField.Root
+          type="password"
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+          placeholder="Passphrase (encrypted vaults only)"
+          size="3"
+        />
+        <Flex gap="3">
+          <TextField.Root
+            value={newVaultName}
+            onChange={(e) => setNewVaultName(e.target.value)}
+            placeholder="New vault name"
+            size="3"
+            style={{ width: "100%" }}
+          />
+          <Button size="3" disabled={isWorking || !newVaultName.trim()} onClick={handleCreate}>
+            Create
+          </Button>
+          <Button size="3" variant="soft" disabled={isWorking} onClick={handleOpen}>
+            Open…
+          </Button>
+        </Flex>
+        <Text size="2" color="red" style={{ height: "1.5em" }}>
+          {error}
+        </Text>
+      </Flex>
+    </Box>
+  );
+}
@@ -29,5 +29,6 @@ export default async function performSyncToDirectory() {
     showToast("Success", "Synced to directory", { type: "background" });
   } catch (err) {
     console.error("Sync error:", err);
+    showToast("Sync failed", String(err));
   }
 }
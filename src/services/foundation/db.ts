@@ -4,7 +4,9 @@ let dbInstance: Database | null = null;
 
 export async function getDB() {
   if (!dbInstance) {
-    dbInstance = await Database.load("sqlite:notesponge.db");
+    // The database is opened (and unlocked, if encrypted) in Rust, so only get a handle
+    // to it here. Database.load() would open a second, unkeyed connection.
+    dbInstance = Database.get("sqlite:notesponge.db");
   }
   return dbInstance;
 }